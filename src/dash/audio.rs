@@ -0,0 +1,118 @@
+use crate::dash::manifest::{AdaptationSet, Label, Manifest};
+use crate::dash::message::{get_reference_path, get_required_parameter, to_descriptors};
+use crate::language;
+use crate::mp4::MediaInfo;
+use crate::utils::{load_xml_file, processing_error, save_xml_file};
+use crate::DashManifestParameters;
+use mcai_worker_sdk::{job::JobResult, MessageError};
+
+pub fn add_audio_track(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<(), MessageError> {
+  let audio_path = get_required_parameter(&job_result, &parameters.audio_path, "audio_path")?;
+
+  let media_info =
+    MediaInfo::read(audio_path).map_err(|message| processing_error(&job_result, &message))?;
+
+  if media_info.handler != "soun" {
+    return Err(processing_error(
+      &job_result,
+      &format!("{} does not contain an audio track", audio_path),
+    ));
+  }
+  if media_info.index_range.is_none() {
+    return Err(processing_error(
+      &job_result,
+      &format!(
+        "{} is not packaged with the DASH on-demand profile (missing sidx box)",
+        audio_path
+      ),
+    ));
+  }
+
+  let audio_language = parameters
+    .audio_language
+    .clone()
+    .or_else(|| media_info.language.clone())
+    .unwrap_or_else(|| "und".to_string());
+  let audio_language = language::normalize(&audio_language, &parameters.language_convention);
+  let audio_role = parameters
+    .audio_role
+    .clone()
+    .unwrap_or_else(|| "alternate".to_string());
+
+  let reference_audio_path = get_reference_path(&job_result, &parameters.source_path, audio_path)?;
+
+  let mut manifest: Manifest = load_xml_file(&job_result, &parameters.source_path)?;
+
+  if let Some(url) = &parameters.reference_url {
+    manifest.prefix_urls(url);
+  }
+
+  if parameters.replace.unwrap_or(false) {
+    manifest.remove_adaptation_set(&audio_language, &audio_role);
+  }
+
+  let mut adaptation_set = AdaptationSet::new_audio(
+    &reference_audio_path,
+    &audio_language,
+    &audio_role,
+    &media_info,
+  );
+
+  for accessibility in to_descriptors(&parameters.audio_accessibility) {
+    adaptation_set.add_accessibility(accessibility);
+  }
+  if let Some(label) = &parameters.audio_label {
+    adaptation_set.add_label(Label::new(label, Some(&audio_language)));
+  }
+
+  manifest.add_adaptation_set(adaptation_set);
+  manifest.normalize_languages(&parameters.language_convention);
+
+  let destination_manifest_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+  save_xml_file(&job_result, &manifest, destination_manifest_path)
+}
+
+#[test]
+fn add_audio_description_track() {
+  use crate::{DashAction, DescriptorParameters};
+  use std::fs;
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::AddAudio),
+    source_path: "tests/sample_3.mpd".to_string(),
+    audio_path: Some("tests/sample_audio_dashinit.mp4".to_string()),
+    audio_role: Some("description".to_string()),
+    audio_accessibility: Some(vec![DescriptorParameters {
+      scheme_id_uri: "urn:tva:metadata:cs:AudioPurposeCS:2007".to_string(),
+      value: Some("1".to_string()),
+    }]),
+    destination_path: Some("tests/sample_3_audio.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_audio_track(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_3_audio.mpd").unwrap();
+  assert!(content.contains(r#"<mpd:AdaptationSet segmentAlignment="true" lang="eng" subsegmentAlignment="true" subsegmentStartsWithSAP="1" contentType="audio"><Accessibility schemeIdUri="urn:tva:metadata:cs:AudioPurposeCS:2007" value="1" /><Role schemeIdUri="urn:mpeg:dash:role:2011" value="description" />"#));
+  assert!(content.contains(r#"mpd:mimeType="audio/mp4" mpd:codecs="mp4a.40.2" mpd:audioSamplingRate="48000" mpd:startWithSAP="1" mpd:bandwidth="3519""#));
+  assert!(content.contains(r#"<mpd:AudioChannelConfiguration schemeIdUri="urn:mpeg:dash:23003:3:audio_channel_configuration:2011" value="2" />"#));
+  assert!(content.contains(r#"<mpd:BaseURL>sample_audio_dashinit.mp4</mpd:BaseURL><mpd:SegmentBase indexRangeExact="true" indexRange="603-670" timescale="48000"><Initialization range="0-602" /></mpd:SegmentBase>"#));
+}
+
+#[test]
+fn add_audio_track_requires_audio_file() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_3.mpd".to_string(),
+    audio_path: Some("tests/sample_subtitle.ttml".to_string()),
+    destination_path: Some("tests/sample_3_audio_error.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(add_audio_track(job_result, parameters).is_err());
+}
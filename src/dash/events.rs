@@ -0,0 +1,227 @@
+use crate::dash::manifest::{Event, EventStream, Manifest};
+use crate::utils::{encode_base64, load_xml_file, processing_error, save_xml_file};
+use crate::{scte35, DashManifestParameters};
+use mcai_worker_sdk::{job::JobResult, MessageError};
+
+const SCTE35_SCHEME_ID_URI: &str = "urn:scte:scte35:2014:xml+bin";
+
+pub fn add_splice_cues(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<(), MessageError> {
+  let cues = parameters
+    .cues
+    .as_ref()
+    .filter(|cues| !cues.is_empty())
+    .ok_or_else(|| processing_error(&job_result, "missing cues parameter"))?;
+
+  let mut manifest: Manifest = load_xml_file(&job_result, &parameters.source_path)?;
+
+  if parameters.replace.unwrap_or(false) {
+    manifest.remove_event_stream(SCTE35_SCHEME_ID_URI);
+  }
+
+  // ids follow the existing splice events, so that they do not collide
+  let first_id = manifest
+    .get_max_event_id(SCTE35_SCHEME_ID_URI)
+    .map_or(1, |max_id| max_id + 1);
+  if first_id + cues.len() as u64 > u32::MAX as u64 {
+    return Err(processing_error(&job_result, "too many splice events"));
+  }
+
+  let bounds = manifest.get_period_bounds();
+  let mut event_streams: Vec<Option<EventStream>> = bounds.iter().map(|_| None).collect();
+  for (index, cue) in cues.iter().enumerate() {
+    if !cue.time.is_finite()
+      || cue.time < 0.0
+      || cue
+        .duration
+        .map_or(false, |duration| !duration.is_finite() || duration <= 0.0)
+    {
+      return Err(processing_error(
+        &job_result,
+        &format!("invalid splice cue at {}s", cue.time),
+      ));
+    }
+
+    // the last Period starting before the cue, which must not be over
+    let period_index = bounds
+      .iter()
+      .rposition(|(start, _)| *start <= cue.time)
+      .filter(|period_index| {
+        let (start, duration) = bounds[*period_index];
+        duration.map_or(true, |duration| cue.time < start + duration)
+      })
+      .ok_or_else(|| {
+        processing_error(
+          &job_result,
+          &format!("splice cue at {}s is out of the presentation", cue.time),
+        )
+      })?;
+    let period_start = bounds[period_index].0;
+
+    let id = (first_id + index as u64) as u32;
+    // the splice time stays on the presentation timeline, the event being timed from its Period
+    let splice_time = (cue.time * scte35::TIMESCALE as f64).round() as u64;
+    let presentation_time = ((cue.time - period_start) * scte35::TIMESCALE as f64).round() as u64;
+    let duration = cue
+      .duration
+      .map(|duration| (duration * scte35::TIMESCALE as f64).round() as u64);
+    let splice_info_section = scte35::splice_insert(id, splice_time, duration);
+
+    event_streams[period_index]
+      .get_or_insert_with(|| EventStream::new(SCTE35_SCHEME_ID_URI, scte35::TIMESCALE))
+      .add_event(Event::new_splice(
+        id as u64,
+        presentation_time,
+        duration,
+        &encode_base64(&splice_info_section),
+      ));
+  }
+  for (period_index, event_stream) in event_streams.into_iter().enumerate() {
+    if let Some(event_stream) = event_stream {
+      manifest.add_event_stream(period_index, event_stream);
+    }
+  }
+
+  let destination_manifest_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+  save_xml_file(&job_result, &manifest, destination_manifest_path)
+}
+
+#[test]
+fn add_splice_cues_to_manifest() {
+  use crate::{CueParameters, DashAction};
+  use std::fs;
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::AddSpliceCues),
+    source_path: "tests/sample_1.mpd".to_string(),
+    cues: Some(vec![
+      CueParameters {
+        time: 10.0,
+        duration: Some(30.0),
+      },
+      CueParameters {
+        time: 60.5,
+        duration: None,
+      },
+    ]),
+    destination_path: Some("tests/sample_1_splice_cues.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_splice_cues(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_splice_cues.mpd").unwrap();
+  assert!(content.contains(r#"<Period duration="PT0H2M6.520S"><mpd:EventStream schemeIdUri="urn:scte:scte35:2014:xml+bin" timescale="90000"><mpd:Event presentationTime="900000" duration="2700000" id="1"><scte35:Signal xmlns:scte35="http://www.scte.org/schemas/35/2016"><scte35:Binary>"#));
+  assert!(content.contains(r#"<mpd:Event presentationTime="5445000" id="2">"#));
+
+  let binary = encode_base64(&scte35::splice_insert(1, 900_000, Some(2_700_000)));
+  assert!(content.contains(&format!("<scte35:Binary>{}</scte35:Binary>", binary)));
+
+  // cues added to the previous ones are numbered after them
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1_splice_cues.mpd".to_string(),
+    cues: Some(vec![CueParameters {
+      time: 90.0,
+      duration: None,
+    }]),
+    destination_path: Some("tests/sample_1_more_splice_cues.mpd".to_string()),
+    ..Default::default()
+  };
+  add_splice_cues(JobResult::new(666), parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_more_splice_cues.mpd").unwrap();
+  assert!(content.contains(r#"<mpd:Event presentationTime="5445000" id="2">"#));
+  assert!(content.contains(r#"<mpd:Event presentationTime="8100000" id="3">"#));
+  let binary = encode_base64(&scte35::splice_insert(3, 8_100_000, None));
+  assert!(content.contains(&format!("<scte35:Binary>{}</scte35:Binary>", binary)));
+}
+
+#[test]
+fn add_splice_cues_rejects_invalid_cues() {
+  use crate::CueParameters;
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    cues: Some(vec![CueParameters {
+      time: -1.0,
+      duration: None,
+    }]),
+    destination_path: Some("tests/sample_1_invalid_cues.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(add_splice_cues(job_result, parameters).is_err());
+
+  let invalid_cues = vec![
+    (f64::NAN, None),
+    (f64::INFINITY, None),
+    (10.0, Some(f64::NAN)),
+    (10.0, Some(f64::INFINITY)),
+    // after the end of the presentation
+    (200.0, None),
+  ];
+  for (time, duration) in invalid_cues {
+    let parameters = DashManifestParameters {
+      source_path: "tests/sample_1.mpd".to_string(),
+      cues: Some(vec![CueParameters { time, duration }]),
+      destination_path: Some("tests/sample_1_invalid_cues.mpd".to_string()),
+      ..Default::default()
+    };
+    assert!(add_splice_cues(JobResult::new(666), parameters).is_err());
+  }
+}
+
+#[test]
+fn add_splice_cues_to_periods() {
+  use crate::dash::periods::split_periods;
+  use crate::CueParameters;
+  use std::fs;
+
+  let cues = |times: &[(f64, Option<f64>)]| {
+    times
+      .iter()
+      .map(|(time, duration)| CueParameters {
+        time: *time,
+        duration: *duration,
+      })
+      .collect()
+  };
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4.mpd".to_string(),
+    cues: Some(cues(&[(10.0, None), (20.0, None)])),
+    destination_path: Some("tests/sample_4_periods.mpd".to_string()),
+    ..Default::default()
+  };
+  split_periods(JobResult::new(666), parameters).unwrap();
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4_periods.mpd".to_string(),
+    cues: Some(cues(&[(5.0, None), (25.0, Some(2.0))])),
+    destination_path: Some("tests/sample_4_periods_splice_cues.mpd".to_string()),
+    ..Default::default()
+  };
+  add_splice_cues(JobResult::new(666), parameters).unwrap();
+
+  // the events are timed from the start of their Period
+  let content = fs::read_to_string("tests/sample_4_periods_splice_cues.mpd").unwrap();
+  assert!(content.contains(r#"<Period id="1-1" start="PT0H0M0.000S" duration="PT0H0M10.000S"><mpd:EventStream schemeIdUri="urn:scte:scte35:2014:xml+bin" timescale="90000"><mpd:Event presentationTime="450000" id="1">"#));
+  assert!(content.contains(
+    r#"<Period id="1-2" start="PT0H0M10.000S" duration="PT0H0M10.000S"><mpd:AdaptationSet"#
+  ));
+  assert!(content.contains(r#"<Period id="1-3" start="PT0H0M20.000S" duration="PT0H0M10.000S"><mpd:EventStream schemeIdUri="urn:scte:scte35:2014:xml+bin" timescale="90000"><mpd:Event presentationTime="450000" duration="180000" id="2">"#));
+  let binary = encode_base64(&scte35::splice_insert(2, 2_250_000, Some(180_000)));
+  assert!(content.contains(&format!("<scte35:Binary>{}</scte35:Binary>", binary)));
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4_periods.mpd".to_string(),
+    cues: Some(cues(&[(30.0, None)])),
+    destination_path: Some("tests/sample_4_periods_invalid_cues.mpd".to_string()),
+    ..Default::default()
+  };
+  assert!(add_splice_cues(JobResult::new(666), parameters).is_err());
+}
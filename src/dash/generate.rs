@@ -0,0 +1,189 @@
+use crate::dash::manifest::{AdaptationSet, Manifest};
+use crate::dash::message::get_reference_path;
+use crate::language;
+use crate::mp4::MediaInfo;
+use crate::utils::{processing_error, save_xml_file};
+use crate::DashManifestParameters;
+use mcai_worker_sdk::{job::JobResult, MessageError};
+
+struct Track {
+  content_type: &'static str,
+  language: String,
+  role: String,
+  path: String,
+  media_info: MediaInfo,
+}
+
+impl Track {
+  /// Tracks of an AdaptationSet must share the same content, language, role and codec family
+  fn is_switchable_with(&self, other: &Track) -> bool {
+    self.content_type == other.content_type
+      && self.language == other.language
+      && self.role == other.role
+      && self.media_info.codecs.split('.').next() == other.media_info.codecs.split('.').next()
+  }
+}
+
+pub fn generate_manifest(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<(), MessageError> {
+  let media_files = parameters
+    .media_files
+    .as_ref()
+    .filter(|media_files| !media_files.is_empty())
+    .ok_or_else(|| processing_error(&job_result, "missing media_files parameter"))?;
+
+  let destination_manifest_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+
+  let mut tracks = vec![];
+  for media_file in media_files {
+    let media_info = MediaInfo::read(&media_file.path)
+      .map_err(|message| processing_error(&job_result, &message))?;
+
+    if media_info.index_range.is_none() {
+      return Err(processing_error(
+        &job_result,
+        &format!(
+          "{} is not packaged with the DASH on-demand profile (missing sidx box)",
+          media_file.path
+        ),
+      ));
+    }
+
+    let content_type = match media_info.handler.as_str() {
+      "vide" => "video",
+      "soun" => "audio",
+      "subt" | "text" | "sbtl" => "text",
+      handler => {
+        return Err(processing_error(
+          &job_result,
+          &format!("{}: unsupported {} track", media_file.path, handler),
+        ))
+      }
+    };
+
+    tracks.push(Track {
+      content_type,
+      language: language::normalize(
+        &media_file
+          .language
+          .clone()
+          .or_else(|| media_info.language.clone())
+          .unwrap_or_else(|| "und".to_string()),
+        &parameters.language_convention,
+      ),
+      role: media_file
+        .role
+        .clone()
+        .unwrap_or_else(|| "main".to_string()),
+      path: get_reference_path(&job_result, destination_manifest_path, &media_file.path)?,
+      media_info,
+    });
+  }
+
+  let duration = tracks
+    .iter()
+    .map(|track| track.media_info.get_duration())
+    .fold(0.0, f64::max);
+  let max_segment_duration = tracks
+    .iter()
+    .map(|track| track.media_info.get_max_segment_duration())
+    .fold(0.0, f64::max);
+
+  // video first, then audio and text, each one ordered by bandwidth
+  let content_types = ["video", "audio", "text"];
+  tracks.sort_by_key(|track| {
+    (
+      content_types
+        .iter()
+        .position(|content_type| *content_type == track.content_type),
+      track.media_info.get_bandwidth(),
+    )
+  });
+
+  let mut groups: Vec<(&Track, AdaptationSet)> = vec![];
+  let mut counters = [0; 3];
+  for track in &tracks {
+    let index = content_types
+      .iter()
+      .position(|content_type| *content_type == track.content_type)
+      .unwrap_or_default();
+    counters[index] += 1;
+    let id = format!("{}{}", ["v", "a", "s"][index], counters[index]);
+
+    let position = groups
+      .iter()
+      .position(|(reference, _)| reference.is_switchable_with(track));
+    let adaptation_set = match position {
+      Some(position) => &mut groups[position].1,
+      None => {
+        groups.push((
+          track,
+          AdaptationSet::new(track.content_type, &track.language, &track.role),
+        ));
+        &mut groups.last_mut().unwrap().1
+      }
+    };
+    adaptation_set.add_media_representation(&id, &track.path, &track.media_info);
+  }
+
+  let mut manifest = Manifest::new_on_demand(duration, max_segment_duration);
+  for (_, adaptation_set) in groups {
+    manifest.add_adaptation_set(adaptation_set);
+  }
+
+  save_xml_file(&job_result, &manifest, destination_manifest_path)
+}
+
+#[test]
+fn generate_on_demand_manifest() {
+  use crate::{DashAction, MediaFileParameters};
+  use std::fs;
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::Generate),
+    source_path: "tests/generated.mpd".to_string(),
+    media_files: Some(vec![
+      MediaFileParameters {
+        path: "tests/sample_subtitle_dashinit.mp4".to_string(),
+        language: None,
+        role: Some("subtitle".to_string()),
+      },
+      MediaFileParameters {
+        path: "tests/sample_audio_dashinit.mp4".to_string(),
+        language: Some("fra".to_string()),
+        role: None,
+      },
+      MediaFileParameters {
+        path: "tests/sample_video_dashinit.mp4".to_string(),
+        language: None,
+        role: None,
+      },
+    ]),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  generate_manifest(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/generated.mpd").unwrap();
+  assert!(content.contains(r#"<mpd:MPD xmlns:mpd="urn:mpeg:dash:schema:mpd:2011" minBufferTime="PT1.500S" mediaPresentationDuration="PT0H0M6.000S" maxSegmentDuration="PT0H0M2.000S" profiles="urn:mpeg:dash:profile:isoff-on-demand:2011" type="static"><Period duration="PT0H0M6.000S">"#));
+  assert!(content.contains(r#"<mpd:AdaptationSet segmentAlignment="true" maxWidth="1280" maxHeight="720" lang="und" subsegmentAlignment="true" subsegmentStartsWithSAP="1" contentType="video"><Role schemeIdUri="urn:mpeg:dash:role:2011" value="main" /><mpd:Representation mpd:id="v1" mpd:mimeType="video/mp4" mpd:codecs="avc1.64001F" mpd:width="1280" mpd:height="720""#));
+  assert!(content.contains(r#"<mpd:AdaptationSet segmentAlignment="true" lang="fra" subsegmentAlignment="true" subsegmentStartsWithSAP="1" contentType="audio"><Role schemeIdUri="urn:mpeg:dash:role:2011" value="main" /><mpd:Representation mpd:id="a1" mpd:mimeType="audio/mp4" mpd:codecs="mp4a.40.2""#));
+  assert!(content.contains(r#"<mpd:AdaptationSet segmentAlignment="true" lang="fra" subsegmentAlignment="true" subsegmentStartsWithSAP="1" contentType="text"><Role schemeIdUri="urn:mpeg:dash:role:2011" value="subtitle" /><mpd:Representation mpd:id="s1" mpd:mimeType="application/mp4" mpd:codecs="stpp""#));
+  assert!(content.contains(r#"<mpd:BaseURL>sample_video_dashinit.mp4</mpd:BaseURL><mpd:SegmentBase indexRangeExact="true" indexRange="643-710" timescale="12800"><Initialization range="0-642" /></mpd:SegmentBase>"#));
+}
+
+#[test]
+fn generate_manifest_requires_media_files() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/generated_empty.mpd".to_string(),
+    media_files: Some(vec![]),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(generate_manifest(job_result, parameters).is_err());
+}
@@ -0,0 +1,611 @@
+use crate::dash::manifest::{AdaptationSet, Manifest, Period, Representation};
+use crate::hls::playlist::{
+  ByteRange, Map, MediaPlaylist, MediaSegment, MediaType, MultivariantPlaylist, Rendition,
+  VariantStream,
+};
+use crate::mp4::MediaInfo;
+use crate::utils::{load_xml_file, processing_error, rebase_url};
+use crate::{language, DashManifestParameters, LanguageConvention};
+use mcai_worker_sdk::{job::JobResult, warn, MessageError};
+use std::fs;
+use std::path::Path;
+
+/// Track of the presentation, described by its representation in the first Period
+struct Track<'a> {
+  content_type: String,
+  adaptation_set: &'a AdaptationSet,
+  representation: &'a Representation,
+  uri: String,
+}
+
+/// Audio renditions a variant stream can switch to
+struct AudioGroup {
+  group_id: String,
+  bandwidth: u64,
+  codecs: Vec<String>,
+  /// Playlist of the default rendition
+  uri: String,
+}
+
+pub fn convert_to_hls(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<(), MessageError> {
+  let manifest: Manifest = load_xml_file(&job_result, &parameters.source_path)?;
+
+  let playlist_path = parameters.destination_path.clone().unwrap_or_else(|| {
+    Path::new(&parameters.source_path)
+      .with_extension("m3u8")
+      .to_string_lossy()
+      .to_string()
+  });
+  let playlist_folder = Path::new(&playlist_path)
+    .parent()
+    .unwrap_or_else(|| Path::new(""));
+  let playlist_name = Path::new(&playlist_path)
+    .file_stem()
+    .and_then(|file_stem| file_stem.to_str())
+    .unwrap_or("playlist");
+
+  let first_period = manifest
+    .get_periods()
+    .first()
+    .ok_or_else(|| processing_error(&job_result, "missing Period in the manifest"))?;
+
+  let mut version = 3;
+  let mut tracks = vec![];
+  for adaptation_set in first_period.get_adaptation_sets() {
+    let content_type = adaptation_set.get_content_type().unwrap_or_default();
+    let mut representations: Vec<&Representation> =
+      adaptation_set.get_representations().iter().collect();
+    // renditions of audio and text sets are not switchable, the best one is kept
+    if content_type != "video" {
+      representations
+        .sort_by_key(|representation| std::cmp::Reverse(representation.get_bandwidth()));
+      representations.truncate(1);
+    }
+
+    for representation in representations {
+      let segments = match get_segments(
+        &job_result,
+        &manifest,
+        adaptation_set,
+        representation,
+        &parameters.source_path,
+        &playlist_path,
+      )? {
+        Some(segments) => segments,
+        None => {
+          warn!(
+            "unsupported {} representation {} is skipped",
+            content_type,
+            representation.get_id()
+          );
+          continue;
+        }
+      };
+
+      // fragmented MP4 segments are supported since the version 7
+      let media_version = if segments.iter().any(|segment| segment.map.is_some()) {
+        7
+      } else {
+        3
+      };
+      version = version.max(media_version);
+
+      let uri = format!("{}_{}.m3u8", playlist_name, representation.get_id());
+      let media_playlist = MediaPlaylist::new_vod(media_version, segments);
+      fs::write(playlist_folder.join(&uri), media_playlist.to_string())
+        .map_err(|e| processing_error(&job_result, &e.to_string()))?;
+
+      tracks.push(Track {
+        content_type: content_type.clone(),
+        adaptation_set,
+        representation,
+        uri,
+      });
+    }
+  }
+
+  let mut multivariant_playlist = MultivariantPlaylist {
+    version: Some(version),
+    independent_segments: true,
+    ..Default::default()
+  };
+  add_renditions(&mut multivariant_playlist, &tracks);
+  add_variant_streams(&mut multivariant_playlist, &tracks);
+
+  if multivariant_playlist.variant_streams.is_empty() {
+    return Err(processing_error(
+      &job_result,
+      "no video or audio track to convert",
+    ));
+  }
+
+  fs::write(&playlist_path, multivariant_playlist.to_string())
+    .map_err(|e| processing_error(&job_result, &e.to_string()))
+}
+
+/// Segments of the representation of the first Period, and of the matching ones of the next
+/// Periods, separated by discontinuities
+fn get_segments(
+  job_result: &JobResult,
+  manifest: &Manifest,
+  first_adaptation_set: &AdaptationSet,
+  first_representation: &Representation,
+  manifest_path: &str,
+  playlist_path: &str,
+) -> Result<Option<Vec<MediaSegment>>, MessageError> {
+  let periods = manifest.get_periods();
+  let mut segments = vec![];
+  let bounds = manifest.get_period_bounds();
+  for (index, (period, (_, duration))) in periods.iter().zip(bounds).enumerate() {
+    let (adaptation_set, representation) = find_matching_representation(
+      &periods[0],
+      period,
+      first_adaptation_set,
+      first_representation,
+    )
+    .ok_or_else(|| {
+      processing_error(
+        job_result,
+        &format!(
+          "representation {} is missing from the Period {}",
+          first_representation.get_id(),
+          index + 1
+        ),
+      )
+    })?;
+    let duration = duration
+      .ok_or_else(|| processing_error(job_result, "unknown duration of the presentation"))?;
+
+    let period_segments = match get_period_segments(
+      job_result,
+      adaptation_set,
+      representation,
+      duration,
+      manifest_path,
+      playlist_path,
+    )? {
+      Some(period_segments) => period_segments,
+      None => return Ok(None),
+    };
+    for (position, mut segment) in period_segments.into_iter().enumerate() {
+      segment.discontinuity = index > 0 && position == 0;
+      segments.push(segment);
+    }
+  }
+  Ok(Some(segments))
+}
+
+/// Representation of the Period matching the one of the first Period, whatever their ids as the
+/// Periods may come from different sources: same rank among the similar adaptation sets, and same
+/// bandwidth rank, or the lowest bandwidth of a shorter ladder
+fn find_matching_representation<'a>(
+  first_period: &Period,
+  period: &'a Period,
+  first_adaptation_set: &AdaptationSet,
+  first_representation: &Representation,
+) -> Option<(&'a AdaptationSet, &'a Representation)> {
+  let adaptation_set_rank = get_similar_adaptation_sets(first_period, first_adaptation_set)
+    .iter()
+    .position(|adaptation_set| std::ptr::eq(*adaptation_set, first_adaptation_set))?;
+  let representation_rank = get_ladder(first_adaptation_set)
+    .iter()
+    .position(|representation| std::ptr::eq(*representation, first_representation))?;
+
+  let adaptation_set =
+    *get_similar_adaptation_sets(period, first_adaptation_set).get(adaptation_set_rank)?;
+  let ladder = get_ladder(adaptation_set);
+  let representation = ladder.get(representation_rank).or_else(|| ladder.last())?;
+  Some((adaptation_set, *representation))
+}
+
+/// Adaptation sets of the Period with the content type and the language of the adaptation set
+fn get_similar_adaptation_sets<'a>(
+  period: &'a Period,
+  adaptation_set: &AdaptationSet,
+) -> Vec<&'a AdaptationSet> {
+  period
+    .get_adaptation_sets()
+    .iter()
+    .filter(|other| {
+      other.get_content_type() == adaptation_set.get_content_type()
+        && language::is_same_language(other.get_language(), adaptation_set.get_language())
+    })
+    .collect()
+}
+
+/// Representations of the adaptation set, from the highest bandwidth
+fn get_ladder(adaptation_set: &AdaptationSet) -> Vec<&Representation> {
+  let mut representations: Vec<&Representation> =
+    adaptation_set.get_representations().iter().collect();
+  representations.sort_by_key(|representation| std::cmp::Reverse(representation.get_bandwidth()));
+  representations
+}
+
+fn get_period_segments(
+  job_result: &JobResult,
+  adaptation_set: &AdaptationSet,
+  representation: &Representation,
+  period_duration: f64,
+  manifest_path: &str,
+  playlist_path: &str,
+) -> Result<Option<Vec<MediaSegment>>, MessageError> {
+  if let Some(segment_template) = adaptation_set.get_segment_template(representation) {
+    let map = segment_template
+      .get_initialization_url(representation)
+      .map(|url| Map {
+        uri: rebase_url(&url, manifest_path, playlist_path),
+        byte_range: None,
+      });
+
+    let segments = segment_template
+      .get_media_segments(representation, period_duration)
+      .into_iter()
+      .map(|(url, duration)| MediaSegment {
+        duration,
+        map: map.clone(),
+        uri: rebase_url(&url, manifest_path, playlist_path),
+        ..Default::default()
+      })
+      .collect();
+    return Ok(Some(segments));
+  }
+
+  let base_url = match representation.get_base_url() {
+    Some(base_url) => base_url,
+    None => return Ok(None),
+  };
+  let uri = rebase_url(base_url, manifest_path, playlist_path);
+
+  // byte ranges of on-demand files are described by their segment index
+  if representation.has_segment_base() {
+    if base_url.contains("://") {
+      return Err(processing_error(
+        job_result,
+        &format!("unable to read the segment index of {}", base_url),
+      ));
+    }
+    let media_path = Path::new(manifest_path)
+      .parent()
+      .unwrap_or_else(|| Path::new(""))
+      .join(base_url);
+    let media_info = MediaInfo::read(&media_path.to_string_lossy())
+      .map_err(|message| processing_error(job_result, &message))?;
+
+    let (initialization_start, initialization_end) = media_info.initialization_range;
+    let map = Map {
+      uri: uri.clone(),
+      byte_range: Some(ByteRange::new(initialization_start, initialization_end)),
+    };
+    let timescale = media_info.timescale as f64;
+    let start = representation.get_presentation_time_offset();
+    let end = start + period_duration;
+
+    let segments = media_info
+      .segments
+      .iter()
+      .filter(|segment| {
+        let segment_start = segment.start as f64 / timescale;
+        let segment_end = segment_start + segment.duration as f64 / timescale;
+        segment_end > start && segment_start < end
+      })
+      .map(|segment| MediaSegment {
+        duration: segment.duration as f64 / timescale,
+        byte_range: Some(ByteRange::new(
+          segment.offset,
+          segment.offset + segment.size - 1,
+        )),
+        map: Some(map.clone()),
+        uri: uri.clone(),
+        ..Default::default()
+      })
+      .collect();
+    return Ok(Some(segments));
+  }
+
+  // WebVTT sidecar files are referenced as a single segment
+  if representation.get_mime_type() == Some("text/vtt") || base_url.ends_with(".vtt") {
+    return Ok(Some(vec![MediaSegment {
+      duration: period_duration,
+      uri,
+      ..Default::default()
+    }]));
+  }
+  Ok(None)
+}
+
+fn add_renditions(multivariant_playlist: &mut MultivariantPlaylist, tracks: &[Track]) {
+  // main tracks are selected by default
+  let mut tracks: Vec<&Track> = tracks
+    .iter()
+    .filter(|track| track.content_type == "audio" || track.content_type == "text")
+    .collect();
+  tracks.sort_by_key(|track| !track.adaptation_set.has_role("main"));
+
+  for track in tracks {
+    let (media_type, group_id) = get_rendition_group(track);
+    let is_first = !multivariant_playlist
+      .renditions
+      .iter()
+      .any(|rendition| rendition.group_id == group_id);
+
+    let name = track
+      .adaptation_set
+      .get_label()
+      .unwrap_or_else(|| track.adaptation_set.get_language())
+      .to_string();
+    let mut unique_name = name.clone();
+    let mut index = 1;
+    while multivariant_playlist
+      .renditions
+      .iter()
+      .any(|rendition| rendition.group_id == group_id && rendition.name == unique_name)
+    {
+      index += 1;
+      unique_name = format!("{} {}", name, index);
+    }
+
+    // HLS expects BCP 47 language tags, whatever the DASH convention
+    let language = Some(track.adaptation_set.get_language())
+      .filter(|language| !language.is_empty() && *language != "und")
+      .map(|language| language::normalize(language, &Some(LanguageConvention::Bcp47)));
+
+    let characteristics = if track.adaptation_set.has_role("description") {
+      Some("public.accessibility.describes-video".to_string())
+    } else if track.adaptation_set.has_role("caption") {
+      Some(
+        "public.accessibility.transcribes-spoken-dialog,public.accessibility.describes-music-and-sound"
+          .to_string(),
+      )
+    } else {
+      None
+    };
+
+    multivariant_playlist.renditions.push(Rendition {
+      default: is_first && media_type == MediaType::Audio,
+      autoselect: true,
+      forced: track.adaptation_set.has_role("forced-subtitle"),
+      instream_id: None,
+      media_type,
+      group_id,
+      name: unique_name,
+      language,
+      characteristics,
+      channels: track
+        .representation
+        .get_audio_channels()
+        .map(|channels| channels.to_string()),
+      uri: Some(track.uri.clone()),
+      other_attributes: vec![],
+    });
+  }
+}
+
+fn add_variant_streams(multivariant_playlist: &mut MultivariantPlaylist, tracks: &[Track]) {
+  let mut audio_groups: Vec<AudioGroup> = vec![];
+  let mut subtitles_codecs = vec![];
+  for track in tracks {
+    let codecs = track
+      .representation
+      .get_codecs()
+      .unwrap_or_default()
+      .to_string();
+    match track.content_type.as_str() {
+      "audio" => {
+        let (_, group_id) = get_rendition_group(track);
+        match audio_groups
+          .iter_mut()
+          .find(|audio_group| audio_group.group_id == group_id)
+        {
+          Some(audio_group) => {
+            audio_group.bandwidth = audio_group
+              .bandwidth
+              .max(track.representation.get_bandwidth());
+            if !audio_group.codecs.contains(&codecs) {
+              audio_group.codecs.push(codecs);
+            }
+          }
+          None => audio_groups.push(AudioGroup {
+            group_id,
+            bandwidth: track.representation.get_bandwidth(),
+            codecs: vec![codecs],
+            uri: track.uri.clone(),
+          }),
+        }
+      }
+      "text" if !codecs.is_empty() && !subtitles_codecs.contains(&codecs) => {
+        subtitles_codecs.push(codecs)
+      }
+      _ => {}
+    }
+  }
+  let subtitles = if tracks.iter().any(|track| track.content_type == "text") {
+    Some("subtitles".to_string())
+  } else {
+    None
+  };
+
+  let video_tracks: Vec<&Track> = tracks
+    .iter()
+    .filter(|track| track.content_type == "video")
+    .collect();
+
+  // audio only presentation
+  if video_tracks.is_empty() {
+    for audio_group in &audio_groups {
+      multivariant_playlist.variant_streams.push(VariantStream {
+        bandwidth: audio_group.bandwidth,
+        codecs: Some(audio_group.codecs.join(",")),
+        audio: Some(audio_group.group_id.clone()),
+        subtitles: subtitles.clone(),
+        uri: audio_group.uri.clone(),
+        ..Default::default()
+      });
+    }
+    return;
+  }
+
+  for video_track in video_tracks {
+    let video_codecs = video_track
+      .representation
+      .get_codecs()
+      .map(|codecs| vec![codecs.to_string()])
+      .unwrap_or_default();
+    let groups: Vec<Option<&AudioGroup>> = if audio_groups.is_empty() {
+      vec![None]
+    } else {
+      audio_groups.iter().map(Some).collect()
+    };
+
+    for group in groups {
+      let mut codecs = video_codecs.clone();
+      let mut bandwidth = video_track.representation.get_bandwidth();
+      if let Some(audio_group) = group {
+        bandwidth += audio_group.bandwidth;
+        codecs.extend(audio_group.codecs.iter().cloned());
+      }
+      codecs.extend(subtitles_codecs.iter().cloned());
+
+      multivariant_playlist.variant_streams.push(VariantStream {
+        bandwidth,
+        codecs: Some(codecs.join(",")).filter(|codecs| !codecs.is_empty()),
+        resolution: video_track.representation.get_resolution(),
+        frame_rate: video_track
+          .representation
+          .get_frame_rate()
+          .map(|frame_rate| frame_rate as f64),
+        audio: group.map(|audio_group| audio_group.group_id.clone()),
+        subtitles: subtitles.clone(),
+        uri: video_track.uri.clone(),
+        ..Default::default()
+      });
+    }
+  }
+}
+
+/// Audio renditions are grouped by codec family, a variant stream only referencing decodable ones
+fn get_rendition_group(track: &Track) -> (MediaType, String) {
+  if track.content_type == "text" {
+    return (MediaType::Subtitles, "subtitles".to_string());
+  }
+  let codecs = track.representation.get_codecs().unwrap_or("audio");
+  (
+    MediaType::Audio,
+    format!("audio-{}", codecs.split('.').next().unwrap_or_default()),
+  )
+}
+
+#[test]
+fn convert_on_demand_manifest() {
+  use crate::DashAction;
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::ConvertToHls),
+    source_path: "tests/sample_5.mpd".to_string(),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  convert_to_hls(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_5.m3u8").unwrap();
+  assert_eq!(
+    content,
+    r#"#EXTM3U
+#EXT-X-VERSION:7
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="audio-mp4a",NAME="fra",LANGUAGE="fr",DEFAULT=YES,AUTOSELECT=YES,CHANNELS="2",URI="sample_5_a1.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="audio-mp4a",NAME="Audiodescription",LANGUAGE="fr",DEFAULT=NO,AUTOSELECT=YES,CHARACTERISTICS="public.accessibility.describes-video",CHANNELS="2",URI="sample_5_a2.m3u8"
+#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID="subtitles",NAME="fra",LANGUAGE="fr",DEFAULT=NO,AUTOSELECT=YES,FORCED=NO,URI="sample_5_s1.m3u8"
+#EXT-X-STREAM-INF:BANDWIDTH=7091,CODECS="avc1.64001F,mp4a.40.2,stpp",RESOLUTION=1280x720,FRAME-RATE=25.000,AUDIO="audio-mp4a",SUBTITLES="subtitles"
+sample_5_v1.m3u8
+"#
+  );
+
+  let content = fs::read_to_string("tests/sample_5_a1.m3u8").unwrap();
+  assert_eq!(
+    content,
+    r#"#EXTM3U
+#EXT-X-VERSION:7
+#EXT-X-TARGETDURATION:2
+#EXT-X-PLAYLIST-TYPE:VOD
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXT-X-MAP:URI="sample_audio_dashinit.mp4",BYTERANGE="603@0"
+#EXTINF:2.000,
+#EXT-X-BYTERANGE:556@671
+sample_audio_dashinit.mp4
+#EXTINF:2.000,
+#EXT-X-BYTERANGE:656@1227
+sample_audio_dashinit.mp4
+#EXTINF:2.000,
+#EXT-X-BYTERANGE:756@1883
+sample_audio_dashinit.mp4
+#EXT-X-ENDLIST
+"#
+  );
+  // TTML sidecar files are not supported by HLS
+  assert!(!Path::new("tests/sample_5_s2.m3u8").exists());
+}
+
+#[test]
+fn convert_segment_template_manifest() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4.mpd".to_string(),
+    destination_path: Some("tests/sample_4_hls.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  convert_to_hls(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_4_hls.m3u8").unwrap();
+  assert!(content.contains("#EXT-X-STREAM-INF:BANDWIDTH=880081,CODECS=\"avc1.4D401F,mp4a.40.2,stpp\",RESOLUTION=640x360,FRAME-RATE=25.000,AUDIO=\"audio-mp4a\",SUBTITLES=\"subtitles\"\nsample_4_hls_v2.m3u8\n"));
+
+  let content = fs::read_to_string("tests/sample_4_hls_v1.m3u8").unwrap();
+  assert!(content
+    .contains("#EXT-X-MAP:URI=\"video/v1/init.mp4\"\n#EXTINF:2.000,\nvideo/v1/segment_1.m4s\n"));
+  assert_eq!(content.matches("#EXTINF:").count(), 15);
+
+  let content = fs::read_to_string("tests/sample_4_hls_a1.m3u8").unwrap();
+  assert!(content.contains("#EXTINF:1.925,\naudio/fra/segment_1347584.m4s\n#EXT-X-ENDLIST\n"));
+}
+
+#[test]
+fn convert_concatenated_manifest() {
+  use crate::dash::periods::concatenate_manifests;
+  use crate::SourceManifestParameters;
+
+  // the sources use the same representation ids for different renditions
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4_5_concatenated.mpd".to_string(),
+    source_manifests: Some(vec![
+      SourceManifestParameters {
+        path: "tests/sample_4.mpd".to_string(),
+        asset_identifier: None,
+      },
+      SourceManifestParameters {
+        path: "tests/sample_5.mpd".to_string(),
+        asset_identifier: None,
+      },
+    ]),
+    ..Default::default()
+  };
+  concatenate_manifests(JobResult::new(666), parameters).unwrap();
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4_5_concatenated.mpd".to_string(),
+    ..Default::default()
+  };
+  convert_to_hls(JobResult::new(666), parameters).unwrap();
+
+  // the renditions of the second Period have the same bandwidth rank, or the lowest bandwidth
+  let content = fs::read_to_string("tests/sample_4_5_concatenated_v1.m3u8").unwrap();
+  assert!(content.contains("video/v1/segment_15.m4s\n#EXT-X-DISCONTINUITY\n#EXT-X-MAP:URI=\"sample_video_dashinit.mp4\",BYTERANGE=\"643@0\"\n"));
+  let content = fs::read_to_string("tests/sample_4_5_concatenated_v2.m3u8").unwrap();
+  assert!(content.contains("video/v2/segment_15.m4s\n#EXT-X-DISCONTINUITY\n#EXT-X-MAP:URI=\"sample_video_dashinit.mp4\",BYTERANGE=\"643@0\"\n"));
+  assert_eq!(content.matches("#EXTINF:").count(), 18);
+  let content = fs::read_to_string("tests/sample_4_5_concatenated_a1.m3u8").unwrap();
+  assert!(content.contains("audio/fra/segment_1347584.m4s\n#EXT-X-DISCONTINUITY\n#EXT-X-MAP:URI=\"sample_audio_dashinit.mp4\",BYTERANGE=\"603@0\"\n"));
+  let content = fs::read_to_string("tests/sample_4_5_concatenated_s1.m3u8").unwrap();
+  assert!(content.contains("#EXT-X-DISCONTINUITY\n#EXT-X-MAP:URI=\"sample_subtitle_dashinit.mp4\""));
+}
@@ -1,3 +1,7 @@
+use crate::language;
+use crate::mp4::MediaInfo;
+use crate::utils::{format_duration, parse_duration};
+use crate::LanguageConvention;
 use mcai_worker_sdk::debug;
 use std::io::{Read, Write};
 use yaserde::{YaDeserialize, YaSerialize};
@@ -23,57 +27,357 @@ pub struct Manifest {
   #[yaserde(rename = "ProgramInformation")]
   program_information: Option<ProgramInformation>,
   #[yaserde(rename = "Period")]
-  period: Period,
+  period: Vec<Period>,
 }
 
 impl Manifest {
+  pub fn new_on_demand(duration: f64, max_segment_duration: f64) -> Self {
+    Manifest {
+      min_buffer_time: Some("PT1.500S".to_string()),
+      media_presentation_duration: format_duration(duration),
+      max_segment_duration: format_duration(max_segment_duration),
+      profiles: "urn:mpeg:dash:profile:isoff-on-demand:2011".to_string(),
+      kind: "static".to_string(),
+      program_information: None,
+      period: vec![Period {
+        duration: Some(format_duration(duration)),
+        ..Default::default()
+      }],
+    }
+  }
+
+  pub fn get_periods(&self) -> &[Period] {
+    &self.period
+  }
+
+  /// Duration of the presentation, in seconds
+  pub fn get_duration(&self) -> Option<f64> {
+    parse_duration(&self.media_presentation_duration)
+  }
+
+  /// Longest segment of the presentation, in seconds
+  pub fn get_max_segment_duration(&self) -> Option<f64> {
+    parse_duration(&self.max_segment_duration)
+  }
+
+  /// Start and duration of each Period on the presentation timeline, in seconds
+  pub fn get_period_bounds(&self) -> Vec<(f64, Option<f64>)> {
+    let mut bounds = vec![];
+    let mut next_start = 0.0;
+    for (index, period) in self.period.iter().enumerate() {
+      let start = period
+        .start
+        .as_deref()
+        .and_then(parse_duration)
+        .unwrap_or(next_start);
+      let duration = period
+        .duration
+        .as_deref()
+        .and_then(parse_duration)
+        .or_else(|| {
+          self
+            .period
+            .get(index + 1)
+            .and_then(|next_period| next_period.start.as_deref())
+            .and_then(parse_duration)
+            .or_else(|| self.get_duration())
+            .map(|end| end - start)
+        });
+      next_start = start + duration.unwrap_or_default();
+      bounds.push((start, duration));
+    }
+    bounds
+  }
+
+  /// Add the event stream to the Period at the index, its events being timed from the Period start
+  pub fn add_event_stream(&mut self, period_index: usize, event_stream: EventStream) {
+    if let Some(period) = self.period.get_mut(period_index) {
+      period.event_stream.push(event_stream);
+    }
+  }
+
+  /// Highest id of the events of the scheme, in all the Periods
+  pub fn get_max_event_id(&self, scheme_id_uri: &str) -> Option<u64> {
+    self
+      .period
+      .iter()
+      .flat_map(|period| period.event_stream.iter())
+      .filter(|event_stream| event_stream.scheme_id_uri == scheme_id_uri)
+      .flat_map(|event_stream| event_stream.event.iter())
+      .filter_map(|event| event.id)
+      .max()
+  }
+
+  pub fn remove_event_stream(&mut self, scheme_id_uri: &str) {
+    for period in self.period.iter_mut() {
+      period
+        .event_stream
+        .retain(|event_stream| event_stream.scheme_id_uri != scheme_id_uri);
+    }
+  }
+
+  /// Add the adaptation set to every Period, its media timeline starting with the presentation
   pub fn add_adaptation_set(&mut self, adaptation_set: AdaptationSet) {
-    self.period.adaptation_set.push(adaptation_set);
+    let bounds = self.get_period_bounds();
+    for (period, (start, _)) in self.period.iter_mut().zip(bounds) {
+      let mut adaptation_set = adaptation_set.clone();
+      adaptation_set.offset_segment_base(start);
+      period.adaptation_set.push(adaptation_set);
+    }
   }
 
   pub fn remove_adaptation_set(&mut self, ttml_language: &str, ttml_role: &str) {
-    self.period.adaptation_set = self
-      .period
-      .adaptation_set
-      .clone()
-      .into_iter()
-      .filter(|adaptation_set| {
-        ttml_language == adaptation_set.language.as_str()
-          && adaptation_set.role
-            == Some(Role {
-              scheme_id_uri: "urn:mpeg:dash:role:2011".to_string(),
-              id: None,
-              content: Some(ttml_role.to_string()),
-            })
-      })
-      .collect();
+    for period in self.period.iter_mut() {
+      period.adaptation_set.retain(|adaptation_set| {
+        !language::is_same_language(ttml_language, &adaptation_set.language)
+          || !adaptation_set.has_role(ttml_role)
+      });
+    }
+  }
+
+  /// Write the languages of the adaptation sets following the convention
+  pub fn normalize_languages(&mut self, convention: &Option<LanguageConvention>) {
+    for period in self.period.iter_mut() {
+      for adaptation_set in period.adaptation_set.iter_mut() {
+        adaptation_set.language = language::normalize(&adaptation_set.language, convention);
+      }
+    }
   }
 
   pub fn prefix_urls(&mut self, reference_url: &str) {
     let reference_url = reference_url.to_string().replace("manifest.mpd", "");
 
-    self.period.adaptation_set = self
-      .period
-      .adaptation_set
-      .iter()
-      .map(|adaptation_set| {
-        let mut adaptation_set = adaptation_set.clone();
-        adaptation_set.representation = adaptation_set
-          .representation
-          .iter()
-          .map(|representation| {
-            let mut representation = representation.clone();
-            representation.base_url = if !representation.base_url.starts_with("http") {
-              reference_url.clone() + &representation.base_url
-            } else {
-              representation.base_url
-            };
-            representation
-          })
-          .collect();
-        adaptation_set
-      })
-      .collect();
+    self.update_urls(|url| {
+      if url.starts_with("http") {
+        url.to_string()
+      } else {
+        reference_url.clone() + url
+      }
+    });
+  }
+
+  /// Update the media URLs, segment templates being resolved against the BaseURL when there is one
+  pub fn update_urls<F: Fn(&str) -> String>(&mut self, update: F) {
+    for period in self.period.iter_mut() {
+      for adaptation_set in period.adaptation_set.iter_mut() {
+        let mut has_base_url = false;
+        for representation in adaptation_set.representation.iter_mut() {
+          if let Some(base_url) = &mut representation.base_url {
+            *base_url = update(base_url);
+            has_base_url = true;
+            continue;
+          }
+          if let Some(segment_base) = &mut representation.segment_base {
+            if let Some(source_url) = &mut segment_base.initialization.source_url {
+              *source_url = update(source_url);
+            }
+          }
+          if let Some(segment_template) = &mut representation.segment_template {
+            segment_template.update_urls(&update);
+          }
+        }
+        if let Some(segment_template) = &mut adaptation_set.segment_template {
+          if !has_base_url {
+            segment_template.update_urls(&update);
+          }
+        }
+      }
+    }
+  }
+
+  /// Copy the selected adaptation sets of the manifest to the matching Periods, returning their count
+  pub fn merge<F: Fn(&AdaptationSet) -> bool>(
+    &mut self,
+    manifest: Manifest,
+    select: F,
+  ) -> Result<usize, String> {
+    if let (Some(duration), Some(merged_duration)) = (self.get_duration(), manifest.get_duration())
+    {
+      let tolerance = self
+        .get_max_segment_duration()
+        .into_iter()
+        .chain(manifest.get_max_segment_duration())
+        .fold(0.0, f64::max);
+      if (duration - merged_duration).abs() > tolerance {
+        return Err(format!(
+          "duration of {:.3}s does not match the {:.3}s of the presentation",
+          merged_duration, duration
+        ));
+      }
+    }
+    if manifest.period.len() != self.period.len() {
+      return Err(format!(
+        "{} Periods where {} are expected",
+        manifest.period.len(),
+        self.period.len()
+      ));
+    }
+
+    let mut count = 0;
+    for (period, merged_period) in self.period.iter_mut().zip(manifest.period) {
+      for mut adaptation_set in merged_period.adaptation_set {
+        if !select(&adaptation_set) {
+          continue;
+        }
+        // representation ids are unique in a Period
+        for representation in adaptation_set.representation.iter_mut() {
+          let id = representation.id.clone();
+          let mut index = 1;
+          while period.has_representation(&representation.id) {
+            index += 1;
+            representation.id = format!("{}-{}", id, index);
+          }
+        }
+        period.adaptation_set.push(adaptation_set);
+        count += 1;
+      }
+    }
+    Ok(count)
+  }
+
+  /// Identify the asset of every Period
+  pub fn set_asset_identifier(&mut self, asset_identifier: &str) {
+    for period in self.period.iter_mut() {
+      period.asset_identifier = Some(Descriptor::new(
+        "urn:org:dashif:asset-id:2013",
+        Some(asset_identifier),
+      ));
+    }
+  }
+
+  /// Append the Periods of the manifest at the end of the presentation, Period ids being renumbered
+  pub fn append(&mut self, manifest: Manifest) -> Result<(), String> {
+    let duration = self
+      .get_duration()
+      .ok_or_else(|| "unknown duration of the presentation".to_string())?;
+    let appended_duration = manifest
+      .get_duration()
+      .ok_or_else(|| "unknown duration of the appended presentation".to_string())?;
+
+    self.set_period_bounds();
+    let bounds = manifest.get_period_bounds();
+    for (mut period, (start, period_duration)) in manifest.period.into_iter().zip(bounds) {
+      period.start = Some(format_duration(duration + start));
+      period.duration = period_duration.map(format_duration);
+      self.period.push(period);
+    }
+    self.renumber_periods();
+
+    self.media_presentation_duration = format_duration(duration + appended_duration);
+    if let Some(max_segment_duration) = parse_duration(&manifest.max_segment_duration) {
+      if parse_duration(&self.max_segment_duration)
+        .map_or(true, |current| current < max_segment_duration)
+      {
+        self.max_segment_duration = manifest.max_segment_duration;
+      }
+    }
+    if let Some(min_buffer_time) = manifest.min_buffer_time.as_deref().and_then(parse_duration) {
+      if self
+        .min_buffer_time
+        .as_deref()
+        .and_then(parse_duration)
+        .map_or(true, |current| current < min_buffer_time)
+      {
+        self.min_buffer_time = manifest.min_buffer_time;
+      }
+    }
+    for profile in manifest.profiles.split(',') {
+      if !self
+        .profiles
+        .split(',')
+        .any(|item| item.trim() == profile.trim())
+      {
+        self.profiles = format!("{},{}", self.profiles, profile.trim());
+      }
+    }
+    Ok(())
+  }
+
+  /// Restrict the presentation to the `[start, end)` range, in seconds
+  pub fn clip(&mut self, start: f64, end: Option<f64>) -> Result<(), String> {
+    let duration = self
+      .get_duration()
+      .ok_or_else(|| "unknown duration of the presentation".to_string())?;
+    let end = end.map_or(duration, |end| end.min(duration));
+    if start < 0.0 || start >= end {
+      return Err(format!(
+        "invalid [{}, {}) range of a {:.3}s presentation",
+        start, end, duration
+      ));
+    }
+
+    let bounds = self.get_period_bounds();
+    let mut periods = vec![];
+    for (period, (period_start, period_duration)) in self.period.iter().zip(bounds) {
+      let period_end =
+        period_duration.map_or(end, |period_duration| period_start + period_duration);
+      let clip_start = start.max(period_start);
+      let clip_end = end.min(period_end);
+      if clip_start >= clip_end {
+        continue;
+      }
+
+      let mut clipped_period = period.clip(clip_start - period_start, clip_end - period_start)?;
+      if clipped_period.start.is_some() {
+        clipped_period.start = Some(format_duration(clip_start - start));
+      }
+      periods.push(clipped_period);
+    }
+
+    self.period = periods;
+    self.media_presentation_duration = format_duration(end - start);
+    Ok(())
+  }
+
+  /// Number the Periods from 1, in presentation order
+  pub fn renumber_periods(&mut self) {
+    for (index, period) in self.period.iter_mut().enumerate() {
+      period.id = Some((index + 1).to_string());
+    }
+  }
+
+  /// Make the start and duration of the Periods explicit
+  pub fn set_period_bounds(&mut self) {
+    let bounds = self.get_period_bounds();
+    for (period, (start, duration)) in self.period.iter_mut().zip(bounds) {
+      period.start = Some(format_duration(start));
+      period.duration = duration.map(format_duration);
+    }
+  }
+
+  /// Split the Periods at the given presentation times, in seconds
+  pub fn split_periods(&mut self, times: &[f64]) -> Result<(), String> {
+    let bounds = self.get_period_bounds();
+    let mut periods = vec![];
+    for (index, (period, (start, duration))) in self.period.iter().zip(bounds).enumerate() {
+      let mut cuts: Vec<f64> = times
+        .iter()
+        .map(|time| time - start)
+        .filter(|time| *time > 0.0 && duration.map_or(true, |duration| *time < duration))
+        .collect();
+      if cuts.is_empty() {
+        periods.push(period.clone());
+        continue;
+      }
+
+      let duration =
+        duration.ok_or_else(|| "unable to split a Period with an unknown duration".to_string())?;
+      cuts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+      cuts.dedup();
+
+      let period_id = period.id.clone().unwrap_or_else(|| (index + 1).to_string());
+      let mut boundaries = vec![0.0];
+      boundaries.extend(cuts);
+      boundaries.push(duration);
+      for (part, range) in boundaries.windows(2).enumerate() {
+        let mut clipped_period = period.clip(range[0], range[1])?;
+        clipped_period.id = Some(format!("{}-{}", period_id, part + 1));
+        clipped_period.start = Some(format_duration(start + range[0]));
+        periods.push(clipped_period);
+      }
+    }
+    self.period = periods;
+    Ok(())
   }
 }
 
@@ -96,20 +400,157 @@ impl Default for ProgramInformation {
   }
 }
 
-#[derive(Debug, Clone, YaSerialize, YaDeserialize)]
+#[derive(Debug, Clone, Default, YaSerialize, YaDeserialize)]
 #[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
 pub struct Period {
   #[yaserde(attribute)]
-  duration: String,
+  id: Option<String>,
+  #[yaserde(attribute)]
+  start: Option<String>,
+  #[yaserde(attribute)]
+  duration: Option<String>,
+  #[yaserde(prefix = "mpd", rename = "AssetIdentifier")]
+  asset_identifier: Option<Descriptor>,
+  #[yaserde(prefix = "mpd", rename = "EventStream")]
+  event_stream: Vec<EventStream>,
   #[yaserde(prefix = "mpd", rename = "AdaptationSet")]
   adaptation_set: Vec<AdaptationSet>,
 }
 
-impl Default for Period {
+impl Period {
+  /// Restrict the Period to the `[start, end)` range, in seconds from its start
+  pub fn clip(&self, start: f64, end: f64) -> Result<Period, String> {
+    let mut period = self.clone();
+    period.duration = Some(format_duration(end - start));
+    for event_stream in period.event_stream.iter_mut() {
+      event_stream.clip(start, end);
+    }
+    for adaptation_set in period.adaptation_set.iter_mut() {
+      adaptation_set.clip(start, end)?;
+    }
+    Ok(period)
+  }
+
+  pub fn get_adaptation_sets(&self) -> &[AdaptationSet] {
+    &self.adaptation_set
+  }
+
+  fn has_representation(&self, id: &str) -> bool {
+    self.adaptation_set.iter().any(|adaptation_set| {
+      adaptation_set
+        .representation
+        .iter()
+        .any(|representation| representation.id == id)
+    })
+  }
+}
+
+#[derive(Debug, Clone, YaSerialize, YaDeserialize)]
+#[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
+pub struct EventStream {
+  #[yaserde(rename = "schemeIdUri", attribute)]
+  scheme_id_uri: String,
+  #[yaserde(rename = "value", attribute)]
+  content: Option<String>,
+  #[yaserde(attribute)]
+  timescale: Option<u64>,
+  #[yaserde(rename = "presentationTimeOffset", attribute)]
+  presentation_time_offset: Option<u64>,
+  #[yaserde(prefix = "mpd", rename = "Event")]
+  event: Vec<Event>,
+}
+
+impl EventStream {
+  pub fn new(scheme_id_uri: &str, timescale: u64) -> Self {
+    EventStream {
+      scheme_id_uri: scheme_id_uri.to_string(),
+      content: None,
+      timescale: Some(timescale),
+      presentation_time_offset: None,
+      event: vec![],
+    }
+  }
+
+  pub fn add_event(&mut self, event: Event) {
+    self.event.push(event);
+  }
+
+  /// Keep the events of the `[start, end)` range, in seconds from the Period start
+  fn clip(&mut self, start: f64, end: f64) {
+    let timescale = self.timescale.unwrap_or(1) as f64;
+    let presentation_time_offset = self.presentation_time_offset.unwrap_or(0);
+    let media_start = presentation_time_offset + (start * timescale).round() as u64;
+    let media_end = presentation_time_offset + (end * timescale).round() as u64;
+
+    self.event.retain(|event| {
+      let presentation_time = event.presentation_time.unwrap_or(0);
+      presentation_time >= media_start && presentation_time < media_end
+    });
+    if media_start != presentation_time_offset {
+      self.presentation_time_offset = Some(media_start);
+    }
+  }
+}
+
+impl Default for EventStream {
+  fn default() -> Self {
+    EventStream {
+      scheme_id_uri: "".to_string(),
+      content: None,
+      timescale: None,
+      presentation_time_offset: None,
+      event: vec![],
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, YaSerialize, YaDeserialize)]
+#[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
+pub struct Event {
+  #[yaserde(rename = "presentationTime", attribute)]
+  presentation_time: Option<u64>,
+  #[yaserde(attribute)]
+  duration: Option<u64>,
+  #[yaserde(attribute)]
+  id: Option<u64>,
+  #[yaserde(rename = "contentEncoding", attribute)]
+  content_encoding: Option<String>,
+  #[yaserde(rename = "messageData", attribute)]
+  message_data: Option<String>,
+  #[yaserde(prefix = "scte35", rename = "Signal")]
+  signal: Option<Signal>,
+}
+
+impl Event {
+  /// Event carrying a binary SCTE-35 splice_info_section
+  pub fn new_splice(id: u64, presentation_time: u64, duration: Option<u64>, binary: &str) -> Self {
+    Event {
+      presentation_time: Some(presentation_time),
+      duration,
+      id: Some(id),
+      content_encoding: None,
+      message_data: None,
+      signal: Some(Signal {
+        binary: binary.to_string(),
+      }),
+    }
+  }
+}
+
+#[derive(Debug, Clone, YaSerialize, YaDeserialize)]
+#[yaserde(
+  prefix = "scte35",
+  namespace = "scte35: http://www.scte.org/schemas/35/2016"
+)]
+pub struct Signal {
+  #[yaserde(prefix = "scte35", rename = "Binary")]
+  binary: String,
+}
+
+impl Default for Signal {
   fn default() -> Self {
-    Period {
-      duration: "".to_string(),
-      adaptation_set: vec![],
+    Signal {
+      binary: "".to_string(),
     }
   }
 }
@@ -136,8 +577,20 @@ pub struct AdaptationSet {
   #[yaserde(rename = "contentType", attribute)]
   content_type: Option<String>,
 
+  #[yaserde(rename = "EssentialProperty")]
+  essential_property: Vec<Descriptor>,
+  #[yaserde(rename = "SupplementalProperty")]
+  supplemental_property: Vec<Descriptor>,
+  #[yaserde(rename = "InbandEventStream")]
+  inband_event_stream: Vec<Descriptor>,
+  #[yaserde(rename = "Accessibility")]
+  accessibility: Vec<Descriptor>,
   #[yaserde(rename = "Role")]
-  role: Option<Role>,
+  role: Vec<Role>,
+  #[yaserde(rename = "Label")]
+  label: Vec<Label>,
+  #[yaserde(prefix = "mpd", rename = "SegmentTemplate")]
+  segment_template: Option<SegmentTemplate>,
   #[yaserde(prefix = "mpd", rename = "Representation")]
   representation: Vec<Representation>,
 }
@@ -154,11 +607,17 @@ impl AdaptationSet {
       subsegment_alignment: None,
       subsegment_starts_sith_sap: None,
       content_type: Some("text".to_string()),
-      role: Some(Role {
+      essential_property: vec![],
+      supplemental_property: vec![],
+      inband_event_stream: vec![],
+      accessibility: vec![],
+      role: vec![Role {
         scheme_id_uri: "urn:mpeg:dash:role:2011".to_string(),
         id: None,
         content: Some(role.to_string()),
-      }),
+      }],
+      label: vec![],
+      segment_template: None,
       representation: vec![Representation {
         id: "s1".to_string(),
         mime_type: Some("application/ttml+xml".to_string()),
@@ -171,11 +630,241 @@ impl AdaptationSet {
         start_with_sap: None,
         bandwidth: file_size,
         audio_channel_configuration: vec![],
-        base_url: file_path.to_string(),
+        base_url: Some(file_path.to_string()),
         segment_base: None,
+        segment_template: None,
       }],
     }
   }
+
+  pub fn new(content_type: &str, language: &str, role: &str) -> Self {
+    AdaptationSet {
+      segment_alignement: true,
+      max_width: None,
+      max_height: None,
+      max_frame_rate: None,
+      pixel_aspect_ratio: None,
+      language: language.to_string(),
+      subsegment_alignment: None,
+      subsegment_starts_sith_sap: None,
+      content_type: Some(content_type.to_string()),
+      essential_property: vec![],
+      supplemental_property: vec![],
+      inband_event_stream: vec![],
+      accessibility: vec![],
+      role: vec![Role {
+        scheme_id_uri: "urn:mpeg:dash:role:2011".to_string(),
+        id: None,
+        content: Some(role.to_string()),
+      }],
+      label: vec![],
+      segment_template: None,
+      representation: vec![],
+    }
+  }
+
+  pub fn new_audio(file_path: &str, language: &str, role: &str, media_info: &MediaInfo) -> Self {
+    let mut adaptation_set = AdaptationSet::new("audio", language, role);
+    adaptation_set.add_media_representation(
+      &format!("a_{}_{}", language, role),
+      file_path,
+      media_info,
+    );
+    adaptation_set
+  }
+
+  /// Fill the representations with the description of their packaged MP4 file
+  pub fn update_from_media_info(&mut self, media_info: &MediaInfo) {
+    for representation in self.representation.iter_mut() {
+      representation.update_from_media_info(media_info);
+    }
+    self.update_alignment_and_size(media_info);
+  }
+
+  pub fn set_codecs(&mut self, codecs: &str) {
+    for representation in self.representation.iter_mut() {
+      representation.codecs = Some(codecs.to_string());
+    }
+  }
+
+  /// Add a representation described by its packaged MP4 file
+  pub fn add_media_representation(&mut self, id: &str, file_path: &str, media_info: &MediaInfo) {
+    let mut representation = Representation::new(id, file_path);
+    representation.update_from_media_info(media_info);
+    self.representation.push(representation);
+    self.update_alignment_and_size(media_info);
+  }
+
+  fn update_alignment_and_size(&mut self, media_info: &MediaInfo) {
+    if media_info.index_range.is_some() {
+      self.subsegment_alignment = Some(true);
+      self.subsegment_starts_sith_sap = Some("1".to_string());
+    }
+    if media_info.width.is_some() {
+      self.max_width = self.max_width.max(media_info.width);
+      self.max_height = self.max_height.max(media_info.height);
+    }
+  }
+
+  /// Restrict the segments to the `[start, end)` range, in seconds from the Period start
+  pub fn clip(&mut self, start: f64, end: f64) -> Result<(), String> {
+    if let Some(segment_template) = &mut self.segment_template {
+      segment_template.clip(start, end)?;
+    }
+    for representation in self.representation.iter_mut() {
+      if let Some(segment_template) = &mut representation.segment_template {
+        segment_template.clip(start, end)?;
+      }
+    }
+    self.offset_segment_base(start);
+    Ok(())
+  }
+
+  /// Shift the on-demand representations to a Period starting `start` seconds in their media
+  fn offset_segment_base(&mut self, start: f64) {
+    for representation in self.representation.iter_mut() {
+      if let Some(segment_base) = &mut representation.segment_base {
+        segment_base.offset(start);
+      }
+    }
+  }
+
+  pub fn get_language(&self) -> &str {
+    &self.language
+  }
+
+  pub fn get_label(&self) -> Option<&str> {
+    self.label.first().map(|label| label.content.as_str())
+  }
+
+  pub fn get_representations(&self) -> &[Representation] {
+    &self.representation
+  }
+
+  /// Segment template of the representation, defined at its level or inherited from the set
+  pub fn get_segment_template<'a>(
+    &'a self,
+    representation: &'a Representation,
+  ) -> Option<&'a SegmentTemplate> {
+    representation
+      .segment_template
+      .as_ref()
+      .or(self.segment_template.as_ref())
+  }
+
+  /// Content type of the set, guessed from its representations when it is not specified
+  pub fn get_content_type(&self) -> Option<String> {
+    self.content_type.clone().or_else(|| {
+      self
+        .representation
+        .iter()
+        .find_map(|representation| {
+          let mime_type = representation.mime_type.as_deref().unwrap_or_default();
+          let codecs = representation.codecs.as_deref().unwrap_or_default();
+          if mime_type.starts_with("video/") {
+            Some("video")
+          } else if mime_type.starts_with("audio/") {
+            Some("audio")
+          } else if mime_type.starts_with("text/")
+            || mime_type == "application/ttml+xml"
+            || codecs.starts_with("stpp")
+            || codecs.starts_with("wvtt")
+          {
+            Some("text")
+          } else {
+            None
+          }
+        })
+        .map(|content_type| content_type.to_string())
+    })
+  }
+
+  /// Check whether one of the roles matches the value, whatever its scheme
+  pub fn has_role(&self, role: &str) -> bool {
+    self
+      .role
+      .iter()
+      .any(|item| item.content.as_deref() == Some(role))
+  }
+
+  pub fn add_accessibility(&mut self, accessibility: Descriptor) {
+    self.accessibility.push(accessibility);
+  }
+
+  pub fn add_essential_property(&mut self, essential_property: Descriptor) {
+    self.essential_property.push(essential_property);
+  }
+
+  pub fn add_supplemental_property(&mut self, supplemental_property: Descriptor) {
+    self.supplemental_property.push(supplemental_property);
+  }
+
+  pub fn add_label(&mut self, label: Label) {
+    self.label.push(label);
+  }
+}
+
+/// Generic DASH descriptor, used for Accessibility, EssentialProperty and SupplementalProperty elements
+#[derive(Debug, Clone, PartialEq, YaSerialize, YaDeserialize)]
+#[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
+pub struct Descriptor {
+  #[yaserde(rename = "schemeIdUri", attribute)]
+  scheme_id_uri: String,
+  #[yaserde(rename = "value", attribute)]
+  content: Option<String>,
+  #[yaserde(attribute)]
+  id: Option<String>,
+}
+
+impl Descriptor {
+  pub fn new(scheme_id_uri: &str, value: Option<&str>) -> Self {
+    Descriptor {
+      scheme_id_uri: scheme_id_uri.to_string(),
+      content: value.map(|value| value.to_string()),
+      id: None,
+    }
+  }
+}
+
+impl Default for Descriptor {
+  fn default() -> Self {
+    Descriptor {
+      scheme_id_uri: "".to_string(),
+      content: None,
+      id: None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, YaSerialize, YaDeserialize)]
+#[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
+pub struct Label {
+  #[yaserde(attribute)]
+  id: Option<u32>,
+  #[yaserde(rename = "lang", attribute)]
+  language: Option<String>,
+  #[yaserde(text)]
+  content: String,
+}
+
+impl Label {
+  pub fn new(content: &str, language: Option<&str>) -> Self {
+    Label {
+      id: None,
+      language: language.map(|language| language.to_string()),
+      content: content.to_string(),
+    }
+  }
+}
+
+impl Default for Label {
+  fn default() -> Self {
+    Label {
+      id: None,
+      language: None,
+      content: "".to_string(),
+    }
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, YaSerialize, YaDeserialize)]
@@ -226,9 +915,98 @@ pub struct Representation {
   #[yaserde(prefix = "mpd", rename = "AudioChannelConfiguration")]
   audio_channel_configuration: Vec<AudioChannelConfiguration>,
   #[yaserde(prefix = "mpd", rename = "BaseURL")]
-  base_url: String,
+  base_url: Option<String>,
   #[yaserde(prefix = "mpd", rename = "SegmentBase")]
   segment_base: Option<SegmentBase>,
+  #[yaserde(prefix = "mpd", rename = "SegmentTemplate")]
+  segment_template: Option<SegmentTemplate>,
+}
+
+impl Representation {
+  pub fn new(id: &str, base_url: &str) -> Self {
+    Representation {
+      id: id.to_string(),
+      base_url: Some(base_url.to_string()),
+      ..Default::default()
+    }
+  }
+
+  pub fn get_id(&self) -> &str {
+    &self.id
+  }
+
+  pub fn get_mime_type(&self) -> Option<&str> {
+    self.mime_type.as_deref()
+  }
+
+  pub fn get_codecs(&self) -> Option<&str> {
+    self.codecs.as_deref()
+  }
+
+  pub fn get_resolution(&self) -> Option<(u32, u32)> {
+    match (self.width, self.height) {
+      (Some(width), Some(height)) => Some((width, height)),
+      _ => None,
+    }
+  }
+
+  pub fn get_frame_rate(&self) -> Option<u32> {
+    self.frame_rate
+  }
+
+  pub fn get_bandwidth(&self) -> u64 {
+    self.bandwidth
+  }
+
+  pub fn get_audio_channels(&self) -> Option<&str> {
+    self
+      .audio_channel_configuration
+      .first()
+      .and_then(|audio_channel_configuration| audio_channel_configuration.content.as_deref())
+  }
+
+  pub fn get_base_url(&self) -> Option<&str> {
+    self.base_url.as_deref()
+  }
+
+  pub fn has_segment_base(&self) -> bool {
+    self.segment_base.is_some()
+  }
+
+  /// Start of the Period in the media of an on-demand representation, in seconds
+  pub fn get_presentation_time_offset(&self) -> f64 {
+    self
+      .segment_base
+      .as_ref()
+      .map(|segment_base| {
+        segment_base.presentation_time_offset.unwrap_or(0) as f64
+          / segment_base.timescale.unwrap_or(1) as f64
+      })
+      .unwrap_or_default()
+  }
+
+  /// Fill the representation with the description of its packaged MP4 file
+  pub fn update_from_media_info(&mut self, media_info: &MediaInfo) {
+    self.mime_type = Some(media_info.get_mime_type());
+    self.codecs = Some(media_info.codecs.clone());
+    self.width = media_info.width;
+    self.height = media_info.height;
+    self.audio_sampling_rate = media_info
+      .sampling_rate
+      .map(|sampling_rate| sampling_rate.to_string());
+    self.audio_channel_configuration = media_info
+      .channels
+      .map(|channels| AudioChannelConfiguration {
+        scheme_id_uri: "urn:mpeg:dash:23003:3:audio_channel_configuration:2011".to_string(),
+        content: Some(channels.to_string()),
+        id: None,
+      })
+      .into_iter()
+      .collect();
+    self.start_with_sap = Some(1);
+    self.bandwidth = media_info.get_bandwidth();
+    self.segment_base = Some(SegmentBase::from(media_info));
+  }
 }
 
 impl Default for Representation {
@@ -244,8 +1022,9 @@ impl Default for Representation {
       audio_sampling_rate: None,
       start_with_sap: None,
       bandwidth: 0,
-      base_url: "".to_string(),
+      base_url: None,
       segment_base: None,
+      segment_template: None,
       audio_channel_configuration: vec![],
     }
   }
@@ -258,6 +1037,8 @@ pub struct SegmentBase {
   index_range_exact: bool,
   #[yaserde(rename = "indexRange", attribute)]
   index_range: Option<String>,
+  #[yaserde(attribute)]
+  timescale: Option<u32>,
   #[yaserde(rename = "presentationTimeOffset", attribute)]
   presentation_time_offset: Option<u64>,
   #[yaserde(rename = "Initialization")]
@@ -269,12 +1050,279 @@ impl Default for SegmentBase {
     SegmentBase {
       index_range_exact: false,
       index_range: None,
+      timescale: None,
       presentation_time_offset: None,
       initialization: Initialization::default(),
     }
   }
 }
 
+impl SegmentBase {
+  /// Shift the presentation to start `start` seconds in the media
+  fn offset(&mut self, start: f64) {
+    let offset = (start * self.timescale.unwrap_or(1) as f64).round() as u64;
+    if offset > 0 {
+      self.presentation_time_offset = Some(self.presentation_time_offset.unwrap_or(0) + offset);
+    }
+  }
+}
+
+impl From<&MediaInfo> for SegmentBase {
+  fn from(media_info: &MediaInfo) -> Self {
+    let (initialization_start, initialization_end) = media_info.initialization_range;
+
+    SegmentBase {
+      index_range_exact: media_info.index_range.is_some(),
+      index_range: media_info
+        .index_range
+        .map(|(start, end)| format!("{}-{}", start, end)),
+      timescale: Some(media_info.timescale),
+      presentation_time_offset: None,
+      initialization: Initialization {
+        source_url: None,
+        range: Some(format!("{}-{}", initialization_start, initialization_end)),
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, YaSerialize, YaDeserialize)]
+#[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
+pub struct SegmentTemplate {
+  #[yaserde(attribute)]
+  media: Option<String>,
+  #[yaserde(attribute)]
+  initialization: Option<String>,
+  #[yaserde(attribute)]
+  timescale: Option<u32>,
+  #[yaserde(rename = "startNumber", attribute)]
+  start_number: Option<u64>,
+  #[yaserde(attribute)]
+  duration: Option<u64>,
+  #[yaserde(rename = "presentationTimeOffset", attribute)]
+  presentation_time_offset: Option<u64>,
+  #[yaserde(prefix = "mpd", rename = "SegmentTimeline")]
+  segment_timeline: Option<SegmentTimeline>,
+}
+
+impl SegmentTemplate {
+  /// URL of the initialization segment of the representation
+  pub fn get_initialization_url(&self, representation: &Representation) -> Option<String> {
+    self
+      .initialization
+      .as_deref()
+      .map(|initialization| expand_template(initialization, representation, 0, 0))
+  }
+
+  /// URL and duration, in seconds, of the segments of the representation covering a Period
+  pub fn get_media_segments(
+    &self,
+    representation: &Representation,
+    period_duration: f64,
+  ) -> Vec<(String, f64)> {
+    let media = match &self.media {
+      Some(media) => media,
+      None => return vec![],
+    };
+    let timescale = self.timescale.unwrap_or(1) as f64;
+    let start_number = self.start_number.unwrap_or(1);
+    let presentation_time_offset = self.presentation_time_offset.unwrap_or(0);
+    let media_end = presentation_time_offset + (period_duration * timescale).round() as u64;
+
+    let segments = if let Some(segment_timeline) = &self.segment_timeline {
+      segment_timeline.get_segments(media_end)
+    } else if let Some(duration) = self.duration.filter(|duration| *duration > 0) {
+      (0..)
+        .map(|index| (presentation_time_offset + index * duration, duration))
+        .take_while(|(time, _)| *time < media_end)
+        .collect()
+    } else {
+      vec![]
+    };
+
+    segments
+      .into_iter()
+      .enumerate()
+      .filter(|(_, (time, _))| *time < media_end)
+      .map(|(index, (time, duration))| {
+        let end = (time + duration).min(media_end);
+        (
+          expand_template(media, representation, start_number + index as u64, time),
+          (end - time) as f64 / timescale,
+        )
+      })
+      .collect()
+  }
+
+  fn update_urls<F: Fn(&str) -> String>(&mut self, update: &F) {
+    if let Some(media) = &mut self.media {
+      *media = update(media);
+    }
+    if let Some(initialization) = &mut self.initialization {
+      *initialization = update(initialization);
+    }
+  }
+
+  /// Keep the segments overlapping the `[start, end)` range, in seconds from the Period start
+  fn clip(&mut self, start: f64, end: f64) -> Result<(), String> {
+    let timescale = self.timescale.unwrap_or(1) as f64;
+    let offset = (start * timescale).round() as u64;
+    let presentation_time_offset = self.presentation_time_offset.unwrap_or(0);
+    let media_start = presentation_time_offset + offset;
+    let media_end = presentation_time_offset + (end * timescale).round() as u64;
+
+    if let Some(segment_timeline) = &mut self.segment_timeline {
+      let segments = segment_timeline.get_segments(media_end);
+      let first_index = segments
+        .iter()
+        .position(|(time, duration)| time + duration > media_start)
+        .unwrap_or(segments.len());
+      let segments: Vec<(u64, u64)> = segments[first_index..]
+        .iter()
+        .filter(|(time, _)| *time < media_end)
+        .cloned()
+        .collect();
+      segment_timeline.set_segments(&segments);
+
+      if first_index > 0 {
+        self.start_number = Some(self.start_number.unwrap_or(1) + first_index as u64);
+      }
+    } else if let Some(duration) = self.duration {
+      // with a constant duration, segment numbers are bound to the Period start
+      if offset.checked_rem(duration) != Some(0) {
+        return Err(format!(
+          "{}s is not aligned on a segment boundary of {}",
+          start,
+          self.media.as_deref().unwrap_or_default()
+        ));
+      }
+      self.start_number = Some(self.start_number.unwrap_or(1) + offset / duration);
+    }
+
+    if offset > 0 {
+      self.presentation_time_offset = Some(media_start);
+    }
+    Ok(())
+  }
+}
+
+/// Substitute the identifiers of a segment template ($RepresentationID$, $Number$, $Time$, $Bandwidth$)
+fn expand_template(
+  template: &str,
+  representation: &Representation,
+  number: u64,
+  time: u64,
+) -> String {
+  let mut url = String::new();
+  // identifiers are enclosed between dollar signs
+  let mut is_identifier = true;
+  for part in template.split('$') {
+    is_identifier = !is_identifier;
+    if !is_identifier {
+      url.push_str(part);
+      continue;
+    }
+
+    let (identifier, format) = match part.find('%') {
+      Some(position) => (&part[..position], Some(&part[position..])),
+      None => (part, None),
+    };
+    let value = match identifier {
+      "" => {
+        url.push('$');
+        continue;
+      }
+      "RepresentationID" => {
+        url.push_str(&representation.id);
+        continue;
+      }
+      "Number" => number,
+      "Time" => time,
+      "Bandwidth" => representation.bandwidth,
+      _ => {
+        url.push_str(&format!("${}$", part));
+        continue;
+      }
+    };
+    let width = format
+      .and_then(|format| {
+        format
+          .trim_start_matches('%')
+          .trim_end_matches('d')
+          .parse::<usize>()
+          .ok()
+      })
+      .unwrap_or_default();
+    url.push_str(&format!("{:0width$}", value, width = width));
+  }
+  url
+}
+
+#[derive(Debug, Clone, Default, YaSerialize, YaDeserialize)]
+#[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
+pub struct SegmentTimeline {
+  #[yaserde(prefix = "mpd", rename = "S")]
+  segments: Vec<TimelineSegment>,
+}
+
+impl SegmentTimeline {
+  /// Start time and duration of each segment, open-ended repetitions stopping at `end`
+  fn get_segments(&self, end: u64) -> Vec<(u64, u64)> {
+    let mut segments = vec![];
+    let mut time = 0;
+    for (index, segment) in self.segments.iter().enumerate() {
+      time = segment.time.unwrap_or(time);
+      let repeat = match segment.repeat {
+        Some(repeat) if repeat < 0 => {
+          let limit = self
+            .segments
+            .get(index + 1)
+            .and_then(|next_segment| next_segment.time)
+            .unwrap_or(end);
+          limit.saturating_sub(time).saturating_sub(1) / segment.duration.max(1)
+        }
+        repeat => repeat.unwrap_or(0) as u64,
+      };
+      for _ in 0..=repeat {
+        segments.push((time, segment.duration));
+        time += segment.duration;
+      }
+    }
+    segments
+  }
+
+  /// Replace the timeline, grouping the contiguous segments of the same duration
+  fn set_segments(&mut self, segments: &[(u64, u64)]) {
+    let mut timeline_segments: Vec<TimelineSegment> = vec![];
+    let mut next_time = None;
+    for (time, duration) in segments {
+      match timeline_segments.last_mut() {
+        Some(last) if last.duration == *duration && next_time == Some(*time) => {
+          last.repeat = Some(last.repeat.unwrap_or(0) + 1);
+        }
+        _ => timeline_segments.push(TimelineSegment {
+          time: Some(*time).filter(|time| next_time != Some(*time)),
+          duration: *duration,
+          repeat: None,
+        }),
+      }
+      next_time = Some(time + duration);
+    }
+    self.segments = timeline_segments;
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, YaSerialize, YaDeserialize)]
+#[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
+pub struct TimelineSegment {
+  #[yaserde(rename = "t", attribute)]
+  time: Option<u64>,
+  #[yaserde(rename = "d", attribute)]
+  duration: u64,
+  #[yaserde(rename = "r", attribute)]
+  repeat: Option<i64>,
+}
+
 #[derive(Debug, Clone, YaSerialize, YaDeserialize)]
 #[yaserde(prefix = "mpd", namespace = "mpd: urn:mpeg:dash:schema:mpd:2011")]
 pub struct Initialization {
@@ -375,3 +1423,209 @@ fn sample_3() {
     println!("{}", s);
   }
 }
+
+#[test]
+fn multiple_roles() {
+  use yaserde::de::from_str;
+  use yaserde::ser::to_string;
+
+  let contents = r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT0H0M10.000S" maxSegmentDuration="PT0H0M2.000S" profiles="urn:mpeg:dash:profile:isoff-on-demand:2011">
+ <Period duration="PT0H0M10.000S">
+  <AdaptationSet segmentAlignment="true" lang="fra">
+   <Role schemeIdUri="urn:mpeg:dash:role:2011" value="main"/>
+   <Role schemeIdUri="urn:mpeg:dash:role:2011" value="caption"/>
+   <Representation id="1" bandwidth="1000">
+    <BaseURL>subtitle.ttml</BaseURL>
+   </Representation>
+  </AdaptationSet>
+ </Period>
+</MPD>"#;
+
+  let mut manifest: Manifest = from_str(contents).unwrap();
+  let adaptation_set = &manifest.period[0].adaptation_set[0];
+  assert_eq!(adaptation_set.role.len(), 2);
+  assert!(adaptation_set.has_role("main"));
+  assert!(adaptation_set.has_role("caption"));
+
+  let serialized = to_string(&manifest).unwrap();
+  assert!(serialized.contains(r#"value="main""#));
+  assert!(serialized.contains(r#"value="caption""#));
+
+  manifest.remove_adaptation_set("fra", "caption");
+  assert!(manifest.period[0].adaptation_set.is_empty());
+}
+
+#[test]
+fn remove_adaptation_set_with_custom_role_scheme() {
+  use yaserde::de::from_str;
+
+  let contents = std::fs::read_to_string("tests/sample_3.mpd").unwrap();
+  let mut manifest: Manifest = from_str(&contents).unwrap();
+  assert_eq!(manifest.period[0].adaptation_set.len(), 4);
+
+  manifest.remove_adaptation_set("qaa", "synchronized");
+  assert_eq!(manifest.period[0].adaptation_set.len(), 3);
+  assert!(manifest.period[0]
+    .adaptation_set
+    .iter()
+    .all(|adaptation_set| !adaptation_set.has_role("synchronized")));
+
+  manifest.remove_adaptation_set("fra", "main");
+  assert_eq!(manifest.period[0].adaptation_set.len(), 1);
+}
+
+#[test]
+fn event_streams() {
+  use yaserde::de::from_str;
+  use yaserde::ser::to_string;
+
+  let contents = r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" xmlns:scte35="http://www.scte.org/schemas/35/2016" type="static" mediaPresentationDuration="PT0H0M10.000S" maxSegmentDuration="PT0H0M2.000S" profiles="urn:mpeg:dash:profile:isoff-on-demand:2011">
+ <Period duration="PT0H0M10.000S">
+  <EventStream schemeIdUri="urn:scte:scte35:2014:xml+bin" timescale="90000">
+   <Event presentationTime="450000" duration="2700000" id="1">
+    <scte35:Signal>
+     <scte35:Binary>/DAlAAAAAAAAAP/wFAUAAAABf+/+AAbd0H4AKTLgAAEAAAAAhb9xRQ==</scte35:Binary>
+    </scte35:Signal>
+   </Event>
+  </EventStream>
+  <AdaptationSet segmentAlignment="true" lang="fra">
+   <InbandEventStream schemeIdUri="urn:scte:scte35:2013:bin"/>
+   <Representation id="1" bandwidth="1000">
+    <BaseURL>audio.mp4</BaseURL>
+   </Representation>
+  </AdaptationSet>
+ </Period>
+</MPD>"#;
+
+  let manifest: Manifest = from_str(contents).unwrap();
+  let event_stream = &manifest.period[0].event_stream[0];
+  assert_eq!(event_stream.scheme_id_uri, "urn:scte:scte35:2014:xml+bin");
+  assert_eq!(event_stream.timescale, Some(90000));
+  assert_eq!(event_stream.event[0].presentation_time, Some(450000));
+  assert_eq!(event_stream.event[0].duration, Some(2700000));
+  assert_eq!(
+    event_stream.event[0].signal.as_ref().unwrap().binary,
+    "/DAlAAAAAAAAAP/wFAUAAAABf+/+AAbd0H4AKTLgAAEAAAAAhb9xRQ=="
+  );
+  assert_eq!(
+    manifest.period[0].adaptation_set[0].inband_event_stream,
+    vec![Descriptor::new("urn:scte:scte35:2013:bin", None)]
+  );
+
+  let serialized = to_string(&manifest).unwrap();
+  let reloaded: Manifest = from_str(&serialized).unwrap();
+  assert_eq!(reloaded.period[0].event_stream[0].event.len(), 1);
+}
+
+#[test]
+fn split_segment_timeline_periods() {
+  use yaserde::de::from_str;
+  use yaserde::ser::to_string;
+
+  let contents = std::fs::read_to_string("tests/sample_4.mpd").unwrap();
+  let mut manifest: Manifest = from_str(&contents).unwrap();
+  assert_eq!(manifest.get_period_bounds(), vec![(0.0, Some(30.0))]);
+
+  manifest.split_periods(&[10.0, 45.0]).unwrap();
+  assert_eq!(
+    manifest.get_period_bounds(),
+    vec![(0.0, Some(10.0)), (10.0, Some(20.0))]
+  );
+
+  // the audio segment spanning the split point belongs to both Periods
+  let audio_template = |period: &Period| period.adaptation_set[1].segment_template.clone().unwrap();
+  let first = audio_template(&manifest.period[0]);
+  assert_eq!(first.presentation_time_offset, None);
+  assert_eq!(
+    first.segment_timeline.unwrap().segments,
+    vec![TimelineSegment {
+      time: Some(0),
+      duration: 96256,
+      repeat: Some(4),
+    }]
+  );
+  let second = audio_template(&manifest.period[1]);
+  assert_eq!(second.presentation_time_offset, Some(480000));
+  assert_eq!(second.start_number, Some(5));
+  assert_eq!(
+    second.segment_timeline.unwrap().segments,
+    vec![
+      TimelineSegment {
+        time: Some(385024),
+        duration: 96256,
+        repeat: Some(9),
+      },
+      TimelineSegment {
+        time: None,
+        duration: 92416,
+        repeat: None,
+      }
+    ]
+  );
+
+  let serialized = to_string(&manifest).unwrap();
+  let reloaded: Manifest = from_str(&serialized).unwrap();
+  assert_eq!(reloaded.period.len(), 2);
+  assert_eq!(reloaded.period[1].id.as_deref(), Some("1-2"));
+}
+
+#[test]
+fn open_ended_segment_timeline() {
+  let segment_timeline = SegmentTimeline {
+    segments: vec![TimelineSegment {
+      time: Some(1000),
+      duration: 2000,
+      repeat: Some(-1),
+    }],
+  };
+  assert_eq!(
+    segment_timeline.get_segments(6000),
+    vec![(1000, 2000), (3000, 2000), (5000, 2000)]
+  );
+}
+
+#[test]
+fn segment_template_urls() {
+  let representation = Representation {
+    id: "v1".to_string(),
+    bandwidth: 2366818,
+    ..Default::default()
+  };
+  assert_eq!(
+    expand_template(
+      "$RepresentationID$/$Bandwidth$/segment_$Number%05d$.m4s",
+      &representation,
+      42,
+      0
+    ),
+    "v1/2366818/segment_00042.m4s"
+  );
+  assert_eq!(
+    expand_template("segment_$Time$$$.m4s", &representation, 0, 128000),
+    "segment_128000$.m4s"
+  );
+
+  let segment_template = SegmentTemplate {
+    media: Some("segment_$Number$.m4s".to_string()),
+    initialization: Some("$RepresentationID$/init.mp4".to_string()),
+    timescale: Some(1000),
+    start_number: Some(3),
+    duration: Some(2000),
+    presentation_time_offset: Some(4000),
+    ..Default::default()
+  };
+  assert_eq!(
+    segment_template.get_initialization_url(&representation),
+    Some("v1/init.mp4".to_string())
+  );
+  assert_eq!(
+    segment_template.get_media_segments(&representation, 5.0),
+    vec![
+      ("segment_3.m4s".to_string(), 2.0),
+      ("segment_4.m4s".to_string(), 2.0),
+      ("segment_5.m4s".to_string(), 1.0),
+    ]
+  );
+}
@@ -0,0 +1,143 @@
+use crate::dash::manifest::{AdaptationSet, Manifest};
+use crate::language;
+use crate::utils::{load_xml_file, processing_error, rebase_url, save_xml_file};
+use crate::DashManifestParameters;
+use mcai_worker_sdk::{job::JobResult, MessageError};
+
+pub fn merge_manifests(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<(), MessageError> {
+  let source_manifests = parameters
+    .source_manifests
+    .as_ref()
+    .filter(|source_manifests| !source_manifests.is_empty())
+    .ok_or_else(|| processing_error(&job_result, "missing source_manifests parameter"))?;
+
+  let destination_manifest_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+
+  let mut manifest: Manifest = load_xml_file(&job_result, &parameters.source_path)?;
+  manifest.update_urls(|url| rebase_url(url, &parameters.source_path, destination_manifest_path));
+
+  for source_manifest in source_manifests {
+    let mut merged_manifest: Manifest = load_xml_file(&job_result, &source_manifest.path)?;
+    merged_manifest
+      .update_urls(|url| rebase_url(url, &source_manifest.path, destination_manifest_path));
+
+    let count = manifest
+      .merge(merged_manifest, |adaptation_set| {
+        is_selected(adaptation_set, &parameters)
+      })
+      .map_err(|message| {
+        processing_error(
+          &job_result,
+          &format!("{}: {}", source_manifest.path, message),
+        )
+      })?;
+    if count == 0 {
+      return Err(processing_error(
+        &job_result,
+        &format!("{}: no track matches the selection", source_manifest.path),
+      ));
+    }
+  }
+
+  if let Some(url) = &parameters.reference_url {
+    manifest.prefix_urls(url);
+  }
+  manifest.normalize_languages(&parameters.language_convention);
+
+  save_xml_file(&job_result, &manifest, destination_manifest_path)
+}
+
+fn is_selected(adaptation_set: &AdaptationSet, parameters: &DashManifestParameters) -> bool {
+  let content_type = adaptation_set.get_content_type();
+
+  parameters
+    .content_types
+    .as_ref()
+    .map_or(true, |content_types| {
+      content_types
+        .iter()
+        .any(|item| Some(item) == content_type.as_ref())
+    })
+    && parameters.languages.as_ref().map_or(true, |languages| {
+      languages
+        .iter()
+        .any(|item| language::is_same_language(item, adaptation_set.get_language()))
+    })
+}
+
+#[test]
+fn merge_audio_tracks() {
+  use crate::{DashAction, SourceManifestParameters};
+  use std::fs;
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::Merge),
+    source_path: "tests/sample_1.mpd".to_string(),
+    source_manifests: Some(vec![SourceManifestParameters {
+      path: "tests/audio/sample_1_audio.mpd".to_string(),
+      asset_identifier: None,
+    }]),
+    content_types: Some(vec!["audio".to_string()]),
+    languages: Some(vec!["eng".to_string()]),
+    destination_path: Some("tests/sample_1_merged.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  merge_manifests(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_merged.mpd").unwrap();
+  assert!(content.contains(r#"<mpd:AdaptationSet segmentAlignment="true" lang="eng" subsegmentAlignment="true" subsegmentStartsWithSAP="1"><Role schemeIdUri="urn:mpeg:dash:role:2011" value="dub" /><mpd:Representation mpd:id="a1""#));
+  assert!(content.contains(
+    r#"<mpd:BaseURL>audio/191496242-5bd703996a1d5-eng_track2_dashinit.mp4</mpd:BaseURL>"#
+  ));
+  assert!(content.contains(
+    r#"<mpd:BaseURL>191496242-5bd703996a1d5-standard1_track1_dashinit.mp4</mpd:BaseURL>"#
+  ));
+  assert!(!content.contains(r#"lang="deu""#));
+  assert_eq!(content.matches("<mpd:AdaptationSet ").count(), 3);
+}
+
+#[test]
+fn merge_tracks_with_same_representation_id() {
+  use crate::SourceManifestParameters;
+  use std::fs;
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    source_manifests: Some(vec![SourceManifestParameters {
+      path: "tests/audio/sample_1_audio.mpd".to_string(),
+      asset_identifier: None,
+    }]),
+    content_types: Some(vec!["video".to_string()]),
+    destination_path: Some("tests/sample_1_merged_video.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  merge_manifests(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_merged_video.mpd").unwrap();
+  assert!(content.contains(r#"<mpd:Representation mpd:id="v1-2""#));
+}
+
+#[test]
+fn merge_tracks_with_another_duration() {
+  use crate::SourceManifestParameters;
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    source_manifests: Some(vec![SourceManifestParameters {
+      path: "tests/sample_3.mpd".to_string(),
+      asset_identifier: None,
+    }]),
+    destination_path: Some("tests/sample_1_merged_error.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(merge_manifests(job_result, parameters).is_err());
+}
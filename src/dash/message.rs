@@ -1,118 +1,276 @@
-use crate::dash::manifest::{AdaptationSet, Manifest};
-use crate::DashManifestParameters;
+use crate::dash::audio::add_audio_track;
+use crate::dash::events::add_splice_cues;
+use crate::dash::generate::generate_manifest;
+use crate::dash::hls::convert_to_hls;
+use crate::dash::manifest::{AdaptationSet, Descriptor, Label, Manifest};
+use crate::dash::merge::merge_manifests;
+use crate::dash::periods::{clip_manifest, concatenate_manifests, split_periods};
+use crate::language;
+use crate::mp4::MediaInfo;
+use crate::subtitle::{self, ttml};
+use crate::utils::{load_xml_file, processing_error, rebase_url, save_xml_file};
+use crate::{DashAction, DashManifestParameters, DescriptorParameters};
 use mcai_worker_sdk::{
   job::{JobResult, JobStatus},
-  McaiChannel, MessageError,
+  warn, McaiChannel, MessageError,
 };
 use std::fs;
 use std::path::Path;
-use yaserde::{de::from_str, ser::to_string};
 
 pub fn process(
   _channel: Option<McaiChannel>,
   parameters: DashManifestParameters,
   job_result: JobResult,
 ) -> Result<JobResult, MessageError> {
-  add_ttml_subtitle(job_result.clone(), parameters)?;
+  match parameters.action.clone().unwrap_or(DashAction::AddSubtitle) {
+    DashAction::AddSubtitle => {
+      if let Some(warning) = add_ttml_subtitle(job_result.clone(), parameters)? {
+        warn!("{}", warning);
+        return Ok(
+          job_result
+            .with_status(JobStatus::Completed)
+            .with_message(&warning),
+        );
+      }
+    }
+    DashAction::AddAudio => add_audio_track(job_result.clone(), parameters)?,
+    DashAction::Generate => generate_manifest(job_result.clone(), parameters)?,
+    DashAction::AddSpliceCues => add_splice_cues(job_result.clone(), parameters)?,
+    DashAction::SplitPeriods => split_periods(job_result.clone(), parameters)?,
+    DashAction::Concatenate => concatenate_manifests(job_result.clone(), parameters)?,
+    DashAction::Merge => merge_manifests(job_result.clone(), parameters)?,
+    DashAction::Clip => clip_manifest(job_result.clone(), parameters)?,
+    DashAction::ConvertToHls => convert_to_hls(job_result.clone(), parameters)?,
+    DashAction::ConvertSubtitle => {
+      if let Some(warning) = convert_subtitle(job_result.clone(), parameters)? {
+        warn!("{}", warning);
+        return Ok(
+          job_result
+            .with_status(JobStatus::Completed)
+            .with_message(&warning),
+        );
+      }
+    }
+  }
 
   Ok(job_result.with_status(JobStatus::Completed))
 }
 
+pub fn get_required_parameter<'a>(
+  job_result: &JobResult,
+  value: &'a Option<String>,
+  name: &str,
+) -> Result<&'a str, MessageError> {
+  value
+    .as_deref()
+    .ok_or_else(|| processing_error(job_result, &format!("missing {} parameter", name)))
+}
+
+/// Path of the file, relative to the manifest folder when it is located inside
+pub fn get_reference_path(
+  job_result: &JobResult,
+  manifest_path: &str,
+  path: &str,
+) -> Result<String, MessageError> {
+  let mp_folder = Path::new(manifest_path).parent().ok_or_else(|| {
+    processing_error(
+      job_result,
+      "unable to found folder directory of the manifest",
+    )
+  })?;
+
+  let reference_path = if let Ok(path) = Path::new(path).strip_prefix(mp_folder) {
+    path.to_str().unwrap()
+  } else {
+    path
+  };
+  Ok(reference_path.to_string())
+}
+
+pub fn to_descriptors(parameters: &Option<Vec<DescriptorParameters>>) -> Vec<Descriptor> {
+  parameters
+    .iter()
+    .flatten()
+    .map(|descriptor| Descriptor::new(&descriptor.scheme_id_uri, descriptor.value.as_deref()))
+    .collect()
+}
+
 fn add_ttml_subtitle(
   job_result: JobResult,
   parameters: DashManifestParameters,
-) -> Result<(), MessageError> {
-  let mp_folder = Path::new(&parameters.source_path).parent();
-
-  if mp_folder.is_none() {
-    return Err(MessageError::ProcessingError(
-      job_result
-        .with_status(JobStatus::Error)
-        .with_message("unable to found folder directory of the manifest"),
-    ));
-  }
+) -> Result<Option<String>, MessageError> {
+  let ttml_path = get_required_parameter(&job_result, &parameters.ttml_path, "ttml_path")?;
+  let ttml_role = get_required_parameter(&job_result, &parameters.ttml_role, "ttml_role")?;
+  let destination_manifest_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
 
-  let reference_ttml_path =
-    if let Ok(path) = Path::new(&parameters.ttml_path).strip_prefix(mp_folder.unwrap()) {
-      path.to_str().unwrap()
-    } else {
-      &parameters.ttml_path
-    };
+  let retiming = subtitle::Retiming::new(
+    parameters.ttml_time_offset,
+    parameters.ttml_frame_rate,
+    parameters.ttml_target_frame_rate,
+  )
+  .map_err(|message| processing_error(&job_result, &message))?;
 
-  let contents = fs::read_to_string(&parameters.source_path).map_err(|e| {
-    MessageError::ProcessingError(
-      job_result
-        .clone()
-        .with_status(JobStatus::Error)
-        .with_message(&e.to_string()),
+  // SubRip and retimed subtitles are written to a TTML sidecar file, next to the manifest by default
+  let is_subrip = subtitle::Format::from_extension(ttml_path) == Some(subtitle::Format::SubRip);
+  let (ttml_path, reference_ttml_path, conversion_warnings) = if is_subrip || retiming.is_some() {
+    let (converted_path, reference_path) = match &parameters.ttml_destination_path {
+      Some(path) => (
+        path.clone(),
+        rebase_url(path, "", destination_manifest_path),
+      ),
+      None => {
+        let stem = Path::new(ttml_path)
+          .file_stem()
+          .and_then(|stem| stem.to_str())
+          .unwrap_or("subtitle");
+        let file_name = if retiming.is_some() {
+          format!("{}_retimed.ttml", stem)
+        } else {
+          format!("{}.ttml", stem)
+        };
+        let converted_path = Path::new(destination_manifest_path)
+          .parent()
+          .unwrap_or_else(|| Path::new(""))
+          .join(&file_name);
+        // an existing file may be the source itself, or referenced by another manifest
+        if converted_path.exists() {
+          return Err(processing_error(
+            &job_result,
+            &format!(
+              "{} already exists, set the ttml_destination_path parameter to convert {}",
+              converted_path.display(),
+              ttml_path
+            ),
+          ));
+        }
+        (converted_path.to_string_lossy().to_string(), file_name)
+      }
+    };
+    let conversion_warnings = subtitle::convert(ttml_path, &converted_path, &retiming)
+      .map_err(|message| processing_error(&job_result, &message))?;
+    (converted_path, reference_path, conversion_warnings)
+  } else {
+    (
+      ttml_path.to_string(),
+      get_reference_path(&job_result, &parameters.source_path, ttml_path)?,
+      vec![],
     )
-  })?;
+  };
+  let ttml_path = ttml_path.as_str();
+  let media_info = MediaInfo::read(ttml_path).ok();
 
-  let mut manifest: Manifest = from_str(&contents).map_err(|message| {
-    MessageError::ProcessingError(
-      job_result
-        .clone()
-        .with_status(JobStatus::Error)
-        .with_message(&message),
-    )
-  })?;
-  let ttml_file_size = if let Ok(metadata) = fs::metadata(&parameters.ttml_path) {
+  // the language declared by the file is used when none is given
+  let declared_language = match &media_info {
+    Some(media_info) => media_info
+      .language
+      .clone()
+      .filter(|language| language != "und"),
+    None => subtitle::get_declared_language(ttml_path),
+  };
+  let (ttml_language, language_warning) = subtitle::resolve_language(
+    parameters.ttml_language.as_deref(),
+    declared_language.as_deref(),
+  )
+  .ok_or_else(|| processing_error(&job_result, "missing ttml_language parameter"))?;
+  let mut warnings: Vec<String> = language_warning.into_iter().collect();
+  warnings.extend(conversion_warnings);
+  let ttml_language = language::normalize(&ttml_language, &parameters.language_convention);
+  let ttml_language = ttml_language.as_str();
+
+  let mut manifest: Manifest = load_xml_file(&job_result, &parameters.source_path)?;
+
+  let ttml_file_size = if let Ok(metadata) = fs::metadata(ttml_path) {
     metadata.len()
   } else {
     0
   };
 
   if let Some(url) = &parameters.reference_url {
-    manifest.prefix_urls(&url);
+    manifest.prefix_urls(url);
   }
 
   if parameters.replace.unwrap_or(false) {
-    manifest.remove_adaptation_set(&parameters.ttml_language, &parameters.ttml_role);
+    manifest.remove_adaptation_set(ttml_language, ttml_role);
   }
-  let adaptation_set = AdaptationSet::new_ttml_subtitle(
+  let mut adaptation_set = AdaptationSet::new_ttml_subtitle(
     &reference_ttml_path,
-    &parameters.ttml_language,
-    &parameters.ttml_role,
+    ttml_language,
+    ttml_role,
     ttml_file_size,
   );
+
+  // subtitles packaged in MP4 (stpp) are described from the file itself
+  if let Some(media_info) = &media_info {
+    adaptation_set.update_from_media_info(media_info);
+  } else if !ttml_path.contains("://") {
+    let contents = fs::read_to_string(ttml_path)
+      .map_err(|e| processing_error(&job_result, &format!("{}: {}", ttml_path, e)))?;
+    let profile = ttml::detect_profile(&contents)
+      .map_err(|message| processing_error(&job_result, &format!("{}: {}", ttml_path, message)))?;
+    match profile {
+      Some(profile) => adaptation_set.set_codecs(profile.get_codecs()),
+      None => warnings.push(format!(
+        "{} declares no IMSC1 or EBU-TT-D profile, its codecs are not set",
+        ttml_path
+      )),
+    }
+  }
+
+  for accessibility in to_descriptors(&parameters.ttml_accessibility) {
+    adaptation_set.add_accessibility(accessibility);
+  }
+  for essential_property in to_descriptors(&parameters.ttml_essential_property) {
+    adaptation_set.add_essential_property(essential_property);
+  }
+  for supplemental_property in to_descriptors(&parameters.ttml_supplemental_property) {
+    adaptation_set.add_supplemental_property(supplemental_property);
+  }
+  if let Some(label) = &parameters.ttml_label {
+    adaptation_set.add_label(Label::new(label, Some(ttml_language)));
+  }
+
   manifest.add_adaptation_set(adaptation_set);
+  manifest.normalize_languages(&parameters.language_convention);
 
-  let updated_manifest = to_string(&manifest).map_err(|message| {
-    MessageError::ProcessingError(
-      job_result
-        .clone()
-        .with_status(JobStatus::Error)
-        .with_message(&message),
-    )
-  })?;
+  save_xml_file(&job_result, &manifest, destination_manifest_path)?;
+  Ok(Some(warnings.join("; ")).filter(|warning| !warning.is_empty()))
+}
 
-  let manifest_path = parameters.source_path.clone();
-  let destination_manifest_path = parameters.destination_path.unwrap_or_else(|| manifest_path);
-  fs::write(destination_manifest_path, &updated_manifest.into_bytes()).map_err(|e| {
-    MessageError::ProcessingError(
-      job_result
-        .clone()
-        .with_status(JobStatus::Error)
-        .with_message(&e.to_string()),
-    )
-  })?;
+fn convert_subtitle(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<Option<String>, MessageError> {
+  let ttml_path = get_required_parameter(&job_result, &parameters.ttml_path, "ttml_path")?;
+  let destination_path = get_required_parameter(
+    &job_result,
+    &parameters.destination_path,
+    "destination_path",
+  )?;
 
-  Ok(())
+  let warnings = subtitle::convert(ttml_path, destination_path, &None)
+    .map_err(|message| processing_error(&job_result, &message))?;
+  Ok(Some(warnings.join("; ")).filter(|warning| !warning.is_empty()))
 }
 
 #[test]
 fn add_subtitle_ttml_track() {
   let parameters = DashManifestParameters {
     source_path: "tests/sample_1.mpd".to_string(),
-    ttml_path: "tests/sample_subtitle.ttml".to_string(),
-    ttml_language: "fra".to_string(),
-    ttml_role: "subtitle".to_string(),
-    replace: None,
+    ttml_path: Some("tests/sample_subtitle.ttml".to_string()),
+    ttml_language: Some("fra".to_string()),
+    ttml_role: Some("subtitle".to_string()),
     destination_path: Some("tests/sample_1_updated.mpd".to_string()),
-    reference_url: None,
+    ..Default::default()
   };
   let job_result = JobResult::new(666);
-  add_ttml_subtitle(job_result, parameters).unwrap();
+  // the file declares no profile, so no codecs are set
+  let warning = add_ttml_subtitle(job_result, parameters).unwrap();
+  assert!(warning
+    .unwrap()
+    .contains("declares no IMSC1 or EBU-TT-D profile"));
 
   let reference = fs::read_to_string("tests/sample_1_for_validation.mpd").unwrap();
   let content = fs::read_to_string("tests/sample_1_updated.mpd").unwrap();
@@ -124,12 +282,13 @@ fn add_subtitle_ttml_track() {
 fn replace_subtitle_ttml_track_with_reference() {
   let parameters = DashManifestParameters {
     source_path: "tests/sample_1.mpd".to_string(),
-    ttml_path: "tests/sample_subtitle.ttml".to_string(),
-    ttml_language: "qaa".to_string(),
-    ttml_role: "subtitle".to_string(),
+    ttml_path: Some("tests/sample_subtitle.ttml".to_string()),
+    ttml_language: Some("qaa".to_string()),
+    ttml_role: Some("subtitle".to_string()),
     replace: Some(false),
     destination_path: Some("tests/sample_1_replaced.mpd".to_string()),
     reference_url: Some("http://server.com/dash/manifest.mpd".to_string()),
+    ..Default::default()
   };
   let job_result = JobResult::new(666);
 
@@ -145,12 +304,13 @@ fn replace_subtitle_ttml_track_with_reference() {
 fn add_http_subtitle_ttml_track() {
   let parameters = DashManifestParameters {
     source_path: "tests/sample_1.mpd".to_string(),
-    ttml_path: "http://server/static/sample_subtitle.ttml".to_string(),
-    ttml_language: "fra".to_string(),
-    ttml_role: "subtitle".to_string(),
+    ttml_path: Some("http://server/static/sample_subtitle.ttml".to_string()),
+    ttml_language: Some("fra".to_string()),
+    ttml_role: Some("subtitle".to_string()),
     replace: Some(false),
     destination_path: Some("tests/sample_1_http_ttml.mpd".to_string()),
     reference_url: None,
+    ..Default::default()
   };
   let job_result = JobResult::new(666);
 
@@ -161,3 +321,267 @@ fn add_http_subtitle_ttml_track() {
 
   assert_eq!(content, reference);
 }
+
+#[test]
+fn add_subtitle_ttml_track_with_descriptors() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle.ttml".to_string()),
+    ttml_language: Some("fra".to_string()),
+    ttml_role: Some("subtitle".to_string()),
+    ttml_accessibility: Some(vec![DescriptorParameters {
+      scheme_id_uri: "urn:tva:metadata:cs:AudioPurposeCS:2007".to_string(),
+      value: Some("2".to_string()),
+    }]),
+    ttml_supplemental_property: Some(vec![DescriptorParameters {
+      scheme_id_uri: "urn:mpeg:dash:hard-of-hearing:2020".to_string(),
+      value: None,
+    }]),
+    ttml_label: Some("Sous-titres malentendants".to_string()),
+    destination_path: Some("tests/sample_1_descriptors.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_ttml_subtitle(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_descriptors.mpd").unwrap();
+  assert!(content.contains(
+    r#"<Accessibility schemeIdUri="urn:tva:metadata:cs:AudioPurposeCS:2007" value="2" />"#
+  ));
+  assert!(content
+    .contains(r#"<SupplementalProperty schemeIdUri="urn:mpeg:dash:hard-of-hearing:2020" />"#));
+  assert!(content.contains(r#"<Label lang="fra">Sous-titres malentendants</Label>"#));
+
+  let manifest: Result<Manifest, String> = yaserde::de::from_str(&content);
+  assert!(manifest.is_ok());
+}
+
+#[test]
+fn add_subtitle_mp4_track() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle_dashinit.mp4".to_string()),
+    ttml_language: Some("fra".to_string()),
+    ttml_role: Some("subtitle".to_string()),
+    destination_path: Some("tests/sample_1_mp4_subtitle.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_ttml_subtitle(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_mp4_subtitle.mpd").unwrap();
+  assert!(content.contains(r#"<mpd:AdaptationSet segmentAlignment="true" lang="fra" subsegmentAlignment="true" subsegmentStartsWithSAP="1" contentType="text">"#));
+  assert!(content.contains(r#"<mpd:Representation mpd:id="s1" mpd:mimeType="application/mp4" mpd:codecs="stpp" mpd:startWithSAP="1" mpd:bandwidth="3472"><mpd:BaseURL>sample_subtitle_dashinit.mp4</mpd:BaseURL><mpd:SegmentBase indexRangeExact="true" indexRange="568-635" timescale="1000"><Initialization range="0-567" /></mpd:SegmentBase></mpd:Representation>"#));
+}
+
+#[test]
+fn add_invalid_subtitle_ttml_track() {
+  for ttml_path in &["tests/missing.ttml", "tests/sample_subtitle.vtt"] {
+    let parameters = DashManifestParameters {
+      source_path: "tests/sample_1.mpd".to_string(),
+      ttml_path: Some(ttml_path.to_string()),
+      ttml_language: Some("fra".to_string()),
+      ttml_role: Some("subtitle".to_string()),
+      destination_path: Some("tests/sample_1_invalid_ttml.mpd".to_string()),
+      ..Default::default()
+    };
+    let job_result = JobResult::new(666);
+    assert!(add_ttml_subtitle(job_result, parameters).is_err());
+  }
+  assert!(!Path::new("tests/sample_1_invalid_ttml.mpd").exists());
+}
+
+#[test]
+fn add_subtitle_ttml_track_with_declared_language() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle_en.ttml".to_string()),
+    ttml_role: Some("subtitle".to_string()),
+    destination_path: Some("tests/sample_1_declared_language.mpd".to_string()),
+    ..Default::default()
+  };
+  let get_message = |job_result: &JobResult| {
+    job_result
+      .get_parameters()
+      .iter()
+      .find(|parameter| parameter.id == "message")
+      .and_then(|parameter| parameter.value.clone())
+  };
+  let job_result = process(None, parameters.clone(), JobResult::new(666)).unwrap();
+  assert_eq!(get_message(&job_result), None);
+
+  let content = fs::read_to_string("tests/sample_1_declared_language.mpd").unwrap();
+  assert!(
+    content.contains(r#"<mpd:AdaptationSet segmentAlignment="true" lang="en" contentType="text">"#)
+  );
+  assert!(content.contains(r#"mpd:mimeType="application/ttml+xml" mpd:codecs="im1t""#));
+
+  // the given language is kept, with a warning
+  let parameters = DashManifestParameters {
+    ttml_language: Some("fra".to_string()),
+    ..parameters
+  };
+  let job_result = process(None, parameters, JobResult::new(666)).unwrap();
+  let message = get_message(&job_result).unwrap();
+  assert!(message.as_str().unwrap().contains("conflicts"));
+
+  let content = fs::read_to_string("tests/sample_1_declared_language.mpd").unwrap();
+  assert!(content
+    .contains(r#"<mpd:AdaptationSet segmentAlignment="true" lang="fra" contentType="text">"#));
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle.ttml".to_string()),
+    ttml_role: Some("subtitle".to_string()),
+    destination_path: Some("tests/sample_1_declared_language.mpd".to_string()),
+    ..Default::default()
+  };
+  assert!(process(None, parameters, JobResult::new(666)).is_err());
+}
+
+#[test]
+fn add_subtitle_ttml_track_with_language_convention() {
+  use crate::LanguageConvention;
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle.ttml".to_string()),
+    ttml_language: Some("fre".to_string()),
+    ttml_role: Some("subtitle".to_string()),
+    language_convention: Some(LanguageConvention::Bcp47),
+    destination_path: Some("tests/sample_1_bcp_47.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_ttml_subtitle(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_bcp_47.mpd").unwrap();
+  assert!(
+    content.contains(r#"<mpd:AdaptationSet segmentAlignment="true" lang="fr" contentType="text">"#)
+  );
+  // languages without two-letter code are kept
+  assert!(content.contains(r#"<mpd:AdaptationSet segmentAlignment="false" lang="qaa">"#));
+}
+
+#[test]
+fn add_subtitle_srt_track() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle.srt".to_string()),
+    ttml_language: Some("fra".to_string()),
+    ttml_role: Some("subtitle".to_string()),
+    ttml_destination_path: Some("tests/sample_subtitle_from_srt.ttml".to_string()),
+    destination_path: Some("tests/sample_1_srt.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_ttml_subtitle(job_result, parameters.clone()).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_srt.mpd").unwrap();
+  assert!(content.contains("<mpd:BaseURL>sample_subtitle_from_srt.ttml</mpd:BaseURL>"));
+
+  let content = fs::read_to_string("tests/sample_subtitle_from_srt.ttml").unwrap();
+  assert!(content.contains(r#"<p xml:id="cue1" begin="00:00:00.760" end="00:00:03.450">It seems a paradox, does it not,</p>"#));
+
+  // the TTML file next to the manifest is not overwritten
+  let parameters = DashManifestParameters {
+    ttml_destination_path: None,
+    ..parameters
+  };
+  let job_result = JobResult::new(666);
+  let reference = fs::read_to_string("tests/sample_subtitle.ttml").unwrap();
+  assert!(add_ttml_subtitle(job_result, parameters).is_err());
+  assert_eq!(
+    fs::read_to_string("tests/sample_subtitle.ttml").unwrap(),
+    reference
+  );
+}
+
+#[test]
+fn add_retimed_subtitle_ttml_track() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle.ttml".to_string()),
+    ttml_language: Some("fra".to_string()),
+    ttml_role: Some("subtitle".to_string()),
+    ttml_time_offset: Some(1.0),
+    ttml_frame_rate: Some(24.0),
+    ttml_target_frame_rate: Some(25.0),
+    ttml_destination_path: Some("tests/retimed/sample_subtitle.ttml".to_string()),
+    destination_path: Some("tests/sample_1_retimed.mpd".to_string()),
+    ..Default::default()
+  };
+  fs::create_dir_all("tests/retimed").unwrap();
+  let job_result = JobResult::new(666);
+  add_ttml_subtitle(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_1_retimed.mpd").unwrap();
+  assert!(content.contains("<mpd:BaseURL>retimed/sample_subtitle.ttml</mpd:BaseURL>"));
+
+  let content = fs::read_to_string("tests/retimed/sample_subtitle.ttml").unwrap();
+  assert!(content.contains(r#"begin="00:00:05.760" end="00:00:10.560""#));
+  // the styles and comments of the document are kept
+  assert!(content.contains(r#"tts:fontSize="22px""#));
+  assert!(content.contains("<!-- s1 specifies default color, font, and text alignment -->"));
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle.ttml".to_string()),
+    ttml_language: Some("fra".to_string()),
+    ttml_role: Some("subtitle".to_string()),
+    ttml_frame_rate: Some(24.0),
+    destination_path: Some("tests/sample_1_retimed.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(add_ttml_subtitle(job_result, parameters).is_err());
+}
+
+#[test]
+fn convert_webvtt_subtitle_to_ttml() {
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::ConvertSubtitle),
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle.vtt".to_string()),
+    destination_path: Some("tests/sample_subtitle_from_vtt.ttml".to_string()),
+    ..Default::default()
+  };
+  process(None, parameters, JobResult::new(666)).unwrap();
+
+  let content = fs::read_to_string("tests/sample_subtitle_from_vtt.ttml").unwrap();
+  assert!(content.contains(r#"<p xml:id="cue2" begin="00:00:05.000" end="00:00:10.000">that the image formed on<br/>the Retina should be inverted?</p>"#));
+
+  // what the cues do not keep is reported in the job message
+  fs::write(
+    "tests/sample_subtitle_voice.vtt",
+    "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\n<v Narrator>Hello</v>\n",
+  )
+  .unwrap();
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::ConvertSubtitle),
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle_voice.vtt".to_string()),
+    destination_path: Some("tests/sample_subtitle_voice.ttml".to_string()),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(666)).unwrap();
+  let message = job_result
+    .get_parameters()
+    .iter()
+    .find(|parameter| parameter.id == "message")
+    .and_then(|parameter| parameter.value.clone());
+  assert_eq!(
+    message,
+    Some(serde_json::Value::String(
+      "the voice names of the WebVTT cues are not kept".to_string()
+    ))
+  );
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::ConvertSubtitle),
+    source_path: "tests/sample_1.mpd".to_string(),
+    ttml_path: Some("tests/sample_subtitle.vtt".to_string()),
+    ..Default::default()
+  };
+  assert!(process(None, parameters, JobResult::new(666)).is_err());
+}
@@ -1,2 +1,8 @@
+mod audio;
+mod events;
+mod generate;
+mod hls;
 mod manifest;
+mod merge;
 pub mod message;
+mod periods;
@@ -0,0 +1,260 @@
+use crate::dash::manifest::Manifest;
+use crate::dash::message::get_required_parameter;
+use crate::utils::{load_xml_file, processing_error, rebase_url, save_xml_file};
+use crate::DashManifestParameters;
+use mcai_worker_sdk::{job::JobResult, MessageError};
+
+pub fn split_periods(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<(), MessageError> {
+  let cues = parameters
+    .cues
+    .as_ref()
+    .filter(|cues| !cues.is_empty())
+    .ok_or_else(|| processing_error(&job_result, "missing cues parameter"))?;
+
+  let mut manifest: Manifest = load_xml_file(&job_result, &parameters.source_path)?;
+
+  if let Some(url) = &parameters.reference_url {
+    manifest.prefix_urls(url);
+  }
+
+  let times: Vec<f64> = cues.iter().map(|cue| cue.time).collect();
+  manifest
+    .split_periods(&times)
+    .map_err(|message| processing_error(&job_result, &message))?;
+
+  let destination_manifest_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+  save_xml_file(&job_result, &manifest, destination_manifest_path)
+}
+
+pub fn concatenate_manifests(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<(), MessageError> {
+  let source_manifests = parameters
+    .source_manifests
+    .as_ref()
+    .filter(|source_manifests| !source_manifests.is_empty())
+    .ok_or_else(|| processing_error(&job_result, "missing source_manifests parameter"))?;
+
+  let destination_manifest_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+
+  let mut presentation: Option<Manifest> = None;
+  for source_manifest in source_manifests {
+    let mut manifest: Manifest = load_xml_file(&job_result, &source_manifest.path)?;
+    manifest.update_urls(|url| rebase_url(url, &source_manifest.path, destination_manifest_path));
+    manifest.set_asset_identifier(
+      source_manifest
+        .asset_identifier
+        .as_ref()
+        .unwrap_or(&source_manifest.path),
+    );
+
+    match &mut presentation {
+      Some(presentation) => presentation.append(manifest).map_err(|message| {
+        processing_error(
+          &job_result,
+          &format!("{}: {}", source_manifest.path, message),
+        )
+      })?,
+      None => presentation = Some(manifest),
+    }
+  }
+
+  // the list of source manifests is not empty, a single one being numbered as appended ones
+  let mut presentation = presentation.unwrap();
+  presentation.set_period_bounds();
+  presentation.renumber_periods();
+  if let Some(url) = &parameters.reference_url {
+    presentation.prefix_urls(url);
+  }
+
+  save_xml_file(&job_result, &presentation, destination_manifest_path)
+}
+
+pub fn clip_manifest(
+  job_result: JobResult,
+  parameters: DashManifestParameters,
+) -> Result<(), MessageError> {
+  // the source presentation is kept untouched
+  let destination_manifest_path = get_required_parameter(
+    &job_result,
+    &parameters.destination_path,
+    "destination_path",
+  )?;
+
+  let mut manifest: Manifest = load_xml_file(&job_result, &parameters.source_path)?;
+  manifest.update_urls(|url| rebase_url(url, &parameters.source_path, destination_manifest_path));
+  manifest
+    .clip(parameters.start.unwrap_or_default(), parameters.end)
+    .map_err(|message| processing_error(&job_result, &message))?;
+
+  if let Some(url) = &parameters.reference_url {
+    manifest.prefix_urls(url);
+  }
+
+  save_xml_file(&job_result, &manifest, destination_manifest_path)
+}
+
+#[test]
+fn split_manifest_periods() {
+  use crate::{CueParameters, DashAction};
+  use std::fs;
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::SplitPeriods),
+    source_path: "tests/sample_4.mpd".to_string(),
+    cues: Some(vec![
+      CueParameters {
+        time: 20.0,
+        duration: None,
+      },
+      CueParameters {
+        time: 10.0,
+        duration: None,
+      },
+    ]),
+    destination_path: Some("tests/sample_4_split.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  split_periods(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_4_split.mpd").unwrap();
+  assert!(content.contains(r#"<Period id="1-1" start="PT0H0M0.000S" duration="PT0H0M10.000S">"#));
+  assert!(content.contains(r#"<Period id="1-2" start="PT0H0M10.000S" duration="PT0H0M10.000S">"#));
+  assert!(content.contains(r#"<Period id="1-3" start="PT0H0M20.000S" duration="PT0H0M10.000S">"#));
+  assert!(content.contains(r#"media="video/$RepresentationID$/segment_$Number$.m4s" initialization="video/$RepresentationID$/init.mp4" timescale="12800" startNumber="6" presentationTimeOffset="128000"><mpd:SegmentTimeline><mpd:S t="128000" d="25600" r="4" /></mpd:SegmentTimeline>"#));
+  assert!(content.contains(r#"media="text/fra/segment_$Number$.m4s" initialization="text/fra/init.mp4" timescale="1000" startNumber="11" duration="2000" presentationTimeOffset="20000" />"#));
+}
+
+#[test]
+fn split_periods_out_of_segment_boundaries() {
+  use crate::CueParameters;
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4.mpd".to_string(),
+    cues: Some(vec![CueParameters {
+      time: 3.0,
+      duration: None,
+    }]),
+    destination_path: Some("tests/sample_4_split_error.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(split_periods(job_result, parameters).is_err());
+}
+
+#[test]
+fn concatenate_manifest_periods() {
+  use crate::{DashAction, SourceManifestParameters};
+  use std::fs;
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::Concatenate),
+    source_path: "tests/sample_concatenated.mpd".to_string(),
+    source_manifests: Some(vec![
+      SourceManifestParameters {
+        path: "tests/sample_4.mpd".to_string(),
+        asset_identifier: Some("intro".to_string()),
+      },
+      SourceManifestParameters {
+        path: "tests/sample_1.mpd".to_string(),
+        asset_identifier: None,
+      },
+    ]),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  concatenate_manifests(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_concatenated.mpd").unwrap();
+  assert!(content.contains(r#"mediaPresentationDuration="PT0H2M36.520S" maxSegmentDuration="PT0H0M2.520S" profiles="urn:mpeg:dash:profile:isoff-live:2011,urn:mpeg:dash:profile:isoff-on-demand:2011""#));
+  assert!(content.contains(r#"<Period id="1" start="PT0H0M0.000S" duration="PT0H0M30.000S"><mpd:AssetIdentifier schemeIdUri="urn:org:dashif:asset-id:2013" value="intro" />"#));
+  assert!(content.contains(r#"<Period id="2" start="PT0H0M30.000S" duration="PT0H2M6.520S"><mpd:AssetIdentifier schemeIdUri="urn:org:dashif:asset-id:2013" value="tests/sample_1.mpd" />"#));
+  assert!(content.contains(
+    r#"<mpd:BaseURL>191496242-5bd703996a1d5-standard1_track1_dashinit.mp4</mpd:BaseURL>"#
+  ));
+
+  let manifest: Result<Manifest, String> = yaserde::de::from_str(&content);
+  assert_eq!(manifest.unwrap().get_period_bounds().len(), 2);
+
+  // a single source manifest has its Periods numbered as well
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_single_concatenated.mpd".to_string(),
+    source_manifests: Some(vec![SourceManifestParameters {
+      path: "tests/sample_4.mpd".to_string(),
+      asset_identifier: Some("intro".to_string()),
+    }]),
+    ..Default::default()
+  };
+  concatenate_manifests(JobResult::new(666), parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_single_concatenated.mpd").unwrap();
+  assert!(content.contains(r#"<Period id="1" start="PT0H0M0.000S" duration="PT0H0M30.000S"><mpd:AssetIdentifier schemeIdUri="urn:org:dashif:asset-id:2013" value="intro" />"#));
+}
+
+#[test]
+fn clip_manifest_range() {
+  use crate::DashAction;
+  use std::fs;
+
+  let parameters = DashManifestParameters {
+    action: Some(DashAction::Clip),
+    source_path: "tests/sample_4.mpd".to_string(),
+    start: Some(4.0),
+    end: Some(14.0),
+    destination_path: Some("tests/sample_4_clip.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  clip_manifest(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_4_clip.mpd").unwrap();
+  assert!(content.contains(r#"mediaPresentationDuration="PT0H0M10.000S""#));
+  assert!(content.contains(r#"<Period duration="PT0H0M10.000S">"#));
+  assert!(content.contains(r#"timescale="12800" startNumber="3" presentationTimeOffset="51200"><mpd:SegmentTimeline><mpd:S t="51200" d="25600" r="4" /></mpd:SegmentTimeline>"#));
+  assert!(content.contains(r#"timescale="48000" startNumber="2" presentationTimeOffset="192000"><mpd:SegmentTimeline><mpd:S t="96256" d="96256" r="5" /></mpd:SegmentTimeline>"#));
+  assert!(content.contains(
+    r#"timescale="1000" startNumber="3" duration="2000" presentationTimeOffset="4000" />"#
+  ));
+}
+
+#[test]
+fn clip_manifest_errors() {
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4.mpd".to_string(),
+    start: Some(4.0),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(clip_manifest(job_result, parameters).is_err());
+
+  // constant duration segments can not start before the clip
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4.mpd".to_string(),
+    start: Some(5.0),
+    end: Some(15.0),
+    destination_path: Some("tests/sample_4_clip_error.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(clip_manifest(job_result, parameters).is_err());
+
+  let parameters = DashManifestParameters {
+    source_path: "tests/sample_4.mpd".to_string(),
+    start: Some(40.0),
+    destination_path: Some("tests/sample_4_clip_error.mpd".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(clip_manifest(job_result, parameters).is_err());
+}
@@ -0,0 +1,152 @@
+use crate::hls::playlist::{MediaPlaylist, MediaType, MultivariantPlaylist};
+use crate::hls::subtitle::{add_subtitle, convert_subtitle, generate_subtitle_playlist};
+use crate::utils::{load_file, processing_error};
+use crate::{HlsAction, HlsManifestParameters};
+use mcai_worker_sdk::{
+  job::{JobResult, JobStatus},
+  warn, McaiChannel, MessageError, Parameter, ParameterValue,
+};
+use std::path::Path;
+
+pub fn process(
+  _channel: Option<McaiChannel>,
+  parameters: HlsManifestParameters,
+  job_result: JobResult,
+) -> Result<JobResult, MessageError> {
+  match parameters.action.clone().unwrap_or(HlsAction::Inspect) {
+    HlsAction::Inspect => {
+      let mut sources = get_playlist_sources(job_result.clone(), &parameters.source_path)?;
+      return Ok(
+        job_result
+          .with_status(JobStatus::Completed)
+          .with_parameters(&mut sources),
+      );
+    }
+    HlsAction::AddSubtitle => {
+      if let Some(warning) = add_subtitle(job_result.clone(), parameters)? {
+        warn!("{}", warning);
+        return Ok(
+          job_result
+            .with_status(JobStatus::Completed)
+            .with_message(&warning),
+        );
+      }
+    }
+    HlsAction::GenerateSubtitlePlaylist => {
+      if let Some(warning) = generate_subtitle_playlist(job_result.clone(), parameters)? {
+        warn!("{}", warning);
+        return Ok(
+          job_result
+            .with_status(JobStatus::Completed)
+            .with_message(&warning),
+        );
+      }
+    }
+    HlsAction::ConvertSubtitle => {
+      if let Some(warning) = convert_subtitle(job_result.clone(), parameters)? {
+        warn!("{}", warning);
+        return Ok(
+          job_result
+            .with_status(JobStatus::Completed)
+            .with_message(&warning),
+        );
+      }
+    }
+  }
+
+  Ok(job_result.with_status(JobStatus::Completed))
+}
+
+/// Media playlist referenced by a multivariant playlist
+pub fn load_referenced_media_playlist(
+  job_result: &JobResult,
+  playlist_path: &str,
+  uri: &str,
+) -> Result<MediaPlaylist, MessageError> {
+  if uri.contains("://") {
+    return Err(processing_error(
+      job_result,
+      &format!("unable to read the remote media playlist {}", uri),
+    ));
+  }
+  let path = Path::new(playlist_path)
+    .parent()
+    .unwrap_or_else(|| Path::new(""))
+    .join(uri);
+  load_file(job_result, &path.to_string_lossy())
+}
+
+fn get_playlist_sources(job_result: JobResult, path: &str) -> Result<Vec<Parameter>, MessageError> {
+  let playlist: MultivariantPlaylist = load_file(&job_result, path)?;
+
+  // alternative video renditions are listed after the variant streams
+  let mut video_sources = playlist.get_variant_stream_uris();
+  video_sources.extend(playlist.get_rendition_uris(&MediaType::Video));
+
+  Ok(vec![
+    get_sources_parameter("audio", playlist.get_rendition_uris(&MediaType::Audio)),
+    get_sources_parameter("video", video_sources),
+    get_sources_parameter(
+      "subtitles",
+      playlist.get_rendition_uris(&MediaType::Subtitles),
+    ),
+  ])
+}
+
+fn get_sources_parameter(id: &str, sources: Vec<String>) -> Parameter {
+  Parameter {
+    id: id.to_string(),
+    kind: Vec::<String>::get_type_as_string(),
+    store: None,
+    default: None,
+    value: serde_json::to_value(sources).ok(),
+  }
+}
+
+#[test]
+fn ack_message_test() {
+  use mcai_worker_sdk::{job::Job, parameter::container::ParametersContainer};
+
+  let message = r#"{
+    "parameters": [
+      {
+        "id": "source_path",
+        "type": "string",
+        "value": "tests/sample_6.m3u8"
+      }
+    ],
+    "job_id":690
+  }"#;
+
+  let job = Job::new(message).unwrap();
+  let job_result = JobResult::new(job.job_id);
+  let parameters: HlsManifestParameters = job.get_parameters().unwrap();
+  let result = process(None, parameters, job_result);
+
+  assert!(result.is_ok());
+  let job_result = result.unwrap();
+  let audio_sources = job_result.get_parameter::<Vec<String>>("audio");
+  assert_eq!(
+    audio_sources,
+    Ok(vec![
+      "sample_6_a1.m3u8".to_string(),
+      "sample_6_a2.m3u8".to_string()
+    ])
+  );
+  let video_sources = job_result.get_parameter::<Vec<String>>("video");
+  assert_eq!(
+    video_sources,
+    Ok(vec![
+      "sample_6_v1.m3u8".to_string(),
+      "sample_6_v2.m3u8".to_string()
+    ])
+  );
+  let subtitles_sources = job_result.get_parameter::<Vec<String>>("subtitles");
+  assert_eq!(subtitles_sources, Ok(vec!["sample_6_s1.m3u8".to_string()]));
+}
+
+#[test]
+fn media_playlist_source_error() {
+  let job_result = JobResult::new(123);
+  assert!(get_playlist_sources(job_result, "tests/sample_6_v1.m3u8").is_err());
+}
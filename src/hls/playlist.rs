@@ -0,0 +1,806 @@
+use crate::utils::{split_once, strip_prefix};
+use std::fmt;
+use std::str::FromStr;
+
+/// Multivariant (master) playlist, listing the variant streams and their alternative renditions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MultivariantPlaylist {
+  pub version: Option<u8>,
+  pub independent_segments: bool,
+  /// Unsupported tags preceding the renditions and variant streams (e.g. EXT-X-SESSION-DATA)
+  pub header_tags: Vec<String>,
+  pub renditions: Vec<Rendition>,
+  pub variant_streams: Vec<VariantStream>,
+  /// Unsupported tags following the renditions and variant streams (e.g. EXT-X-I-FRAME-STREAM-INF)
+  pub trailing_tags: Vec<String>,
+}
+
+impl MultivariantPlaylist {
+  /// URIs of the media playlists of the renditions with a type
+  pub fn get_rendition_uris(&self, media_type: &MediaType) -> Vec<String> {
+    self
+      .renditions
+      .iter()
+      .filter(|rendition| rendition.media_type == *media_type)
+      .filter_map(|rendition| rendition.uri.clone())
+      .collect()
+  }
+
+  pub fn get_variant_stream_uris(&self) -> Vec<String> {
+    self
+      .variant_streams
+      .iter()
+      .map(|variant_stream| variant_stream.uri.clone())
+      .collect()
+  }
+}
+
+/// Alternative rendition (EXT-X-MEDIA)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rendition {
+  pub media_type: MediaType,
+  pub group_id: String,
+  pub name: String,
+  pub language: Option<String>,
+  pub default: bool,
+  pub autoselect: bool,
+  pub forced: bool,
+  pub instream_id: Option<String>,
+  pub characteristics: Option<String>,
+  pub channels: Option<String>,
+  pub uri: Option<String>,
+  /// Unsupported attributes, as written in the playlist
+  pub other_attributes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaType {
+  Audio,
+  Video,
+  Subtitles,
+  ClosedCaptions,
+}
+
+impl Default for MediaType {
+  fn default() -> Self {
+    MediaType::Audio
+  }
+}
+
+/// Variant stream (EXT-X-STREAM-INF)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VariantStream {
+  pub bandwidth: u64,
+  pub average_bandwidth: Option<u64>,
+  pub codecs: Option<String>,
+  pub resolution: Option<(u32, u32)>,
+  pub frame_rate: Option<f64>,
+  pub video: Option<String>,
+  pub audio: Option<String>,
+  pub subtitles: Option<String>,
+  pub closed_captions: Option<String>,
+  /// Unsupported attributes, as written in the playlist (e.g. VIDEO-RANGE=SDR)
+  pub other_attributes: Vec<String>,
+  pub uri: String,
+}
+
+/// Media playlist, listing the segments of a rendition
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaPlaylist {
+  pub version: Option<u8>,
+  pub target_duration: u64,
+  pub media_sequence: Option<u64>,
+  pub playlist_type: Option<String>,
+  pub independent_segments: bool,
+  pub segments: Vec<MediaSegment>,
+  pub end_list: bool,
+}
+
+impl MediaPlaylist {
+  /// Video on demand playlist, its target duration covering the longest segment
+  pub fn new_vod(version: u8, segments: Vec<MediaSegment>) -> Self {
+    let target_duration = segments
+      .iter()
+      .map(|segment| segment.duration.round() as u64)
+      .max()
+      .unwrap_or_default();
+
+    MediaPlaylist {
+      version: Some(version),
+      target_duration,
+      media_sequence: None,
+      playlist_type: Some("VOD".to_string()),
+      independent_segments: true,
+      segments,
+      end_list: true,
+    }
+  }
+
+  pub fn get_duration(&self) -> f64 {
+    self.segments.iter().map(|segment| segment.duration).sum()
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaSegment {
+  pub duration: f64,
+  pub title: Option<String>,
+  pub byte_range: Option<ByteRange>,
+  pub discontinuity: bool,
+  /// Encryption key (EXT-X-KEY) applying to the segment
+  pub key: Option<Key>,
+  /// Media initialization section (EXT-X-MAP) applying to the segment
+  pub map: Option<Map>,
+  pub uri: String,
+}
+
+/// Sub-range of a resource, the offset following the previous range when it is not specified
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ByteRange {
+  pub length: u64,
+  pub offset: Option<u64>,
+}
+
+impl ByteRange {
+  /// Range between two inclusive positions
+  pub fn new(start: u64, end: u64) -> Self {
+    ByteRange {
+      length: end + 1 - start,
+      offset: Some(start),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Key {
+  pub method: String,
+  pub uri: Option<String>,
+  pub iv: Option<String>,
+  pub key_format: Option<String>,
+  pub key_format_versions: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Map {
+  pub uri: String,
+  pub byte_range: Option<ByteRange>,
+}
+
+impl fmt::Display for MultivariantPlaylist {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "#EXTM3U")?;
+    if let Some(version) = self.version {
+      writeln!(f, "#EXT-X-VERSION:{}", version)?;
+    }
+    if self.independent_segments {
+      writeln!(f, "#EXT-X-INDEPENDENT-SEGMENTS")?;
+    }
+    for tag in &self.header_tags {
+      writeln!(f, "{}", tag)?;
+    }
+    for rendition in &self.renditions {
+      writeln!(f, "{}", rendition)?;
+    }
+    for variant_stream in &self.variant_streams {
+      write!(f, "{}", variant_stream)?;
+    }
+    for tag in &self.trailing_tags {
+      writeln!(f, "{}", tag)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Rendition {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "#EXT-X-MEDIA:TYPE={},GROUP-ID=\"{}\",NAME=\"{}\"",
+      self.media_type, self.group_id, self.name
+    )?;
+    if let Some(language) = &self.language {
+      write!(f, ",LANGUAGE=\"{}\"", language)?;
+    }
+    write!(
+      f,
+      ",DEFAULT={},AUTOSELECT={}",
+      yes_or_no(self.default),
+      yes_or_no(self.autoselect)
+    )?;
+    if self.media_type == MediaType::Subtitles {
+      write!(f, ",FORCED={}", yes_or_no(self.forced))?;
+    }
+    if let Some(instream_id) = &self.instream_id {
+      write!(f, ",INSTREAM-ID=\"{}\"", instream_id)?;
+    }
+    if let Some(characteristics) = &self.characteristics {
+      write!(f, ",CHARACTERISTICS=\"{}\"", characteristics)?;
+    }
+    if let Some(channels) = &self.channels {
+      write!(f, ",CHANNELS=\"{}\"", channels)?;
+    }
+    for attribute in &self.other_attributes {
+      write!(f, ",{}", attribute)?;
+    }
+    if let Some(uri) = &self.uri {
+      write!(f, ",URI=\"{}\"", uri)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for MediaType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let media_type = match self {
+      MediaType::Audio => "AUDIO",
+      MediaType::Video => "VIDEO",
+      MediaType::Subtitles => "SUBTITLES",
+      MediaType::ClosedCaptions => "CLOSED-CAPTIONS",
+    };
+    write!(f, "{}", media_type)
+  }
+}
+
+impl fmt::Display for VariantStream {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "#EXT-X-STREAM-INF:BANDWIDTH={}", self.bandwidth)?;
+    if let Some(average_bandwidth) = self.average_bandwidth {
+      write!(f, ",AVERAGE-BANDWIDTH={}", average_bandwidth)?;
+    }
+    if let Some(codecs) = &self.codecs {
+      write!(f, ",CODECS=\"{}\"", codecs)?;
+    }
+    if let Some((width, height)) = self.resolution {
+      write!(f, ",RESOLUTION={}x{}", width, height)?;
+    }
+    if let Some(frame_rate) = self.frame_rate {
+      write!(f, ",FRAME-RATE={:.3}", frame_rate)?;
+    }
+    if let Some(video) = &self.video {
+      write!(f, ",VIDEO=\"{}\"", video)?;
+    }
+    if let Some(audio) = &self.audio {
+      write!(f, ",AUDIO=\"{}\"", audio)?;
+    }
+    if let Some(subtitles) = &self.subtitles {
+      write!(f, ",SUBTITLES=\"{}\"", subtitles)?;
+    }
+    // the absence of closed captions is an enumerated value
+    match self.closed_captions.as_deref() {
+      Some("NONE") => write!(f, ",CLOSED-CAPTIONS=NONE")?,
+      Some(closed_captions) => write!(f, ",CLOSED-CAPTIONS=\"{}\"", closed_captions)?,
+      None => {}
+    }
+    for attribute in &self.other_attributes {
+      write!(f, ",{}", attribute)?;
+    }
+    writeln!(f)?;
+    writeln!(f, "{}", self.uri)
+  }
+}
+
+impl fmt::Display for MediaPlaylist {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "#EXTM3U")?;
+    if let Some(version) = self.version {
+      writeln!(f, "#EXT-X-VERSION:{}", version)?;
+    }
+    writeln!(f, "#EXT-X-TARGETDURATION:{}", self.target_duration)?;
+    if let Some(media_sequence) = self.media_sequence {
+      writeln!(f, "#EXT-X-MEDIA-SEQUENCE:{}", media_sequence)?;
+    }
+    if let Some(playlist_type) = &self.playlist_type {
+      writeln!(f, "#EXT-X-PLAYLIST-TYPE:{}", playlist_type)?;
+    }
+    if self.independent_segments {
+      writeln!(f, "#EXT-X-INDEPENDENT-SEGMENTS")?;
+    }
+
+    // the key and the initialization section apply until the next EXT-X-KEY and EXT-X-MAP tags
+    let mut current_key = None;
+    let mut current_map = None;
+    for segment in &self.segments {
+      if segment.discontinuity {
+        writeln!(f, "#EXT-X-DISCONTINUITY")?;
+      }
+      if segment.key.as_ref() != current_key {
+        match &segment.key {
+          Some(key) => writeln!(f, "{}", key)?,
+          None => writeln!(f, "#EXT-X-KEY:METHOD=NONE")?,
+        }
+        current_key = segment.key.as_ref();
+      }
+      if let Some(map) = &segment.map {
+        if Some(map) != current_map || segment.discontinuity {
+          writeln!(f, "{}", map)?;
+          current_map = Some(map);
+        }
+      }
+      writeln!(
+        f,
+        "#EXTINF:{:.3},{}",
+        segment.duration,
+        segment.title.as_deref().unwrap_or_default()
+      )?;
+      if let Some(byte_range) = &segment.byte_range {
+        writeln!(f, "#EXT-X-BYTERANGE:{}", byte_range)?;
+      }
+      writeln!(f, "{}", segment.uri)?;
+    }
+
+    if self.end_list {
+      writeln!(f, "#EXT-X-ENDLIST")?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for ByteRange {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.length)?;
+    if let Some(offset) = self.offset {
+      write!(f, "@{}", offset)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Key {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "#EXT-X-KEY:METHOD={}", self.method)?;
+    if let Some(uri) = &self.uri {
+      write!(f, ",URI=\"{}\"", uri)?;
+    }
+    if let Some(iv) = &self.iv {
+      write!(f, ",IV={}", iv)?;
+    }
+    if let Some(key_format) = &self.key_format {
+      write!(f, ",KEYFORMAT=\"{}\"", key_format)?;
+    }
+    if let Some(key_format_versions) = &self.key_format_versions {
+      write!(f, ",KEYFORMATVERSIONS=\"{}\"", key_format_versions)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Map {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "#EXT-X-MAP:URI=\"{}\"", self.uri)?;
+    if let Some(byte_range) = &self.byte_range {
+      write!(f, ",BYTERANGE=\"{}\"", byte_range)?;
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for MultivariantPlaylist {
+  type Err = String;
+
+  fn from_str(content: &str) -> Result<Self, Self::Err> {
+    let mut playlist = MultivariantPlaylist::default();
+    // the URI of a variant stream follows its EXT-X-STREAM-INF tag
+    let mut variant_stream: Option<VariantStream> = None;
+
+    for line in get_lines(content)? {
+      if let Some(version) = strip_prefix(line, "#EXT-X-VERSION:") {
+        playlist.version = Some(parse_number(version)?);
+      } else if line == "#EXT-X-INDEPENDENT-SEGMENTS" {
+        playlist.independent_segments = true;
+      } else if let Some(attributes) = strip_prefix(line, "#EXT-X-MEDIA:") {
+        playlist.renditions.push(attributes.parse()?);
+      } else if let Some(attributes) = strip_prefix(line, "#EXT-X-STREAM-INF:") {
+        variant_stream = Some(attributes.parse()?);
+      } else if line.starts_with("#EXTINF:") {
+        return Err("segments are not allowed in a multivariant playlist".to_string());
+      } else if line.starts_with("#EXT") {
+        // unsupported tags are written back around the renditions and variant streams
+        if playlist.renditions.is_empty() && playlist.variant_streams.is_empty() {
+          playlist.header_tags.push(line.to_string());
+        } else {
+          playlist.trailing_tags.push(line.to_string());
+        }
+      } else if line.starts_with('#') {
+        // comments are ignored
+      } else {
+        let mut variant_stream = variant_stream
+          .take()
+          .ok_or_else(|| format!("unexpected URI: {}", line))?;
+        variant_stream.uri = line.to_string();
+        playlist.variant_streams.push(variant_stream);
+      }
+    }
+
+    if variant_stream.is_some() {
+      return Err("missing URI of the variant stream".to_string());
+    }
+    Ok(playlist)
+  }
+}
+
+impl FromStr for Rendition {
+  type Err = String;
+
+  fn from_str(attributes: &str) -> Result<Self, Self::Err> {
+    let mut rendition = Rendition::default();
+    let mut has_type = false;
+    for (name, value, raw_value) in parse_attribute_list(attributes)? {
+      match name.as_str() {
+        "TYPE" => {
+          rendition.media_type = value.parse()?;
+          has_type = true;
+        }
+        "GROUP-ID" => rendition.group_id = value,
+        "NAME" => rendition.name = value,
+        "LANGUAGE" => rendition.language = Some(value),
+        "DEFAULT" => rendition.default = value == "YES",
+        "AUTOSELECT" => rendition.autoselect = value == "YES",
+        "FORCED" => rendition.forced = value == "YES",
+        "INSTREAM-ID" => rendition.instream_id = Some(value),
+        "CHARACTERISTICS" => rendition.characteristics = Some(value),
+        "CHANNELS" => rendition.channels = Some(value),
+        "URI" => rendition.uri = Some(value),
+        _ => rendition
+          .other_attributes
+          .push(format!("{}={}", name, raw_value)),
+      }
+    }
+
+    if !has_type {
+      return Err("missing TYPE of the rendition".to_string());
+    }
+    Ok(rendition)
+  }
+}
+
+impl FromStr for MediaType {
+  type Err = String;
+
+  fn from_str(media_type: &str) -> Result<Self, Self::Err> {
+    match media_type {
+      "AUDIO" => Ok(MediaType::Audio),
+      "VIDEO" => Ok(MediaType::Video),
+      "SUBTITLES" => Ok(MediaType::Subtitles),
+      "CLOSED-CAPTIONS" => Ok(MediaType::ClosedCaptions),
+      _ => Err(format!("unsupported media type: {}", media_type)),
+    }
+  }
+}
+
+impl FromStr for VariantStream {
+  type Err = String;
+
+  fn from_str(attributes: &str) -> Result<Self, Self::Err> {
+    let mut variant_stream = VariantStream::default();
+    let mut has_bandwidth = false;
+    for (name, value, raw_value) in parse_attribute_list(attributes)? {
+      match name.as_str() {
+        "BANDWIDTH" => {
+          variant_stream.bandwidth = parse_number(&value)?;
+          has_bandwidth = true;
+        }
+        "AVERAGE-BANDWIDTH" => variant_stream.average_bandwidth = Some(parse_number(&value)?),
+        "CODECS" => variant_stream.codecs = Some(value),
+        "RESOLUTION" => {
+          let (width, height) =
+            split_once(&value, "x").ok_or_else(|| format!("invalid resolution: {}", value))?;
+          variant_stream.resolution = Some((parse_number(width)?, parse_number(height)?));
+        }
+        "FRAME-RATE" => variant_stream.frame_rate = Some(parse_number(&value)?),
+        "VIDEO" => variant_stream.video = Some(value),
+        "AUDIO" => variant_stream.audio = Some(value),
+        "SUBTITLES" => variant_stream.subtitles = Some(value),
+        "CLOSED-CAPTIONS" => variant_stream.closed_captions = Some(value),
+        _ => variant_stream
+          .other_attributes
+          .push(format!("{}={}", name, raw_value)),
+      }
+    }
+
+    if !has_bandwidth {
+      return Err("missing BANDWIDTH of the variant stream".to_string());
+    }
+    Ok(variant_stream)
+  }
+}
+
+impl FromStr for MediaPlaylist {
+  type Err = String;
+
+  fn from_str(content: &str) -> Result<Self, Self::Err> {
+    let mut playlist = MediaPlaylist::default();
+    // tags describing the next segment, until its URI
+    let mut segment = MediaSegment::default();
+    let mut has_duration = false;
+
+    for line in get_lines(content)? {
+      if let Some(version) = strip_prefix(line, "#EXT-X-VERSION:") {
+        playlist.version = Some(parse_number(version)?);
+      } else if let Some(target_duration) = strip_prefix(line, "#EXT-X-TARGETDURATION:") {
+        playlist.target_duration = parse_number(target_duration)?;
+      } else if let Some(media_sequence) = strip_prefix(line, "#EXT-X-MEDIA-SEQUENCE:") {
+        playlist.media_sequence = Some(parse_number(media_sequence)?);
+      } else if let Some(playlist_type) = strip_prefix(line, "#EXT-X-PLAYLIST-TYPE:") {
+        playlist.playlist_type = Some(playlist_type.to_string());
+      } else if line == "#EXT-X-INDEPENDENT-SEGMENTS" {
+        playlist.independent_segments = true;
+      } else if line == "#EXT-X-ENDLIST" {
+        playlist.end_list = true;
+      } else if line == "#EXT-X-DISCONTINUITY" {
+        segment.discontinuity = true;
+      } else if let Some(attributes) = strip_prefix(line, "#EXT-X-KEY:") {
+        let key: Key = attributes.parse()?;
+        segment.key = Some(key).filter(|key| key.method != "NONE");
+      } else if let Some(attributes) = strip_prefix(line, "#EXT-X-MAP:") {
+        segment.map = Some(attributes.parse()?);
+      } else if let Some(byte_range) = strip_prefix(line, "#EXT-X-BYTERANGE:") {
+        segment.byte_range = Some(byte_range.parse()?);
+      } else if let Some(information) = strip_prefix(line, "#EXTINF:") {
+        let (duration, title) = split_once(information, ",").unwrap_or((information, ""));
+        segment.duration = parse_number(duration)?;
+        segment.title = Some(title.to_string()).filter(|title| !title.is_empty());
+        has_duration = true;
+      } else if line.starts_with("#EXT-X-STREAM-INF:") {
+        return Err("variant streams are not allowed in a media playlist".to_string());
+      } else if line.starts_with('#') {
+        // comments and unsupported tags are ignored
+      } else {
+        if !has_duration {
+          return Err(format!("missing EXTINF tag of the segment {}", line));
+        }
+        segment.uri = line.to_string();
+        // the key and the initialization section apply to the next segments
+        let next_segment = MediaSegment {
+          key: segment.key.clone(),
+          map: segment.map.clone(),
+          ..Default::default()
+        };
+        playlist.segments.push(segment);
+        segment = next_segment;
+        has_duration = false;
+      }
+    }
+    Ok(playlist)
+  }
+}
+
+impl FromStr for Key {
+  type Err = String;
+
+  fn from_str(attributes: &str) -> Result<Self, Self::Err> {
+    let mut key = Key::default();
+    for (name, value) in parse_attributes(attributes)? {
+      match name.as_str() {
+        "METHOD" => key.method = value,
+        "URI" => key.uri = Some(value),
+        "IV" => key.iv = Some(value),
+        "KEYFORMAT" => key.key_format = Some(value),
+        "KEYFORMATVERSIONS" => key.key_format_versions = Some(value),
+        _ => {}
+      }
+    }
+
+    if key.method.is_empty() {
+      return Err("missing METHOD of the key".to_string());
+    }
+    if key.method != "NONE" && key.uri.is_none() {
+      return Err("missing URI of the key".to_string());
+    }
+    Ok(key)
+  }
+}
+
+impl FromStr for Map {
+  type Err = String;
+
+  fn from_str(attributes: &str) -> Result<Self, Self::Err> {
+    let mut map = Map::default();
+    for (name, value) in parse_attributes(attributes)? {
+      match name.as_str() {
+        "URI" => map.uri = value,
+        "BYTERANGE" => map.byte_range = Some(value.parse()?),
+        _ => {}
+      }
+    }
+
+    if map.uri.is_empty() {
+      return Err("missing URI of the media initialization section".to_string());
+    }
+    Ok(map)
+  }
+}
+
+impl FromStr for ByteRange {
+  type Err = String;
+
+  fn from_str(byte_range: &str) -> Result<Self, Self::Err> {
+    let (length, offset) = match split_once(byte_range, "@") {
+      Some((length, offset)) => (length, Some(parse_number(offset)?)),
+      None => (byte_range, None),
+    };
+
+    Ok(ByteRange {
+      length: parse_number(length)?,
+      offset,
+    })
+  }
+}
+
+/// Lines of the playlist, starting with the EXTM3U header
+fn get_lines(content: &str) -> Result<Vec<&str>, String> {
+  let lines: Vec<&str> = content
+    .lines()
+    .map(|line| line.trim())
+    .filter(|line| !line.is_empty())
+    .collect();
+
+  if lines.first() != Some(&"#EXTM3U") {
+    return Err("missing EXTM3U header".to_string());
+  }
+  Ok(lines[1..].to_vec())
+}
+
+/// Attribute list of a tag, the quoted strings possibly containing commas
+fn parse_attributes(attributes: &str) -> Result<Vec<(String, String)>, String> {
+  Ok(
+    parse_attribute_list(attributes)?
+      .into_iter()
+      .map(|(name, value, _)| (name, value))
+      .collect(),
+  )
+}
+
+/// Names, values and values as written of the attributes
+fn parse_attribute_list(attributes: &str) -> Result<Vec<(String, String, String)>, String> {
+  let mut result = vec![];
+  let mut remaining = attributes.trim();
+
+  while !remaining.is_empty() {
+    let (name, rest) = split_once(remaining, "=")
+      .ok_or_else(|| format!("invalid attribute list: {}", attributes))?;
+
+    let (value, raw_value, rest) = match strip_prefix(rest, "\"") {
+      Some(quoted) => {
+        let (value, rest) = split_once(quoted, "\"")
+          .ok_or_else(|| format!("unterminated quoted string: {}", attributes))?;
+        let raw_value = format!("\"{}\"", value);
+        (value, raw_value, strip_prefix(rest, ",").unwrap_or(rest))
+      }
+      None => {
+        let (value, rest) = split_once(rest, ",").unwrap_or((rest, ""));
+        (value, value.to_string(), rest)
+      }
+    };
+
+    result.push((name.trim().to_string(), value.to_string(), raw_value));
+    remaining = rest.trim_start();
+  }
+  Ok(result)
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+  value
+    .trim()
+    .parse()
+    .map_err(|_| format!("invalid number: {}", value))
+}
+
+fn yes_or_no(value: bool) -> &'static str {
+  if value {
+    "YES"
+  } else {
+    "NO"
+  }
+}
+
+#[test]
+fn serialize_media_playlist() {
+  let map = Map {
+    uri: "audio.mp4".to_string(),
+    byte_range: Some(ByteRange::new(0, 602)),
+  };
+  let segments = vec![
+    MediaSegment {
+      duration: 2.0,
+      byte_range: Some(ByteRange::new(671, 1226)),
+      map: Some(map.clone()),
+      uri: "audio.mp4".to_string(),
+      ..Default::default()
+    },
+    MediaSegment {
+      duration: 1.6,
+      byte_range: Some(ByteRange::new(1227, 1882)),
+      map: Some(map),
+      uri: "audio.mp4".to_string(),
+      ..Default::default()
+    },
+  ];
+  let playlist = MediaPlaylist::new_vod(7, segments);
+
+  assert_eq!(
+    playlist.to_string(),
+    r#"#EXTM3U
+#EXT-X-VERSION:7
+#EXT-X-TARGETDURATION:2
+#EXT-X-PLAYLIST-TYPE:VOD
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXT-X-MAP:URI="audio.mp4",BYTERANGE="603@0"
+#EXTINF:2.000,
+#EXT-X-BYTERANGE:556@671
+audio.mp4
+#EXTINF:1.600,
+#EXT-X-BYTERANGE:656@1227
+audio.mp4
+#EXT-X-ENDLIST
+"#
+  );
+}
+
+#[test]
+fn parse_multivariant_playlist() {
+  let content = std::fs::read_to_string("tests/sample_6.m3u8").unwrap();
+  let playlist: MultivariantPlaylist = content.parse().unwrap();
+
+  assert_eq!(playlist.version, Some(7));
+  assert_eq!(playlist.renditions.len(), 4);
+  assert_eq!(playlist.renditions[1].name, "Français");
+  assert_eq!(playlist.renditions[3].media_type, MediaType::ClosedCaptions);
+  assert_eq!(playlist.renditions[3].instream_id, Some("CC1".to_string()));
+  assert_eq!(
+    playlist.variant_streams[0].codecs,
+    Some("avc1.64001F,mp4a.40.2".to_string())
+  );
+  assert_eq!(playlist.variant_streams[1].resolution, Some((640, 360)));
+  assert_eq!(
+    playlist.get_rendition_uris(&MediaType::Audio),
+    vec!["sample_6_a1.m3u8", "sample_6_a2.m3u8"]
+  );
+  assert_eq!(playlist.to_string(), content);
+
+  // unsupported tags and attributes are kept
+  let content = std::fs::read_to_string("tests/sample_7.m3u8").unwrap();
+  let playlist: MultivariantPlaylist = content.parse().unwrap();
+  assert_eq!(playlist.header_tags.len(), 2);
+  assert_eq!(
+    playlist.variant_streams[0].other_attributes,
+    vec!["VIDEO-RANGE=SDR", "HDCP-LEVEL=TYPE-0"]
+  );
+  assert_eq!(playlist.trailing_tags.len(), 1);
+  assert_eq!(playlist.to_string(), content);
+
+  assert!("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1000\n"
+    .parse::<MultivariantPlaylist>()
+    .is_err());
+  assert!("#EXT-X-STREAM-INF:BANDWIDTH=1000\nvideo.m3u8\n"
+    .parse::<MultivariantPlaylist>()
+    .is_err());
+}
+
+#[test]
+fn parse_media_playlist() {
+  let content = std::fs::read_to_string("tests/sample_6_v1.m3u8").unwrap();
+  let playlist: MediaPlaylist = content.parse().unwrap();
+
+  assert_eq!(playlist.media_sequence, Some(1));
+  assert_eq!(playlist.segments.len(), 3);
+  assert_eq!(
+    playlist.segments[1].byte_range,
+    Some(ByteRange {
+      length: 198014,
+      offset: None
+    })
+  );
+  assert_eq!(
+    playlist.segments[1].key.as_ref().unwrap().uri,
+    Some("skd://key-1".to_string())
+  );
+  assert!(playlist.segments[2].discontinuity);
+  assert_eq!(playlist.segments[2].key, None);
+  assert_eq!(playlist.segments[2].title, Some("ad".to_string()));
+  assert_eq!(playlist.to_string(), content);
+
+  assert!("#EXTM3U\nsegment.ts\n".parse::<MediaPlaylist>().is_err());
+  assert!("#EXTM3U\n#EXT-X-KEY:METHOD=AES-128\n"
+    .parse::<MediaPlaylist>()
+    .is_err());
+}
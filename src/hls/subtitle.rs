@@ -0,0 +1,623 @@
+use crate::hls::message::load_referenced_media_playlist;
+use crate::hls::playlist::{
+  MediaPlaylist, MediaSegment, MediaType, MultivariantPlaylist, Rendition,
+};
+use crate::subtitle::{self, webvtt, Format, Retiming};
+use crate::utils::{load_file, processing_error, rebase_url, save_file};
+use crate::HlsManifestParameters;
+use mcai_worker_sdk::{job::JobResult, MessageError};
+use std::fs;
+use std::path::Path;
+
+pub fn add_subtitle(
+  job_result: JobResult,
+  parameters: HlsManifestParameters,
+) -> Result<Option<String>, MessageError> {
+  let subtitle_path = parameters
+    .subtitle_path
+    .as_ref()
+    .ok_or_else(|| processing_error(&job_result, "missing subtitle_path parameter"))?;
+  // the language declared by a sidecar file is used when none is given
+  let (language, warning) = subtitle::resolve_language(
+    parameters.subtitle_language.as_deref(),
+    subtitle::get_declared_language(subtitle_path).as_deref(),
+  )
+  .ok_or_else(|| processing_error(&job_result, "missing subtitle_language parameter"))?;
+  let language = &language;
+  let forced = parameters.subtitle_forced.unwrap_or(false);
+  let retiming = get_retiming(&job_result, &parameters)?;
+
+  let destination_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+  let mut playlist: MultivariantPlaylist = load_file(&job_result, &parameters.source_path)?;
+  let mut warnings: Vec<String> = warning.into_iter().collect();
+
+  // a variant stream references a single group of subtitles
+  let group_id = parameters
+    .subtitle_group_id
+    .clone()
+    .or_else(|| {
+      playlist
+        .variant_streams
+        .iter()
+        .find_map(|variant_stream| variant_stream.subtitles.clone())
+    })
+    .unwrap_or_else(|| "subtitles".to_string());
+  if let Some(other_group_id) = playlist.variant_streams.iter().find_map(|variant_stream| {
+    variant_stream
+      .subtitles
+      .as_ref()
+      .filter(|subtitles| **subtitles != group_id)
+  }) {
+    return Err(processing_error(
+      &job_result,
+      &format!(
+        "the variant streams already reference the subtitle group {}",
+        other_group_id
+      ),
+    ));
+  }
+  let name = parameters
+    .subtitle_name
+    .clone()
+    .unwrap_or_else(|| language.clone());
+
+  let is_in_group = |rendition: &Rendition| {
+    rendition.media_type == MediaType::Subtitles && rendition.group_id == group_id
+  };
+  if parameters.replace.unwrap_or(false) {
+    playlist.renditions.retain(|rendition| {
+      !is_in_group(rendition)
+        || rendition.language.as_ref() != Some(language)
+        || rendition.forced != forced
+    });
+  }
+  if playlist
+    .renditions
+    .iter()
+    .any(|rendition| is_in_group(rendition) && rendition.name == name)
+  {
+    return Err(processing_error(
+      &job_result,
+      &format!(
+        "a subtitle named {} already exists in the group {}",
+        name, group_id
+      ),
+    ));
+  }
+
+  // job paths are relative to the working directory
+  let uri = if subtitle_path.ends_with(".m3u8") {
+    if retiming.is_some() {
+      return Err(processing_error(
+        &job_result,
+        "the times of a subtitle media playlist cannot be shifted",
+      ));
+    }
+    load_file::<MediaPlaylist>(&job_result, subtitle_path)?;
+    rebase_url(subtitle_path, "", destination_path)
+  } else {
+    // a sidecar file is described by a media playlist of the presentation duration
+    let duration = get_presentation_duration(&job_result, &parameters.source_path)?;
+
+    let playlist_name = Path::new(destination_path)
+      .file_stem()
+      .and_then(|file_stem| file_stem.to_str())
+      .unwrap_or("playlist");
+    let uri = if forced {
+      format!("{}_{}_forced.m3u8", playlist_name, language)
+    } else {
+      format!("{}_{}.m3u8", playlist_name, language)
+    };
+    let media_playlist_path = Path::new(destination_path)
+      .parent()
+      .unwrap_or_else(|| Path::new(""))
+      .join(&uri)
+      .to_string_lossy()
+      .to_string();
+
+    warnings.extend(write_subtitle_playlist(
+      &job_result,
+      subtitle_path,
+      duration,
+      parameters.segment_duration,
+      &retiming,
+      &media_playlist_path,
+    )?);
+    uri
+  };
+
+  let default = parameters.subtitle_default.unwrap_or(false);
+  if default {
+    for rendition in playlist
+      .renditions
+      .iter_mut()
+      .filter(|rendition| is_in_group(rendition))
+    {
+      rendition.default = false;
+    }
+  }
+
+  playlist.renditions.push(Rendition {
+    media_type: MediaType::Subtitles,
+    group_id: group_id.clone(),
+    name,
+    language: Some(language.clone()),
+    default,
+    autoselect: parameters.subtitle_autoselect.unwrap_or(true),
+    forced,
+    uri: Some(uri),
+    ..Default::default()
+  });
+  for variant_stream in &mut playlist.variant_streams {
+    if variant_stream.subtitles.is_none() {
+      variant_stream.subtitles = Some(group_id.clone());
+    }
+  }
+
+  save_file(&job_result, &playlist, destination_path)?;
+  Ok(Some(warnings.join("; ")).filter(|warning| !warning.is_empty()))
+}
+
+pub fn generate_subtitle_playlist(
+  job_result: JobResult,
+  parameters: HlsManifestParameters,
+) -> Result<Option<String>, MessageError> {
+  let subtitle_path = parameters
+    .subtitle_path
+    .as_ref()
+    .ok_or_else(|| processing_error(&job_result, "missing subtitle_path parameter"))?;
+  let media_playlist_path = parameters.destination_path.clone().unwrap_or_else(|| {
+    Path::new(subtitle_path)
+      .with_extension("m3u8")
+      .to_string_lossy()
+      .to_string()
+  });
+
+  let retiming = get_retiming(&job_result, &parameters)?;
+
+  let duration = get_presentation_duration(&job_result, &parameters.source_path)?;
+  let warnings = write_subtitle_playlist(
+    &job_result,
+    subtitle_path,
+    duration,
+    parameters.segment_duration,
+    &retiming,
+    &media_playlist_path,
+  )?;
+  Ok(Some(warnings.join("; ")).filter(|warning| !warning.is_empty()))
+}
+
+pub fn convert_subtitle(
+  job_result: JobResult,
+  parameters: HlsManifestParameters,
+) -> Result<Option<String>, MessageError> {
+  let subtitle_path = parameters
+    .subtitle_path
+    .as_ref()
+    .ok_or_else(|| processing_error(&job_result, "missing subtitle_path parameter"))?;
+  let destination_path = parameters
+    .destination_path
+    .as_ref()
+    .ok_or_else(|| processing_error(&job_result, "missing destination_path parameter"))?;
+
+  let warnings = subtitle::convert(subtitle_path, destination_path, &None)
+    .map_err(|message| processing_error(&job_result, &message))?;
+  Ok(Some(warnings.join("; ")).filter(|warning| !warning.is_empty()))
+}
+
+/// Offset and frame rate conversion of the subtitle times, if any
+fn get_retiming(
+  job_result: &JobResult,
+  parameters: &HlsManifestParameters,
+) -> Result<Option<Retiming>, MessageError> {
+  Retiming::new(
+    parameters.subtitle_time_offset,
+    parameters.subtitle_frame_rate,
+    parameters.subtitle_target_frame_rate,
+  )
+  .map_err(|message| processing_error(job_result, &message))
+}
+
+/// Duration of a multivariant playlist, given by its first variant stream, or of a media playlist
+fn get_presentation_duration(job_result: &JobResult, path: &str) -> Result<f64, MessageError> {
+  let contents =
+    fs::read_to_string(path).map_err(|e| processing_error(job_result, &e.to_string()))?;
+  if !contents.contains("#EXT-X-STREAM-INF:") {
+    let playlist: MediaPlaylist = load_file(job_result, path)?;
+    return Ok(playlist.get_duration());
+  }
+
+  let playlist: MultivariantPlaylist = load_file(job_result, path)?;
+  let variant_stream = playlist
+    .variant_streams
+    .first()
+    .ok_or_else(|| processing_error(job_result, "missing variant stream in the playlist"))?;
+  Ok(load_referenced_media_playlist(job_result, path, &variant_stream.uri)?.get_duration())
+}
+
+/// Media playlist of a sidecar subtitle file, converted to WebVTT segments when needed,
+/// returning the conversion warnings
+fn write_subtitle_playlist(
+  job_result: &JobResult,
+  subtitle_path: &str,
+  duration: f64,
+  segment_duration: Option<f64>,
+  retiming: &Option<Retiming>,
+  media_playlist_path: &str,
+) -> Result<Vec<String>, MessageError> {
+  if segment_duration.map_or(false, |segment_duration| segment_duration <= 0.0) {
+    return Err(processing_error(
+      job_result,
+      "segment_duration must be positive",
+    ));
+  }
+
+  let contents =
+    fs::read_to_string(subtitle_path).map_err(|e| processing_error(job_result, &e.to_string()))?;
+  let format = Format::detect(&contents)
+    .map_err(|message| processing_error(job_result, &format!("{}: {}", subtitle_path, message)))?;
+
+  // a single WebVTT file is referenced as is, unless its times are shifted
+  if format == Format::WebVtt && segment_duration.is_none() && retiming.is_none() {
+    let media_playlist = MediaPlaylist::new_vod(
+      3,
+      vec![MediaSegment {
+        duration,
+        uri: rebase_url(subtitle_path, "", media_playlist_path),
+        ..Default::default()
+      }],
+    );
+    save_file(job_result, &media_playlist, media_playlist_path)?;
+    return Ok(vec![]);
+  }
+
+  let (mut subtitles, warnings) = subtitle::parse_with_warnings(&contents)
+    .map_err(|message| processing_error(job_result, &format!("{}: {}", subtitle_path, message)))?;
+  if let Some(retiming) = retiming {
+    subtitles.retime(retiming);
+  }
+
+  let mut bounds = vec![];
+  match segment_duration {
+    Some(segment_duration) => {
+      let mut start = 0.0;
+      // the last segment is shortened, unless it would be negligible
+      while start < duration - 0.001 {
+        bounds.push((start, (start + segment_duration).min(duration)));
+        start += segment_duration;
+      }
+    }
+    None => bounds.push((0.0, duration)),
+  }
+
+  let folder = Path::new(media_playlist_path)
+    .parent()
+    .unwrap_or_else(|| Path::new(""));
+  let name = Path::new(media_playlist_path)
+    .file_stem()
+    .and_then(|file_stem| file_stem.to_str())
+    .unwrap_or("subtitle");
+
+  let mut segments = vec![];
+  for (index, (start, end)) in bounds.iter().enumerate() {
+    let uri = if segment_duration.is_some() {
+      format!("{}_{}.vtt", name, index + 1)
+    } else {
+      format!("{}.vtt", name)
+    };
+    fs::write(
+      folder.join(&uri),
+      webvtt::serialize(&subtitles.clip(*start, *end)),
+    )
+    .map_err(|e| processing_error(job_result, &e.to_string()))?;
+
+    segments.push(MediaSegment {
+      duration: end - start,
+      uri,
+      ..Default::default()
+    });
+  }
+
+  let media_playlist = MediaPlaylist::new_vod(3, segments);
+  save_file(job_result, &media_playlist, media_playlist_path)?;
+  Ok(warnings)
+}
+
+#[test]
+fn add_webvtt_subtitle() {
+  use crate::HlsAction;
+
+  let parameters = HlsManifestParameters {
+    action: Some(HlsAction::AddSubtitle),
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.vtt".to_string()),
+    subtitle_language: Some("fr".to_string()),
+    subtitle_name: Some("Français".to_string()),
+    subtitle_default: Some(true),
+    destination_path: Some("tests/sample_6_subtitle.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_subtitle(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_6_subtitle.m3u8").unwrap();
+  assert!(content.contains("#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subtitles\",NAME=\"English\",LANGUAGE=\"en\",DEFAULT=NO,AUTOSELECT=YES,FORCED=NO,URI=\"sample_6_s1.m3u8\"\n"));
+  assert!(content.contains("#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subtitles\",NAME=\"Français\",LANGUAGE=\"fr\",DEFAULT=YES,AUTOSELECT=YES,FORCED=NO,URI=\"sample_6_subtitle_fr.m3u8\"\n"));
+  assert_eq!(content.matches("SUBTITLES=\"subtitles\"").count(), 2);
+
+  let content = fs::read_to_string("tests/sample_6_subtitle_fr.m3u8").unwrap();
+  assert_eq!(
+    content,
+    r#"#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-TARGETDURATION:6
+#EXT-X-PLAYLIST-TYPE:VOD
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXTINF:5.500,
+sample_subtitle.vtt
+#EXT-X-ENDLIST
+"#
+  );
+}
+
+#[test]
+fn add_srt_subtitle() {
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.srt".to_string()),
+    subtitle_language: Some("de".to_string()),
+    destination_path: Some("tests/sample_6_srt.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_subtitle(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_6_srt.m3u8").unwrap();
+  assert!(content.contains("#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subtitles\",NAME=\"de\",LANGUAGE=\"de\",DEFAULT=NO,AUTOSELECT=YES,FORCED=NO,URI=\"sample_6_srt_de.m3u8\"\n"));
+  let content = fs::read_to_string("tests/sample_6_srt_de.m3u8").unwrap();
+  assert!(content.contains("#EXTINF:5.500,\nsample_6_srt_de.vtt\n"));
+  let content = fs::read_to_string("tests/sample_6_srt_de.vtt").unwrap();
+  assert!(content
+    .starts_with("WEBVTT\n\n1\n00:00:00.760 --> 00:00:03.450\nIt seems a paradox, does it not,\n"));
+}
+
+#[test]
+fn add_subtitle_keeping_unsupported_tags() {
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_7.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.vtt".to_string()),
+    subtitle_language: Some("fr".to_string()),
+    destination_path: Some("tests/sample_7_subtitle.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_subtitle(job_result, parameters).unwrap();
+
+  // the unsupported tags and attributes are written back unchanged
+  let content = fs::read_to_string("tests/sample_7_subtitle.m3u8").unwrap();
+  assert!(content.contains("#EXT-X-INDEPENDENT-SEGMENTS\n#EXT-X-SESSION-KEY:METHOD=SAMPLE-AES,URI=\"skd://key-1\",KEYFORMAT=\"com.apple.streamingkeydelivery\",KEYFORMATVERSIONS=\"1\"\n#EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Sample\",LANGUAGE=\"en\"\n"));
+  assert!(content.contains(",CHANNELS=\"2\",ASSOC-LANGUAGE=\"en-GB\",URI=\"sample_6_a1.m3u8\"\n"));
+  assert!(content
+    .contains(",AUDIO=\"audio-mp4a\",SUBTITLES=\"subtitles\",VIDEO-RANGE=SDR,HDCP-LEVEL=TYPE-0\n"));
+  assert!(content.ends_with("sample_6_v2.m3u8\n#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=185000,CODECS=\"avc1.64001F\",RESOLUTION=1280x720,VIDEO-RANGE=SDR,URI=\"sample_6_v1_iframes.m3u8\"\n"));
+}
+
+#[test]
+fn add_subtitle_with_declared_language() {
+  fs::write(
+    "tests/sample_subtitle_es.vtt",
+    "WEBVTT\nLanguage: es\n\n00:00:01.000 --> 00:00:02.000\nHola\n",
+  )
+  .unwrap();
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle_es.vtt".to_string()),
+    destination_path: Some("tests/sample_6_declared_language.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert_eq!(
+    add_subtitle(job_result.clone(), parameters.clone()),
+    Ok(None)
+  );
+
+  let content = fs::read_to_string("tests/sample_6_declared_language.m3u8").unwrap();
+  assert!(content.contains("NAME=\"es\",LANGUAGE=\"es\""));
+
+  let parameters = HlsManifestParameters {
+    subtitle_language: Some("it".to_string()),
+    ..parameters
+  };
+  let warning = add_subtitle(job_result, parameters).unwrap();
+  assert!(warning.unwrap().contains("conflicts"));
+}
+
+#[test]
+fn add_segmented_webvtt_subtitle() {
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.m3u8".to_string()),
+    subtitle_language: Some("en".to_string()),
+    subtitle_name: Some("English (forced)".to_string()),
+    subtitle_forced: Some(true),
+    destination_path: Some("tests/sample_6_forced.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  add_subtitle(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_6_forced.m3u8").unwrap();
+  assert!(content.contains("#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subtitles\",NAME=\"English (forced)\",LANGUAGE=\"en\",DEFAULT=NO,AUTOSELECT=YES,FORCED=YES,URI=\"sample_subtitle.m3u8\"\n"));
+  assert!(!Path::new("tests/sample_6_forced_en_forced.m3u8").exists());
+}
+
+#[test]
+fn add_subtitle_errors() {
+  // a rendition with the same name exists
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.m3u8".to_string()),
+    subtitle_language: Some("en".to_string()),
+    subtitle_name: Some("English".to_string()),
+    destination_path: Some("tests/sample_6_subtitle_error.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(add_subtitle(job_result, parameters.clone()).is_err());
+
+  // nothing is written for a rejected sidecar file
+  let _ = fs::remove_file("tests/sample_6_subtitle_error_en.m3u8");
+  let sidecar_parameters = HlsManifestParameters {
+    subtitle_path: Some("tests/sample_subtitle.vtt".to_string()),
+    ..parameters.clone()
+  };
+  assert!(add_subtitle(JobResult::new(666), sidecar_parameters).is_err());
+  assert!(!Path::new("tests/sample_6_subtitle_error_en.m3u8").exists());
+
+  let parameters = HlsManifestParameters {
+    replace: Some(true),
+    destination_path: Some("tests/sample_6_replaced.m3u8".to_string()),
+    ..parameters
+  };
+  let job_result = JobResult::new(666);
+  add_subtitle(job_result, parameters).unwrap();
+  let content = fs::read_to_string("tests/sample_6_replaced.m3u8").unwrap();
+  assert!(content.contains("NAME=\"English\",LANGUAGE=\"en\",DEFAULT=NO,AUTOSELECT=YES,FORCED=NO,URI=\"sample_subtitle.m3u8\""));
+  assert!(!content.contains("sample_6_s1.m3u8"));
+
+  // the variant streams reference another group of subtitles
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.m3u8".to_string()),
+    subtitle_language: Some("fr".to_string()),
+    subtitle_group_id: Some("captions".to_string()),
+    destination_path: Some("tests/sample_6_subtitle_error.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(add_subtitle(job_result, parameters).is_err());
+
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_audio_dashinit.mp4".to_string()),
+    subtitle_language: Some("fr".to_string()),
+    destination_path: Some("tests/sample_6_subtitle_error.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(add_subtitle(job_result, parameters).is_err());
+}
+
+#[test]
+fn generate_ttml_subtitle_playlist() {
+  use crate::HlsAction;
+
+  let parameters = HlsManifestParameters {
+    action: Some(HlsAction::GenerateSubtitlePlaylist),
+    source_path: "tests/sample_6_v1.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.ttml".to_string()),
+    destination_path: Some("tests/sample_subtitle_ttml.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  generate_subtitle_playlist(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_subtitle_ttml.m3u8").unwrap();
+  assert!(content.contains("#EXTINF:5.500,\nsample_subtitle_ttml.vtt\n#EXT-X-ENDLIST\n"));
+  let content = fs::read_to_string("tests/sample_subtitle_ttml.vtt").unwrap();
+  assert_eq!(
+    content,
+    r#"WEBVTT
+
+subtitle1
+00:00:00.760 --> 00:00:03.450 align:center
+It seems a paradox, does it not,
+
+subtitle2
+00:00:05.000 --> 00:00:10.000 align:center
+that the image formed on
+the Retina should be inverted?
+"#
+  );
+}
+
+#[test]
+fn generate_segmented_subtitle_playlist() {
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.vtt".to_string()),
+    segment_duration: Some(2.0),
+    destination_path: Some("tests/sample_subtitle_segmented.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  generate_subtitle_playlist(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_subtitle_segmented.m3u8").unwrap();
+  assert_eq!(
+    content,
+    r#"#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-TARGETDURATION:2
+#EXT-X-PLAYLIST-TYPE:VOD
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXTINF:2.000,
+sample_subtitle_segmented_1.vtt
+#EXTINF:2.000,
+sample_subtitle_segmented_2.vtt
+#EXTINF:1.500,
+sample_subtitle_segmented_3.vtt
+#EXT-X-ENDLIST
+"#
+  );
+  // cues overlapping two segments are repeated
+  let content = fs::read_to_string("tests/sample_subtitle_segmented_2.vtt").unwrap();
+  assert_eq!(content.matches(" --> ").count(), 1);
+  let content = fs::read_to_string("tests/sample_subtitle_segmented_3.vtt").unwrap();
+  assert!(content.contains("2\n00:00:05.000 --> 00:00:10.000\n"));
+
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.vtt".to_string()),
+    segment_duration: Some(0.0),
+    destination_path: Some("tests/sample_subtitle_error.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(generate_subtitle_playlist(job_result, parameters).is_err());
+}
+
+#[test]
+fn generate_retimed_subtitle_playlist() {
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6_v1.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.vtt".to_string()),
+    subtitle_time_offset: Some(-1.0),
+    subtitle_frame_rate: Some(25.0),
+    subtitle_target_frame_rate: Some(50.0),
+    destination_path: Some("tests/sample_subtitle_retimed.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  generate_subtitle_playlist(job_result, parameters).unwrap();
+
+  let content = fs::read_to_string("tests/sample_subtitle_retimed.m3u8").unwrap();
+  assert!(content.contains("#EXTINF:5.500,\nsample_subtitle_retimed.vtt\n"));
+  let content = fs::read_to_string("tests/sample_subtitle_retimed.vtt").unwrap();
+  assert!(content.contains("00:00:00.000 --> 00:00:01.225\n"));
+  assert!(content.contains("00:00:02.000 --> 00:00:04.500\n"));
+
+  let parameters = HlsManifestParameters {
+    source_path: "tests/sample_6.m3u8".to_string(),
+    subtitle_path: Some("tests/sample_subtitle.m3u8".to_string()),
+    subtitle_language: Some("en".to_string()),
+    subtitle_time_offset: Some(-1.0),
+    destination_path: Some("tests/sample_6_retimed.m3u8".to_string()),
+    ..Default::default()
+  };
+  let job_result = JobResult::new(666);
+  assert!(add_subtitle(job_result, parameters).is_err());
+}
@@ -0,0 +1,258 @@
+use crate::language;
+use crate::{ContentType, LanguageConvention};
+use std::io::{Read, Write};
+use yaserde::{YaDeserialize, YaSerialize};
+
+/// Default timescale of the Smooth Streaming times, in units per second
+const DEFAULT_TIMESCALE: u64 = 10_000_000;
+
+#[derive(Debug, Default, YaDeserialize, YaSerialize)]
+#[yaserde(rename = "SmoothStreamingMedia")]
+pub struct ClientManifest {
+  #[yaserde(attribute, rename = "MajorVersion")]
+  major_version: u32,
+  #[yaserde(attribute, rename = "MinorVersion")]
+  minor_version: u32,
+  #[yaserde(attribute, rename = "TimeScale")]
+  timescale: Option<u64>,
+  #[yaserde(attribute, rename = "Duration")]
+  duration: u64,
+  #[yaserde(attribute, rename = "IsLive")]
+  is_live: Option<String>,
+  #[yaserde(rename = "StreamIndex")]
+  stream_index: Vec<StreamIndex>,
+  #[yaserde(rename = "Protection")]
+  protection: Option<Protection>,
+}
+
+/// Description of a stream of the client manifest
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Stream {
+  pub content_type: ContentType,
+  pub name: Option<String>,
+  pub language: Option<String>,
+  pub bit_rates: Vec<u64>,
+  pub chunk_count: u64,
+  /// Duration of all the chunks, in seconds
+  pub duration: f64,
+  /// Longest chunk duration, in seconds
+  pub max_chunk_duration: f64,
+}
+
+impl ClientManifest {
+  /// Duration of the presentation, in seconds
+  pub fn get_duration(&self) -> f64 {
+    self.duration as f64 / self.timescale.unwrap_or(DEFAULT_TIMESCALE) as f64
+  }
+
+  /// Audio, text and video streams, those of an unknown type being skipped
+  pub fn get_streams(&self, convention: &Option<LanguageConvention>) -> Vec<Stream> {
+    self
+      .stream_index
+      .iter()
+      .filter_map(|stream_index| {
+        let content_type = match stream_index.kind.as_str() {
+          "audio" => ContentType::Audio,
+          "video" => ContentType::Video,
+          "text" => ContentType::Text,
+          _ => return None,
+        };
+        let timescale = stream_index
+          .timescale
+          .or(self.timescale)
+          .unwrap_or(DEFAULT_TIMESCALE) as f64;
+        let chunk_durations = stream_index.get_chunk_durations();
+
+        Some(Stream {
+          content_type,
+          name: stream_index.name.clone(),
+          language: stream_index
+            .language
+            .as_ref()
+            .filter(|language| !language.is_empty())
+            .map(|language| language::normalize(language, convention)),
+          bit_rates: stream_index
+            .quality_level
+            .iter()
+            .map(|quality_level| quality_level.bit_rate)
+            .collect(),
+          chunk_count: chunk_durations.len() as u64,
+          duration: chunk_durations.iter().sum::<u64>() as f64 / timescale,
+          max_chunk_duration: chunk_durations.iter().max().copied().unwrap_or_default() as f64
+            / timescale,
+        })
+      })
+      .collect()
+  }
+}
+
+#[derive(Debug, Default, YaDeserialize, YaSerialize)]
+struct StreamIndex {
+  #[yaserde(attribute, rename = "Type")]
+  kind: String,
+  #[yaserde(attribute, rename = "Subtype")]
+  subtype: Option<String>,
+  #[yaserde(attribute, rename = "QualityLevels")]
+  quality_levels: Option<u32>,
+  #[yaserde(attribute, rename = "TimeScale")]
+  timescale: Option<u64>,
+  #[yaserde(attribute, rename = "Name")]
+  name: Option<String>,
+  #[yaserde(attribute, rename = "Language")]
+  language: Option<String>,
+  #[yaserde(attribute, rename = "Chunks")]
+  chunks: Option<u64>,
+  #[yaserde(attribute, rename = "Url")]
+  url: String,
+  #[yaserde(attribute, rename = "MaxWidth")]
+  max_width: Option<u32>,
+  #[yaserde(attribute, rename = "MaxHeight")]
+  max_height: Option<u32>,
+  #[yaserde(attribute, rename = "DisplayWidth")]
+  display_width: Option<u32>,
+  #[yaserde(attribute, rename = "DisplayHeight")]
+  display_height: Option<u32>,
+  #[yaserde(rename = "QualityLevel")]
+  quality_level: Vec<QualityLevel>,
+  #[yaserde(rename = "c")]
+  chunk: Vec<Chunk>,
+}
+
+impl StreamIndex {
+  /// Duration of every chunk, in the stream timescale
+  fn get_chunk_durations(&self) -> Vec<u64> {
+    let mut durations = vec![];
+    for (index, chunk) in self.chunk.iter().enumerate() {
+      // without duration, a chunk lasts until the start of the next one
+      let duration = chunk.duration.or_else(|| {
+        let next_start = self.chunk.get(index + 1)?.start?;
+        let start = chunk.start?;
+        next_start.checked_sub(start)
+      });
+      let duration = duration.unwrap_or_default();
+      for _ in 0..chunk.repeat.unwrap_or(1).max(1) {
+        durations.push(duration);
+      }
+    }
+    durations
+  }
+}
+
+#[derive(Debug, Default, YaDeserialize, YaSerialize)]
+struct QualityLevel {
+  #[yaserde(attribute, rename = "Index")]
+  index: Option<u32>,
+  #[yaserde(attribute, rename = "Bitrate")]
+  bit_rate: u64,
+  #[yaserde(attribute, rename = "CodecPrivateData")]
+  codec_private_data: Option<String>,
+  #[yaserde(attribute, rename = "MaxWidth")]
+  max_width: Option<u32>,
+  #[yaserde(attribute, rename = "MaxHeight")]
+  max_height: Option<u32>,
+  #[yaserde(attribute, rename = "SamplingRate")]
+  sampling_rate: Option<u32>,
+  #[yaserde(attribute, rename = "Channels")]
+  channels: Option<u32>,
+  #[yaserde(attribute, rename = "BitsPerSample")]
+  bits_per_sample: Option<u32>,
+  #[yaserde(attribute, rename = "PacketSize")]
+  packet_size: Option<u32>,
+  #[yaserde(attribute, rename = "AudioTag")]
+  audio_tag: Option<u32>,
+  #[yaserde(attribute, rename = "FourCC")]
+  fourcc: Option<String>,
+  #[yaserde(rename = "CustomAttributes")]
+  custom_attributes: Option<CustomAttributes>,
+}
+
+#[derive(Debug, Default, YaDeserialize, YaSerialize)]
+struct CustomAttributes {
+  #[yaserde(rename = "Attribute")]
+  attribute: Vec<Attribute>,
+}
+
+#[derive(Debug, Default, YaDeserialize, YaSerialize)]
+struct Attribute {
+  #[yaserde(attribute, rename = "Name")]
+  name: String,
+  #[yaserde(attribute, rename = "Value")]
+  value: String,
+}
+
+/// Fragment of the stream, repeated r times (Smooth Streaming 2.2)
+#[derive(Debug, Default, YaDeserialize, YaSerialize)]
+struct Chunk {
+  #[yaserde(attribute, rename = "n")]
+  number: Option<u64>,
+  #[yaserde(attribute, rename = "t")]
+  start: Option<u64>,
+  #[yaserde(attribute, rename = "d")]
+  duration: Option<u64>,
+  #[yaserde(attribute, rename = "r")]
+  repeat: Option<u64>,
+}
+
+/// Content protection of the presentation, the header being encoded in base64
+#[derive(Debug, Default, YaDeserialize, YaSerialize)]
+struct Protection {
+  #[yaserde(rename = "ProtectionHeader")]
+  protection_header: Vec<ProtectionHeader>,
+}
+
+#[derive(Debug, Default, YaDeserialize, YaSerialize)]
+struct ProtectionHeader {
+  #[yaserde(attribute, rename = "SystemID")]
+  system_id: String,
+  #[yaserde(text)]
+  content: String,
+}
+
+#[test]
+fn parse_client_manifest() {
+  use yaserde::{de::from_str, ser::to_string};
+
+  let contents = std::fs::read_to_string("tests/test_ism_file.ismc").unwrap();
+  let manifest: ClientManifest = from_str(&contents).unwrap();
+  assert_eq!(manifest.get_duration(), 6.0);
+
+  let streams = manifest.get_streams(&None);
+  assert_eq!(
+    streams[0],
+    Stream {
+      content_type: ContentType::Audio,
+      name: Some("audio_fre".to_string()),
+      language: Some("fre".to_string()),
+      bit_rates: vec![64000],
+      chunk_count: 3,
+      duration: 6.0,
+      max_chunk_duration: 2.0106667,
+    }
+  );
+  assert_eq!(streams[1].content_type, ContentType::Text);
+  assert_eq!(streams[1].chunk_count, 2);
+  assert_eq!(streams[1].duration, 6.0);
+  assert_eq!(streams[1].max_chunk_duration, 4.0);
+  assert_eq!(streams[2].content_type, ContentType::Video);
+  assert_eq!(streams[2].language, None);
+  assert_eq!(streams[2].bit_rates.len(), 5);
+  assert_eq!(streams[2].chunk_count, 3);
+  assert_eq!(streams[2].duration, 6.0);
+
+  // serialized manifests are parsed back as is
+  let serialized = to_string(&manifest).unwrap();
+  assert!(serialized.contains(r#"<c t="0" d="20000000" r="3" />"#));
+  let parsed_manifest: ClientManifest = from_str(&serialized).unwrap();
+  assert_eq!(parsed_manifest.get_streams(&None), streams);
+  assert_eq!(to_string(&parsed_manifest).unwrap(), serialized);
+
+  // the protection headers and custom attributes are kept
+  let contents = std::fs::read_to_string("tests/sample_protected.ismc").unwrap();
+  let manifest: ClientManifest = from_str(&contents).unwrap();
+  assert_eq!(manifest.get_streams(&None), streams);
+  let serialized = to_string(&manifest).unwrap();
+  assert!(serialized.contains(
+    r#"<ProtectionHeader SystemID="9a04f079-9840-4286-ab92-e65be0885f95">AAAAAAAAAAAAAAAAAAAAAA==</ProtectionHeader>"#
+  ));
+  assert!(serialized.contains(r#"<Attribute Name="hls_group_id" Value="audio_aacl" />"#));
+}
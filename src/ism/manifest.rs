@@ -1,7 +1,269 @@
+use crate::language;
 use crate::utils;
+use crate::xml_tree::{Document, Element};
+use crate::{ContentType, LanguageConvention};
 use mcai_worker_sdk::debug;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::io::{Read, Write};
-use yaserde::{YaDeserialize, YaSerialize};
+use std::str::FromStr;
+use yaserde::{de::from_str, YaDeserialize, YaSerialize};
+
+/// ISM manifest, edited in place so that its layout and comments are kept
+#[derive(Debug)]
+pub struct Manifest {
+  document: Document,
+  smil: Smil,
+}
+
+impl FromStr for Manifest {
+  type Err = String;
+
+  fn from_str(contents: &str) -> Result<Self, Self::Err> {
+    Ok(Manifest {
+      document: Document::parse(contents)?,
+      smil: from_str(contents)?,
+    })
+  }
+}
+
+impl fmt::Display for Manifest {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.document)
+  }
+}
+
+impl Manifest {
+  pub fn get_audio_stream_sources(&self) -> Vec<String> {
+    self.smil.get_audio_stream_sources()
+  }
+
+  pub fn get_video_stream_sources(&self) -> Vec<String> {
+    self.smil.get_video_stream_sources()
+  }
+
+  pub fn get_text_stream_sources(&self) -> Vec<String> {
+    self.smil.get_text_stream_sources()
+  }
+
+  pub fn get_languages(&self, convention: &Option<LanguageConvention>) -> Vec<String> {
+    self.smil.get_languages(convention)
+  }
+
+  pub fn get_tracks(&self, convention: &Option<LanguageConvention>) -> Vec<Track> {
+    self.smil.get_tracks(convention)
+  }
+
+  pub fn get_metadata(&self, param_group: Option<&str>, name: &str) -> Option<&str> {
+    self.smil.get_metadata(param_group, name)
+  }
+
+  pub fn get_key_server_url(&self) -> Option<&str> {
+    self.smil.get_key_server_url()
+  }
+
+  /// Set a head metadata, or a metadata of the parameter group, removing it without content
+  pub fn set_metadata(
+    &mut self,
+    param_group: Option<&str>,
+    name: &str,
+    content: Option<&str>,
+  ) -> Result<(), String> {
+    let root = self.document.root_mut().ok_or("missing smil element")?;
+    let root_indentation = root.get_indentation().unwrap_or("\n").to_string();
+    let head = root.find_mut("head").ok_or("missing head element")?;
+    let head_indentation = head.get_indentation().unwrap_or("\n").to_string();
+
+    let is_param_group = |element: &Element, id: &str| {
+      element.local_name() == "paramGroup" && element.get_attribute("id").as_deref() == Some(id)
+    };
+    let container = match param_group {
+      Some(id) => {
+        if !head.elements().any(|element| is_param_group(element, id)) {
+          if content.is_none() {
+            return Ok(());
+          }
+          let mut param_group = Element::new("paramGroup");
+          param_group.set_attribute("id", id);
+          head.insert_element(head.elements().count(), param_group);
+        }
+        head
+          .elements_mut()
+          .find(|element| is_param_group(element, id))
+          .ok_or("missing paramGroup element")?
+      }
+      None => head,
+    };
+
+    let is_meta = |element: &Element| {
+      element.local_name() == "meta" && element.get_attribute("name").as_deref() == Some(name)
+    };
+    if let Some(content) = content {
+      if container.elements().any(&is_meta) {
+        container
+          .elements_mut()
+          .filter(|element| is_meta(element))
+          .for_each(|meta| meta.set_attribute("content", content));
+      } else {
+        let mut meta = Element::new("meta");
+        meta.set_attribute("name", name);
+        meta.set_attribute("content", content);
+        // the metadata are listed before the parameter groups of the head
+        let index = container
+          .elements()
+          .position(|element| element.local_name() == "paramGroup");
+        let count = container.elements().count();
+        if count == 0 {
+          let indentation = get_child_indentation(&head_indentation, &root_indentation);
+          container.push_element(meta, &indentation, &head_indentation);
+        } else {
+          container.insert_element(index.unwrap_or(count), meta);
+        }
+      }
+    } else {
+      container.retain_elements(|element| !is_meta(element));
+    }
+    self.update()
+  }
+
+  /// Insert an audio or text track, its trackName being generated
+  pub fn add_track(
+    &mut self,
+    content_type: &ContentType,
+    source: &str,
+    bit_rate: u64,
+    language: &str,
+    track_id: u32,
+    params: &[(&str, String)],
+  ) -> Result<(), String> {
+    let element_name = match content_type {
+      ContentType::Audio => "audio",
+      ContentType::Text => "textstream",
+      ContentType::Video => return Err("video tracks cannot be inserted".to_string()),
+    };
+    let tracks = self.get_tracks(&None);
+
+    // track identifiers are unique within the source file
+    if tracks
+      .iter()
+      .any(|track| track.source == source && track.track_id == Some(track_id as u64))
+    {
+      return Err(format!(
+        "track {} of {} is already referenced",
+        track_id, source
+      ));
+    }
+    let track_names: Vec<&str> = tracks
+      .iter()
+      .filter_map(|track| track.parameters.get("trackName"))
+      .map(String::as_str)
+      .collect();
+    let mut track_name = format!("{}_{}", element_name, language);
+    let mut index = 2;
+    while track_names.contains(&track_name.as_str()) {
+      track_name = format!("{}_{}_{}", element_name, language, index);
+      index += 1;
+    }
+
+    let body = self
+      .document
+      .root_mut()
+      .and_then(|root| root.find_mut("body"))
+      .ok_or("missing body element")?;
+    let body_indentation = body.get_indentation().unwrap_or("\n").to_string();
+    let switch = body.find_mut("switch").ok_or("missing switch element")?;
+    let indentation = switch.get_indentation().unwrap_or("\n").to_string();
+    let param_indentation = switch
+      .elements()
+      .find_map(|element| element.get_indentation())
+      .map(str::to_string)
+      .unwrap_or_else(|| get_child_indentation(&indentation, &body_indentation));
+
+    let mut element = Element::new(element_name);
+    element.set_attribute("src", source);
+    element.set_attribute("systemBitrate", &bit_rate.to_string());
+    element.set_attribute("systemLanguage", language);
+    let mut all_params = vec![("trackID", track_id.to_string()), ("trackName", track_name)];
+    all_params.extend(params.iter().cloned());
+    for (label, value) in all_params {
+      let mut param = Element::new("param");
+      param.set_attribute("name", label);
+      param.set_attribute("value", &value);
+      param.set_attribute("valueType", "data");
+      element.push_element(param, &param_indentation, &indentation);
+    }
+
+    // the tracks are grouped by content type, audio tracks first and video tracks last
+    let order = ["audio", "textstream", "video"];
+    let rank = order.iter().position(|name| *name == element_name);
+    let index = switch
+      .elements()
+      .enumerate()
+      .filter(|(_, element)| {
+        let element_rank = order.iter().position(|name| *name == element.local_name());
+        element_rank.is_some() && element_rank <= rank
+      })
+      .map(|(index, _)| index + 1)
+      .last()
+      .unwrap_or_default();
+    switch.insert_element(index, element);
+    self.update()
+  }
+
+  /// Keep the tracks matching the predicate
+  pub fn retain_tracks<F: Fn(&Track) -> bool>(&mut self, predicate: F) -> Result<(), String> {
+    let switch = &self.smil.body.switch;
+    let audio_tracks: Vec<Track> = switch
+      .audio
+      .iter()
+      .map(|audio| audio.to_track(&None))
+      .collect();
+    let text_tracks: Vec<Track> = switch
+      .text
+      .iter()
+      .map(|text| text.to_track(&None))
+      .collect();
+    let video_tracks: Vec<Track> = switch
+      .video
+      .iter()
+      .map(|video| video.to_track(&None))
+      .collect();
+    let mut audio_tracks = audio_tracks.iter();
+    let mut text_tracks = text_tracks.iter();
+    let mut video_tracks = video_tracks.iter();
+
+    self
+      .document
+      .root_mut()
+      .and_then(|root| root.find_mut("body"))
+      .and_then(|body| body.find_mut("switch"))
+      .ok_or("missing switch element")?
+      .retain_elements(|element| {
+        let track = match element.local_name() {
+          "audio" => audio_tracks.next(),
+          "textstream" => text_tracks.next(),
+          "video" => video_tracks.next(),
+          _ => None,
+        };
+        track.map_or(true, &predicate)
+      });
+    self.update()
+  }
+
+  /// Describe the edited document
+  fn update(&mut self) -> Result<(), String> {
+    self.smil = from_str(&self.document.to_string())?;
+    Ok(())
+  }
+}
+
+/// Indentation of the children of an element, with the indentation unit of the document
+fn get_child_indentation(indentation: &str, parent_indentation: &str) -> String {
+  let unit = utils::strip_prefix(indentation, parent_indentation)
+    .filter(|unit| !unit.is_empty())
+    .unwrap_or("    ");
+  format!("{}{}", indentation, unit)
+}
 
 #[derive(Debug, YaDeserialize, YaSerialize)]
 #[yaserde(
@@ -9,7 +271,7 @@ use yaserde::{YaDeserialize, YaSerialize};
   prefix = "smil"
   namespace = "smil: http://www.w3.org/2001/SMIL20/Language"
 )]
-pub struct Smil {
+struct Smil {
   head: Head,
   body: Body,
 }
@@ -50,6 +312,111 @@ impl Smil {
         .collect(),
     )
   }
+
+  /// Languages of the audio and text streams, following the convention
+  pub fn get_languages(&self, convention: &Option<LanguageConvention>) -> Vec<String> {
+    let switch = &self.body.switch;
+    utils::remove_duplicates(
+      switch
+        .audio
+        .iter()
+        .map(|audio| &audio.system_language)
+        .chain(switch.text.iter().map(|text| &text.system_language))
+        .filter(|language| !language.is_empty())
+        .map(|language| language::normalize(language, convention))
+        .collect(),
+    )
+  }
+
+  /// Audio, text and video tracks, in the order of the manifest for each content type
+  pub fn get_tracks(&self, convention: &Option<LanguageConvention>) -> Vec<Track> {
+    let switch = &self.body.switch;
+    let audio_tracks = switch.audio.iter().map(|audio| audio.to_track(convention));
+    let text_tracks = switch.text.iter().map(|text| text.to_track(convention));
+    let video_tracks = switch.video.iter().map(|video| video.to_track(convention));
+    audio_tracks
+      .chain(text_tracks)
+      .chain(video_tracks)
+      .collect()
+  }
+
+  /// Content of a head metadata, or of a metadata of the parameter group
+  pub fn get_metadata(&self, param_group: Option<&str>, name: &str) -> Option<&str> {
+    let meta = match param_group {
+      Some(id) => {
+        &self
+          .head
+          .param_group
+          .iter()
+          .find(|param_group| param_group.id == id)?
+          .meta
+      }
+      None => &self.head.meta,
+    };
+    meta
+      .iter()
+      .find(|meta| meta.label == name)
+      .map(|meta| meta.content.as_str())
+  }
+
+  /// URL of the key server, given by the first parameter group declaring one
+  pub fn get_key_server_url(&self) -> Option<&str> {
+    self
+      .head
+      .param_group
+      .iter()
+      .find_map(|param_group| self.get_metadata(Some(&param_group.id), "license_server_url"))
+  }
+}
+
+/// Description of a track of the manifest
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Track {
+  pub content_type: ContentType,
+  pub source: String,
+  pub track_id: Option<u64>,
+  pub bit_rate: Option<u64>,
+  pub language: Option<String>,
+  pub role: Option<String>,
+  /// FourCC of the codec
+  pub codec: Option<String>,
+  /// All the track parameters, by name
+  pub parameters: BTreeMap<String, String>,
+}
+
+impl Track {
+  fn new(
+    content_type: ContentType,
+    source: &str,
+    system_bit_rate: &str,
+    system_language: Option<&str>,
+    params: &[Param],
+    convention: &Option<LanguageConvention>,
+  ) -> Self {
+    let parameters: BTreeMap<String, String> = params
+      .iter()
+      .map(|param| (param.label.clone(), param.value.clone()))
+      .collect();
+    let get_parameter = |name: &str| {
+      parameters
+        .get(name)
+        .filter(|value| !value.is_empty())
+        .cloned()
+    };
+
+    Track {
+      content_type,
+      source: source.to_string(),
+      track_id: get_parameter("trackID").and_then(|track_id| track_id.parse().ok()),
+      bit_rate: system_bit_rate.parse().ok(),
+      language: system_language
+        .filter(|language| !language.is_empty())
+        .map(|language| language::normalize(language, convention)),
+      role: get_parameter("Role"),
+      codec: get_parameter("FourCC"),
+      parameters,
+    }
+  }
 }
 
 #[derive(Debug, Default, YaDeserialize, YaSerialize)]
@@ -81,6 +448,8 @@ struct Meta {
   namespace = "smil: http://www.w3.org/2001/SMIL20/Language"
 )]
 struct ParamGroup {
+  #[yaserde(attribute)]
+  id: String,
   meta: Vec<Meta>,
 }
 
@@ -117,9 +486,23 @@ struct Audio {
   system_bit_rate: String,
   #[yaserde(attribute, rename = "systemLanguage")]
   system_language: String,
+  #[yaserde(rename = "param")]
   params: Vec<Param>,
 }
 
+impl Audio {
+  fn to_track(&self, convention: &Option<LanguageConvention>) -> Track {
+    Track::new(
+      ContentType::Audio,
+      &self.source,
+      &self.system_bit_rate,
+      Some(&self.system_language),
+      &self.params,
+      convention,
+    )
+  }
+}
+
 #[derive(Debug, YaDeserialize, YaSerialize)]
 #[yaserde(
   prefix = "smil"
@@ -130,9 +513,23 @@ struct Video {
   source: String,
   #[yaserde(attribute, rename = "systemBitrate")]
   system_bit_rate: String,
+  #[yaserde(rename = "param")]
   params: Vec<Param>,
 }
 
+impl Video {
+  fn to_track(&self, convention: &Option<LanguageConvention>) -> Track {
+    Track::new(
+      ContentType::Video,
+      &self.source,
+      &self.system_bit_rate,
+      None,
+      &self.params,
+      convention,
+    )
+  }
+}
+
 #[derive(Debug, YaDeserialize, YaSerialize)]
 #[yaserde(
   prefix = "smil"
@@ -145,9 +542,23 @@ struct TextStream {
   system_bit_rate: String,
   #[yaserde(attribute, rename = "systemLanguage")]
   system_language: String,
+  #[yaserde(rename = "param")]
   params: Vec<Param>,
 }
 
+impl TextStream {
+  fn to_track(&self, convention: &Option<LanguageConvention>) -> Track {
+    Track::new(
+      ContentType::Text,
+      &self.source,
+      &self.system_bit_rate,
+      Some(&self.system_language),
+      &self.params,
+      convention,
+    )
+  }
+}
+
 #[derive(Debug, YaDeserialize, YaSerialize)]
 #[yaserde(
   prefix = "smil"
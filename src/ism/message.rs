@@ -1,43 +1,86 @@
-use crate::ism::manifest::Smil;
-use crate::IsmManifestParameters;
+use crate::ism::client::ClientManifest;
+use crate::ism::manifest::{Manifest, Track};
+use crate::ism::tracks::{add_track, remove_tracks};
+use crate::language;
+use crate::utils::{load_file, load_xml_file, processing_error, save_file};
+use crate::{ContentType, IsmAction, IsmManifestParameters};
 use mcai_worker_sdk::{
   job::{JobResult, JobStatus},
   McaiChannel, MessageError, Parameter, ParameterValue,
 };
-use std::fs;
-use yaserde::de::from_str;
+use serde_json::Value;
+use std::path::Path;
 
 pub fn process(
   _channel: Option<McaiChannel>,
   parameters: IsmManifestParameters,
   job_result: JobResult,
 ) -> Result<JobResult, MessageError> {
-  let mut sources = get_manifest_sources(job_result.clone(), &parameters.source_path)?;
+  match parameters.action.clone().unwrap_or(IsmAction::Inspect) {
+    IsmAction::Inspect => {
+      let mut sources = get_manifest_sources(job_result.clone(), &parameters)?;
+      return Ok(
+        job_result
+          .with_status(JobStatus::Completed)
+          .with_parameters(&mut sources),
+      );
+    }
+    IsmAction::EditMetadata => edit_metadata(&job_result, &parameters)?,
+    IsmAction::AddSubtitle => add_track(&job_result, &parameters, ContentType::Text)?,
+    IsmAction::AddAudio => add_track(&job_result, &parameters, ContentType::Audio)?,
+    IsmAction::RemoveTracks => remove_tracks(&job_result, &parameters)?,
+  }
+
+  Ok(job_result.with_status(JobStatus::Completed))
+}
+
+fn edit_metadata(
+  job_result: &JobResult,
+  parameters: &IsmManifestParameters,
+) -> Result<(), MessageError> {
+  let metadata = parameters
+    .metadata
+    .as_ref()
+    .ok_or_else(|| processing_error(job_result, "missing metadata parameter"))?;
 
-  Ok(
-    job_result
-      .with_status(JobStatus::Completed)
-      .with_parameters(&mut sources),
-  )
+  let mut manifest: Manifest = load_file(job_result, &parameters.source_path)?;
+  for meta in metadata {
+    manifest
+      .set_metadata(
+        meta.param_group.as_deref(),
+        &meta.name,
+        meta.content.as_deref(),
+      )
+      .map_err(|message| processing_error(job_result, &message))?;
+  }
+
+  let destination_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+  save_file(job_result, &manifest, destination_path)
 }
 
-fn get_manifest_sources(job_result: JobResult, path: &str) -> Result<Vec<Parameter>, MessageError> {
-  let contents = fs::read_to_string(path).map_err(|e| {
-    MessageError::ProcessingError(
-      job_result
-        .clone()
-        .with_status(JobStatus::Error)
-        .with_message(&e.to_string()),
-    )
-  })?;
-
-  let manifest: Smil = from_str(&contents).map_err(|message| {
-    MessageError::ProcessingError(
-      job_result
-        .with_status(JobStatus::Error)
-        .with_message(&message),
-    )
-  })?;
+fn get_manifest_sources(
+  job_result: JobResult,
+  parameters: &IsmManifestParameters,
+) -> Result<Vec<Parameter>, MessageError> {
+  if let (Some(min_bit_rate), Some(max_bit_rate)) =
+    (parameters.min_bit_rate, parameters.max_bit_rate)
+  {
+    if min_bit_rate > max_bit_rate {
+      return Err(processing_error(
+        &job_result,
+        "min_bit_rate must not exceed max_bit_rate",
+      ));
+    }
+  }
+  let language_convention = &parameters.language_convention;
+
+  let mut manifest: Manifest = load_file(&job_result, &parameters.source_path)?;
+  manifest
+    .retain_tracks(|track| is_matching(parameters, track))
+    .map_err(|message| processing_error(&job_result, &message))?;
 
   let mut sources = vec![];
   sources.push(Parameter {
@@ -64,18 +107,169 @@ fn get_manifest_sources(job_result: JobResult, path: &str) -> Result<Vec<Paramet
     value: serde_json::to_value(manifest.get_text_stream_sources()).ok(),
   });
 
+  sources.push(Parameter {
+    id: "languages".to_string(),
+    kind: Vec::<String>::get_type_as_string(),
+    store: None,
+    default: None,
+    value: serde_json::to_value(manifest.get_languages(language_convention)).ok(),
+  });
+
+  // the tracks are serialized in JSON, as the parameters have no object kind
+  sources.push(Parameter {
+    id: "tracks".to_string(),
+    kind: String::get_type_as_string(),
+    store: None,
+    default: None,
+    value: serde_json::to_string(&manifest.get_tracks(language_convention))
+      .ok()
+      .map(Value::String),
+  });
+
+  if parameters.follow_client_manifest.unwrap_or(false) {
+    // the client manifest path is relative to the Manifest
+    let client_manifest_path = manifest
+      .get_metadata(None, "clientManifestRelativePath")
+      .ok_or_else(|| {
+        processing_error(&job_result, "missing clientManifestRelativePath metadata")
+      })?;
+    let client_manifest_path = Path::new(&parameters.source_path)
+      .parent()
+      .unwrap_or_else(|| Path::new(""))
+      .join(client_manifest_path)
+      .to_string_lossy()
+      .to_string();
+    let client_manifest: ClientManifest = load_xml_file(&job_result, &client_manifest_path)?;
+
+    let streams: Vec<_> = client_manifest
+      .get_streams(language_convention)
+      .into_iter()
+      .filter(|stream| {
+        is_matching_content_type(parameters, &stream.content_type)
+          && is_matching_language(parameters, &stream.content_type, stream.language.as_deref())
+      })
+      .collect();
+    sources.push(Parameter {
+      id: "duration".to_string(),
+      kind: f64::get_type_as_string(),
+      store: None,
+      default: None,
+      value: serde_json::to_value(client_manifest.get_duration()).ok(),
+    });
+    sources.push(Parameter {
+      id: "streams".to_string(),
+      kind: String::get_type_as_string(),
+      store: None,
+      default: None,
+      value: serde_json::to_string(&streams).ok().map(Value::String),
+    });
+  }
+
+  // head metadata, when declared
+  let metadata = [
+    (
+      "client_manifest_path",
+      manifest.get_metadata(None, "clientManifestRelativePath"),
+    ),
+    ("iss_playout", manifest.get_metadata(None, "iss_playout")),
+    ("hls_playout", manifest.get_metadata(None, "hls_playout")),
+    ("hds_playout", manifest.get_metadata(None, "hds_playout")),
+    ("mpd_playout", manifest.get_metadata(None, "mpd_playout")),
+    ("key_server_url", manifest.get_key_server_url()),
+  ];
+  for (id, content) in metadata {
+    if let Some(content) = content {
+      sources.push(Parameter {
+        id: id.to_string(),
+        kind: String::get_type_as_string(),
+        store: None,
+        default: None,
+        value: serde_json::to_value(content).ok(),
+      });
+    }
+  }
+
   Ok(sources)
 }
 
+/// Whether the track matches the content type, language and bitrate filters
+fn is_matching(parameters: &IsmManifestParameters, track: &Track) -> bool {
+  let is_matching_bit_rate = match track.bit_rate {
+    Some(bit_rate) => {
+      parameters
+        .min_bit_rate
+        .map_or(true, |min_bit_rate| bit_rate >= min_bit_rate)
+        && parameters
+          .max_bit_rate
+          .map_or(true, |max_bit_rate| bit_rate <= max_bit_rate)
+    }
+    None => parameters.min_bit_rate.is_none() && parameters.max_bit_rate.is_none(),
+  };
+  is_matching_content_type(parameters, &track.content_type)
+    && is_matching_language(parameters, &track.content_type, track.language.as_deref())
+    && is_matching_bit_rate
+}
+
+fn is_matching_content_type(
+  parameters: &IsmManifestParameters,
+  content_type: &ContentType,
+) -> bool {
+  parameters
+    .content_types
+    .as_ref()
+    .map_or(true, |content_types| {
+      content_types
+        .iter()
+        .any(|item| item == content_type.as_str())
+    })
+}
+
+fn is_matching_language(
+  parameters: &IsmManifestParameters,
+  content_type: &ContentType,
+  language: Option<&str>,
+) -> bool {
+  // video tracks have no language
+  match (&parameters.languages, language) {
+    (Some(languages), Some(language)) => languages
+      .iter()
+      .any(|item| language::is_same_language(item, language)),
+    (Some(_), None) => *content_type == ContentType::Video,
+    (None, _) => true,
+  }
+}
+
 #[test]
 fn get_manifest_sources_test() {
   use serde_json::Value;
 
   let job_result = JobResult::new(123);
-  let result = get_manifest_sources(job_result, "tests/sample.ism");
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    ..Default::default()
+  };
+  let result = get_manifest_sources(job_result, &parameters);
   assert!(result.is_ok());
   let parameters = result.unwrap();
   let expected_kind = Vec::<String>::get_type_as_string();
+  // the tracks and the head metadata are string parameters
+  let string_ids = [
+    "tracks",
+    "client_manifest_path",
+    "iss_playout",
+    "hls_playout",
+    "hds_playout",
+    "mpd_playout",
+    "key_server_url",
+  ];
+  let (string_parameters, parameters): (Vec<_>, Vec<_>) = parameters
+    .into_iter()
+    .partition(|param| string_ids.contains(&param.id.as_str()));
+  assert_eq!(string_parameters.len(), string_ids.len());
+  for param in string_parameters {
+    assert_eq!(param.kind, String::get_type_as_string());
+  }
+
   for param in parameters {
     assert_eq!(param.kind, expected_kind);
 
@@ -98,6 +292,10 @@ fn get_manifest_sources_test() {
           "test_file.ismt".to_string()
         )]))
       ),
+      "languages" => assert_eq!(
+        param.value,
+        Some(Value::Array(vec![Value::String("fre".to_string())]))
+      ),
       _ => assert!(false),
     }
   }
@@ -140,3 +338,275 @@ fn ack_message_test() {
   let subtitles_sources = job_result.get_parameter::<Vec<String>>("subtitles");
   assert_eq!(subtitles_sources, Ok(vec!["test_file.ismt".to_string()]));
 }
+
+#[test]
+fn get_manifest_languages_test() {
+  use mcai_worker_sdk::parameter::container::ParametersContainer;
+
+  use crate::LanguageConvention;
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    language_convention: Some(LanguageConvention::Bcp47),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(123)).unwrap();
+  let languages = job_result.get_parameter::<Vec<String>>("languages");
+  assert_eq!(languages, Ok(vec!["fr".to_string()]));
+}
+
+#[test]
+fn get_manifest_tracks_test() {
+  use crate::LanguageConvention;
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    language_convention: Some(LanguageConvention::Bcp47),
+    ..Default::default()
+  };
+  let parameters = get_manifest_sources(JobResult::new(123), &parameters).unwrap();
+  let parameter = parameters
+    .into_iter()
+    .find(|parameter| parameter.id == "tracks")
+    .unwrap();
+  assert_eq!(parameter.kind, String::get_type_as_string());
+  let tracks = parameter.value.unwrap();
+  let tracks: Vec<Track> = serde_json::from_str(tracks.as_str().unwrap()).unwrap();
+
+  assert_eq!(tracks.len(), 7);
+  let audio = &tracks[0];
+  assert_eq!(audio.content_type, ContentType::Audio);
+  assert_eq!(audio.source, "test_file.isma");
+  assert_eq!(audio.track_id, Some(1));
+  assert_eq!(audio.bit_rate, Some(64000));
+  assert_eq!(audio.language, Some("fr".to_string()));
+  assert_eq!(audio.role, Some("main".to_string()));
+  assert_eq!(audio.codec, Some("AACL".to_string()));
+  assert_eq!(audio.parameters.len(), 12);
+  assert_eq!(audio.parameters["Channels"], "2");
+  assert_eq!(audio.parameters["CodecPrivateData"], "119056E500");
+
+  let text = &tracks[1];
+  assert_eq!(text.content_type, ContentType::Text);
+  assert_eq!(text.role, Some("subtitle".to_string()));
+  assert_eq!(text.codec, Some("stpp".to_string()));
+  assert_eq!(text.parameters["CodecPrivateData"], "");
+
+  let video = &tracks[6];
+  assert_eq!(video.content_type, ContentType::Video);
+  assert_eq!(video.track_id, Some(5));
+  assert_eq!(video.bit_rate, Some(1404000));
+  assert_eq!(video.language, None);
+  assert_eq!(video.role, None);
+  assert_eq!(video.parameters["MaxWidth"], "1024");
+}
+
+#[test]
+fn get_filtered_manifest_sources_test() {
+  use mcai_worker_sdk::parameter::container::ParametersContainer;
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    content_types: Some(vec!["video".to_string()]),
+    min_bit_rate: Some(200000),
+    max_bit_rate: Some(800000),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(123)).unwrap();
+  assert_eq!(
+    job_result.get_parameter::<Vec<String>>("video"),
+    Ok(vec!["test_file.ismv".to_string()])
+  );
+  assert_eq!(job_result.get_parameter::<Vec<String>>("audio"), Ok(vec![]));
+  assert_eq!(
+    job_result.get_parameter::<Vec<String>>("subtitles"),
+    Ok(vec![])
+  );
+  let tracks = job_result.get_parameter::<String>("tracks").unwrap();
+  let tracks: Vec<Track> = serde_json::from_str(&tracks).unwrap();
+  let bit_rates: Vec<Option<u64>> = tracks.iter().map(|track| track.bit_rate).collect();
+  assert_eq!(bit_rates, vec![Some(240000), Some(512000), Some(768000)]);
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    languages: Some(vec!["fr".to_string()]),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(123)).unwrap();
+  assert_eq!(
+    job_result.get_parameter::<Vec<String>>("audio"),
+    Ok(vec!["test_file.isma".to_string()])
+  );
+  assert_eq!(
+    job_result.get_parameter::<Vec<String>>("video"),
+    Ok(vec!["test_file.ismv".to_string()])
+  );
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    content_types: Some(vec!["audio".to_string(), "text".to_string()]),
+    languages: Some(vec!["en".to_string()]),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(123)).unwrap();
+  assert_eq!(job_result.get_parameter::<Vec<String>>("audio"), Ok(vec![]));
+  assert_eq!(
+    job_result.get_parameter::<Vec<String>>("languages"),
+    Ok(vec![])
+  );
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    min_bit_rate: Some(800000),
+    max_bit_rate: Some(200000),
+    ..Default::default()
+  };
+  assert!(process(None, parameters, JobResult::new(123)).is_err());
+}
+
+#[test]
+fn get_manifest_metadata_test() {
+  use mcai_worker_sdk::parameter::container::ParametersContainer;
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(123)).unwrap();
+  assert_eq!(
+    job_result.get_parameter::<String>("client_manifest_path"),
+    Ok("test_ism_file.ismc".to_string())
+  );
+  assert_eq!(
+    job_result.get_parameter::<String>("hls_playout"),
+    Ok("aes".to_string())
+  );
+  assert_eq!(
+    job_result.get_parameter::<String>("mpd_playout"),
+    Ok("clear".to_string())
+  );
+  assert_eq!(
+    job_result.get_parameter::<String>("key_server_url"),
+    Ok("https://localhost/keys/crypt.key".to_string())
+  );
+}
+
+#[test]
+fn edit_manifest_metadata_test() {
+  use crate::MetaParameters;
+  use mcai_worker_sdk::parameter::container::ParametersContainer;
+
+  let parameters = IsmManifestParameters {
+    action: Some(IsmAction::EditMetadata),
+    source_path: "tests/sample.ism".to_string(),
+    metadata: Some(vec![
+      MetaParameters {
+        name: "hls_playout".to_string(),
+        content: Some("clear".to_string()),
+        param_group: None,
+      },
+      MetaParameters {
+        name: "clientManifestRelativePath".to_string(),
+        content: None,
+        param_group: None,
+      },
+      MetaParameters {
+        name: "license_server_url".to_string(),
+        content: Some("https://keys.example.com/key".to_string()),
+        param_group: Some("hls".to_string()),
+      },
+      MetaParameters {
+        name: "key".to_string(),
+        content: Some("ABCDEF".to_string()),
+        param_group: Some("mpd".to_string()),
+      },
+    ]),
+    destination_path: Some("tests/sample_edited.ism".to_string()),
+    ..Default::default()
+  };
+  process(None, parameters, JobResult::new(123)).unwrap();
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample_edited.ism".to_string(),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(123)).unwrap();
+  assert_eq!(
+    job_result.get_parameter::<String>("hls_playout"),
+    Ok("clear".to_string())
+  );
+  assert!(job_result
+    .get_parameter::<String>("client_manifest_path")
+    .is_err());
+  assert_eq!(
+    job_result.get_parameter::<String>("key_server_url"),
+    Ok("https://keys.example.com/key".to_string())
+  );
+  assert_eq!(
+    job_result.get_parameter::<Vec<String>>("audio"),
+    Ok(vec!["test_file.isma".to_string()])
+  );
+  let manifest: Manifest = load_file(&job_result, "tests/sample_edited.ism").unwrap();
+  assert_eq!(manifest.get_metadata(Some("mpd"), "key"), Some("ABCDEF"));
+
+  let parameters = IsmManifestParameters {
+    action: Some(IsmAction::EditMetadata),
+    source_path: "tests/sample.ism".to_string(),
+    ..Default::default()
+  };
+  assert!(process(None, parameters, JobResult::new(123)).is_err());
+}
+
+#[test]
+fn get_client_manifest_streams_test() {
+  use crate::ism::client::Stream;
+  use mcai_worker_sdk::parameter::container::ParametersContainer;
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    content_types: Some(vec!["audio".to_string(), "video".to_string()]),
+    follow_client_manifest: Some(true),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(123)).unwrap();
+  let streams = job_result.get_parameter::<String>("streams").unwrap();
+  let streams: Vec<Stream> = serde_json::from_str(&streams).unwrap();
+
+  assert_eq!(job_result.get_parameter::<f64>("duration"), Ok(6.0));
+  assert_eq!(streams.len(), 2);
+  assert_eq!(streams[0].content_type, ContentType::Audio);
+  assert_eq!(streams[0].chunk_count, 3);
+  assert_eq!(streams[0].duration, 6.0);
+  assert_eq!(streams[1].content_type, ContentType::Video);
+  assert_eq!(streams[1].chunk_count, 3);
+  assert_eq!(streams[1].max_chunk_duration, 2.0);
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    ..Default::default()
+  };
+  let job_result = process(None, parameters, JobResult::new(123)).unwrap();
+  assert!(!job_result
+    .get_parameters()
+    .iter()
+    .any(|parameter| parameter.id == "streams"));
+
+  let parameters = IsmManifestParameters {
+    action: Some(IsmAction::EditMetadata),
+    source_path: "tests/sample.ism".to_string(),
+    metadata: Some(vec![crate::MetaParameters {
+      name: "clientManifestRelativePath".to_string(),
+      content: Some("missing.ismc".to_string()),
+      param_group: None,
+    }]),
+    destination_path: Some("tests/sample_missing_client_manifest.ism".to_string()),
+    ..Default::default()
+  };
+  process(None, parameters, JobResult::new(123)).unwrap();
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample_missing_client_manifest.ism".to_string(),
+    follow_client_manifest: Some(true),
+    ..Default::default()
+  };
+  assert!(process(None, parameters, JobResult::new(123)).is_err());
+}
@@ -1,2 +1,4 @@
+mod client;
 mod manifest;
 pub mod message;
+mod tracks;
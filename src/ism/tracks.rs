@@ -0,0 +1,323 @@
+use crate::ism::manifest::{Manifest, Track};
+use crate::language;
+use crate::mp4::MediaInfo;
+use crate::utils::{load_file, processing_error, rebase_url, save_file};
+use crate::{ContentType, IsmManifestParameters};
+use mcai_worker_sdk::{job::JobResult, MessageError};
+use std::path::Path;
+
+pub fn add_track(
+  job_result: &JobResult,
+  parameters: &IsmManifestParameters,
+  content_type: ContentType,
+) -> Result<(), MessageError> {
+  let track_path = parameters
+    .track_path
+    .as_ref()
+    .ok_or_else(|| processing_error(job_result, "missing track_path parameter"))?;
+
+  // the track is described from the MP4 file, or by the parameters when it is not available yet
+  let media_info = if Path::new(track_path).exists() {
+    Some(MediaInfo::read(track_path).map_err(|message| processing_error(job_result, &message))?)
+  } else {
+    None
+  };
+  if let Some(media_info) = &media_info {
+    let is_matching_handler = match content_type {
+      ContentType::Audio => media_info.handler == "soun",
+      _ => media_info.handler == "subt" || media_info.handler == "text",
+    };
+    if !is_matching_handler {
+      let kind = match content_type {
+        ContentType::Audio => "an audio",
+        _ => "a subtitle",
+      };
+      return Err(processing_error(
+        job_result,
+        &format!("{} does not contain {} track", track_path, kind),
+      ));
+    }
+  }
+
+  let track_language = parameters
+    .track_language
+    .clone()
+    .or_else(|| {
+      media_info
+        .as_ref()
+        .and_then(|media_info| media_info.language.clone())
+        .filter(|language| language != "und")
+    })
+    .ok_or_else(|| processing_error(job_result, "missing track_language parameter"))?;
+  let track_language = language::normalize(&track_language, &parameters.language_convention);
+  let track_bit_rate = parameters
+    .track_bit_rate
+    .or_else(|| {
+      media_info
+        .as_ref()
+        .map(MediaInfo::get_bandwidth)
+        .filter(|bit_rate| *bit_rate > 0)
+    })
+    .ok_or_else(|| processing_error(job_result, "missing track_bit_rate parameter"))?;
+  let track_codec = parameters
+    .track_codec
+    .clone()
+    .or_else(|| {
+      media_info
+        .as_ref()
+        .and_then(|media_info| get_fourcc(&media_info.codecs))
+        .map(str::to_string)
+    })
+    .ok_or_else(|| processing_error(job_result, "missing track_codec parameter"))?;
+  let track_id = parameters
+    .track_id
+    .or_else(|| media_info.as_ref().map(|media_info| media_info.track_id))
+    .ok_or_else(|| processing_error(job_result, "missing track_id parameter"))?;
+  let track_role = parameters
+    .track_role
+    .clone()
+    .unwrap_or_else(|| match content_type {
+      ContentType::Audio => "main".to_string(),
+      _ => "subtitle".to_string(),
+    });
+
+  let mut params = vec![];
+  if let Some(media_info) = &media_info {
+    params.push(("TimeScale", media_info.timescale.to_string()));
+  }
+  if let Some(description) = &parameters.track_description {
+    params.push(("Description", description.clone()));
+  }
+  params.push(("Role", track_role));
+  if let Some(sampling_rate) = media_info
+    .as_ref()
+    .and_then(|media_info| media_info.sampling_rate)
+  {
+    params.push(("SamplingRate", sampling_rate.to_string()));
+  }
+  if let Some(channels) = media_info
+    .as_ref()
+    .and_then(|media_info| media_info.channels)
+  {
+    params.push(("Channels", channels.to_string()));
+  }
+  params.push(("FourCC", track_codec));
+
+  let destination_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+  let mut manifest: Manifest = load_file(job_result, &parameters.source_path)?;
+  manifest
+    .add_track(
+      &content_type,
+      &rebase_url(track_path, "", destination_path),
+      track_bit_rate,
+      &track_language,
+      track_id,
+      &params,
+    )
+    .map_err(|message| processing_error(job_result, &message))?;
+
+  save_file(job_result, &manifest, destination_path)
+}
+
+pub fn remove_tracks(
+  job_result: &JobResult,
+  parameters: &IsmManifestParameters,
+) -> Result<(), MessageError> {
+  if parameters.content_types.is_none() && parameters.languages.is_none() {
+    return Err(processing_error(
+      job_result,
+      "missing content_types or languages parameter",
+    ));
+  }
+
+  // video tracks have no language, so they are kept when a language is given
+  let is_removed = |track: &Track| {
+    parameters
+      .content_types
+      .as_ref()
+      .map_or(true, |content_types| {
+        content_types
+          .iter()
+          .any(|item| item == track.content_type.as_str())
+      })
+      && parameters.languages.as_ref().map_or(true, |languages| {
+        track.language.as_ref().map_or(false, |track_language| {
+          languages
+            .iter()
+            .any(|item| language::is_same_language(item, track_language))
+        })
+      })
+  };
+
+  let mut manifest: Manifest = load_file(job_result, &parameters.source_path)?;
+  manifest
+    .retain_tracks(|track| !is_removed(track))
+    .map_err(|message| processing_error(job_result, &message))?;
+
+  let destination_path = parameters
+    .destination_path
+    .as_ref()
+    .unwrap_or(&parameters.source_path);
+  save_file(job_result, &manifest, destination_path)
+}
+
+/// FourCC of the MP4 codecs, as declared by the Smooth Streaming manifests
+fn get_fourcc(codecs: &str) -> Option<&'static str> {
+  match codecs {
+    "mp4a.40.2" => Some("AACL"),
+    "mp4a.40.5" | "mp4a.40.29" => Some("AACH"),
+    "ac-3" => Some("AC-3"),
+    "ec-3" => Some("EC-3"),
+    "stpp" => Some("stpp"),
+    _ => None,
+  }
+}
+
+#[test]
+fn ism_round_trip() {
+  let job_result = JobResult::new(666);
+  let manifest: Manifest = load_file(&job_result, "tests/sample.ism").unwrap();
+  save_file(&job_result, &manifest, "tests/sample_round_trip.ism").unwrap();
+
+  assert_eq!(
+    std::fs::read_to_string("tests/sample_round_trip.ism").unwrap(),
+    std::fs::read_to_string("tests/sample.ism").unwrap()
+  );
+  assert_eq!(manifest.get_tracks(&None).len(), 7);
+  assert_eq!(
+    manifest.get_metadata(None, "clientManifestRelativePath"),
+    Some("test_ism_file.ismc")
+  );
+  assert_eq!(
+    manifest.get_metadata(Some("hls"), "key"),
+    Some(":ABCDEFGHIJKLMNOPQRSTUVWXYZ123456")
+  );
+}
+
+#[test]
+fn add_ism_tracks() {
+  use crate::IsmAction;
+
+  let parameters = IsmManifestParameters {
+    action: Some(IsmAction::AddAudio),
+    source_path: "tests/sample.ism".to_string(),
+    track_path: Some("tests/sample_audio_track_3.mp4".to_string()),
+    track_description: Some("Audio English".to_string()),
+    destination_path: Some("tests/sample_with_audio.ism".to_string()),
+    ..Default::default()
+  };
+  add_track(&JobResult::new(666), &parameters, ContentType::Audio).unwrap();
+
+  let manifest: Manifest = load_file(&JobResult::new(666), "tests/sample_with_audio.ism").unwrap();
+  let tracks = manifest.get_tracks(&None);
+  let audio = &tracks[1];
+  assert_eq!(audio.content_type, ContentType::Audio);
+  assert_eq!(audio.source, "sample_audio_track_3.mp4");
+  assert_eq!(audio.track_id, Some(3));
+  assert_eq!(audio.bit_rate, Some(3519));
+  assert_eq!(audio.language, Some("eng".to_string()));
+  assert_eq!(audio.role, Some("main".to_string()));
+  assert_eq!(audio.codec, Some("AACL".to_string()));
+  assert_eq!(audio.parameters["trackName"], "audio_eng");
+  assert_eq!(audio.parameters["TimeScale"], "48000");
+  assert_eq!(audio.parameters["SamplingRate"], "48000");
+  assert_eq!(audio.parameters["Channels"], "2");
+  assert_eq!(audio.parameters["Description"], "Audio English");
+
+  // the missing track file is described by the parameters
+  let parameters = IsmManifestParameters {
+    action: Some(IsmAction::AddSubtitle),
+    source_path: "tests/sample.ism".to_string(),
+    track_path: Some("tests/test_file.ismt".to_string()),
+    track_id: Some(2),
+    track_language: Some("fre".to_string()),
+    track_bit_rate: Some(1000),
+    track_codec: Some("stpp".to_string()),
+    track_role: Some("caption".to_string()),
+    destination_path: Some("tests/sample_with_subtitle.ism".to_string()),
+    ..Default::default()
+  };
+  add_track(&JobResult::new(666), &parameters, ContentType::Text).unwrap();
+
+  let manifest: Manifest =
+    load_file(&JobResult::new(666), "tests/sample_with_subtitle.ism").unwrap();
+  let tracks = manifest.get_tracks(&None);
+  let text = &tracks[2];
+  assert_eq!(text.content_type, ContentType::Text);
+  assert_eq!(text.source, "test_file.ismt");
+  assert_eq!(text.track_id, Some(2));
+  assert_eq!(text.parameters["trackName"], "textstream_fre_2");
+  assert_eq!(text.role, Some("caption".to_string()));
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    track_path: Some("tests/sample_video_dashinit.mp4".to_string()),
+    destination_path: Some("tests/sample_with_video.ism".to_string()),
+    ..Default::default()
+  };
+  assert!(add_track(&JobResult::new(666), &parameters, ContentType::Audio).is_err());
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    track_path: Some("tests/test_file.ismt".to_string()),
+    track_language: Some("fre".to_string()),
+    destination_path: Some("tests/sample_with_subtitle.ism".to_string()),
+    ..Default::default()
+  };
+  assert!(add_track(&JobResult::new(666), &parameters, ContentType::Text).is_err());
+
+  // an existing track file is read even when the parameters describe the track
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    track_path: Some("tests/sample.ism".to_string()),
+    track_id: Some(2),
+    track_language: Some("fre".to_string()),
+    track_bit_rate: Some(1000),
+    track_codec: Some("stpp".to_string()),
+    destination_path: Some("tests/sample_with_subtitle.ism".to_string()),
+    ..Default::default()
+  };
+  assert!(add_track(&JobResult::new(666), &parameters, ContentType::Text).is_err());
+}
+
+#[test]
+fn remove_ism_tracks() {
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    languages: Some(vec!["fr".to_string()]),
+    destination_path: Some("tests/sample_without_french.ism".to_string()),
+    ..Default::default()
+  };
+  remove_tracks(&JobResult::new(666), &parameters).unwrap();
+
+  let manifest: Manifest =
+    load_file(&JobResult::new(666), "tests/sample_without_french.ism").unwrap();
+  let tracks = manifest.get_tracks(&None);
+  assert_eq!(tracks.len(), 5);
+  assert!(tracks
+    .iter()
+    .all(|track| track.content_type == ContentType::Video));
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    content_types: Some(vec!["text".to_string()]),
+    destination_path: Some("tests/sample_without_subtitles.ism".to_string()),
+    ..Default::default()
+  };
+  remove_tracks(&JobResult::new(666), &parameters).unwrap();
+
+  let manifest: Manifest =
+    load_file(&JobResult::new(666), "tests/sample_without_subtitles.ism").unwrap();
+  assert_eq!(manifest.get_text_stream_sources(), Vec::<String>::new());
+  assert_eq!(manifest.get_tracks(&None).len(), 6);
+
+  let parameters = IsmManifestParameters {
+    source_path: "tests/sample.ism".to_string(),
+    destination_path: Some("tests/sample_without_tracks.ism".to_string()),
+    ..Default::default()
+  };
+  assert!(remove_tracks(&JobResult::new(666), &parameters).is_err());
+}
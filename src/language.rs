@@ -0,0 +1,272 @@
+use crate::LanguageConvention;
+
+/// ISO 639-1, ISO 639-2/B and ISO 639-2/T codes of the languages having a two-letter code
+const LANGUAGES: [(&str, &str, &str); 184] = [
+  ("aa", "aar", "aar"),
+  ("ab", "abk", "abk"),
+  ("af", "afr", "afr"),
+  ("ak", "aka", "aka"),
+  ("am", "amh", "amh"),
+  ("ar", "ara", "ara"),
+  ("an", "arg", "arg"),
+  ("as", "asm", "asm"),
+  ("av", "ava", "ava"),
+  ("ae", "ave", "ave"),
+  ("ay", "aym", "aym"),
+  ("az", "aze", "aze"),
+  ("ba", "bak", "bak"),
+  ("bm", "bam", "bam"),
+  ("be", "bel", "bel"),
+  ("bn", "ben", "ben"),
+  ("bh", "bih", "bih"),
+  ("bi", "bis", "bis"),
+  ("bo", "tib", "bod"),
+  ("bs", "bos", "bos"),
+  ("br", "bre", "bre"),
+  ("bg", "bul", "bul"),
+  ("ca", "cat", "cat"),
+  ("cs", "cze", "ces"),
+  ("ch", "cha", "cha"),
+  ("ce", "che", "che"),
+  ("cu", "chu", "chu"),
+  ("cv", "chv", "chv"),
+  ("kw", "cor", "cor"),
+  ("co", "cos", "cos"),
+  ("cr", "cre", "cre"),
+  ("cy", "wel", "cym"),
+  ("da", "dan", "dan"),
+  ("de", "ger", "deu"),
+  ("dv", "div", "div"),
+  ("dz", "dzo", "dzo"),
+  ("el", "gre", "ell"),
+  ("en", "eng", "eng"),
+  ("eo", "epo", "epo"),
+  ("et", "est", "est"),
+  ("eu", "baq", "eus"),
+  ("ee", "ewe", "ewe"),
+  ("fo", "fao", "fao"),
+  ("fa", "per", "fas"),
+  ("fj", "fij", "fij"),
+  ("fi", "fin", "fin"),
+  ("fr", "fre", "fra"),
+  ("fy", "fry", "fry"),
+  ("ff", "ful", "ful"),
+  ("gd", "gla", "gla"),
+  ("ga", "gle", "gle"),
+  ("gl", "glg", "glg"),
+  ("gv", "glv", "glv"),
+  ("gn", "grn", "grn"),
+  ("gu", "guj", "guj"),
+  ("ht", "hat", "hat"),
+  ("ha", "hau", "hau"),
+  ("he", "heb", "heb"),
+  ("hz", "her", "her"),
+  ("hi", "hin", "hin"),
+  ("ho", "hmo", "hmo"),
+  ("hr", "hrv", "hrv"),
+  ("hu", "hun", "hun"),
+  ("hy", "arm", "hye"),
+  ("ig", "ibo", "ibo"),
+  ("io", "ido", "ido"),
+  ("ii", "iii", "iii"),
+  ("iu", "iku", "iku"),
+  ("ie", "ile", "ile"),
+  ("ia", "ina", "ina"),
+  ("id", "ind", "ind"),
+  ("ik", "ipk", "ipk"),
+  ("is", "ice", "isl"),
+  ("it", "ita", "ita"),
+  ("jv", "jav", "jav"),
+  ("ja", "jpn", "jpn"),
+  ("kl", "kal", "kal"),
+  ("kn", "kan", "kan"),
+  ("ks", "kas", "kas"),
+  ("ka", "geo", "kat"),
+  ("kr", "kau", "kau"),
+  ("kk", "kaz", "kaz"),
+  ("km", "khm", "khm"),
+  ("ki", "kik", "kik"),
+  ("rw", "kin", "kin"),
+  ("ky", "kir", "kir"),
+  ("kv", "kom", "kom"),
+  ("kg", "kon", "kon"),
+  ("ko", "kor", "kor"),
+  ("kj", "kua", "kua"),
+  ("ku", "kur", "kur"),
+  ("lo", "lao", "lao"),
+  ("la", "lat", "lat"),
+  ("lv", "lav", "lav"),
+  ("li", "lim", "lim"),
+  ("ln", "lin", "lin"),
+  ("lt", "lit", "lit"),
+  ("lb", "ltz", "ltz"),
+  ("lu", "lub", "lub"),
+  ("lg", "lug", "lug"),
+  ("mh", "mah", "mah"),
+  ("ml", "mal", "mal"),
+  ("mr", "mar", "mar"),
+  ("mk", "mac", "mkd"),
+  ("mg", "mlg", "mlg"),
+  ("mt", "mlt", "mlt"),
+  ("mn", "mon", "mon"),
+  ("mi", "mao", "mri"),
+  ("ms", "may", "msa"),
+  ("my", "bur", "mya"),
+  ("na", "nau", "nau"),
+  ("nv", "nav", "nav"),
+  ("nr", "nbl", "nbl"),
+  ("nd", "nde", "nde"),
+  ("ng", "ndo", "ndo"),
+  ("ne", "nep", "nep"),
+  ("nl", "dut", "nld"),
+  ("nn", "nno", "nno"),
+  ("nb", "nob", "nob"),
+  ("no", "nor", "nor"),
+  ("ny", "nya", "nya"),
+  ("oc", "oci", "oci"),
+  ("oj", "oji", "oji"),
+  ("or", "ori", "ori"),
+  ("om", "orm", "orm"),
+  ("os", "oss", "oss"),
+  ("pa", "pan", "pan"),
+  ("pi", "pli", "pli"),
+  ("pl", "pol", "pol"),
+  ("pt", "por", "por"),
+  ("ps", "pus", "pus"),
+  ("qu", "que", "que"),
+  ("rm", "roh", "roh"),
+  ("ro", "rum", "ron"),
+  ("rn", "run", "run"),
+  ("ru", "rus", "rus"),
+  ("sg", "sag", "sag"),
+  ("sa", "san", "san"),
+  ("si", "sin", "sin"),
+  ("sk", "slo", "slk"),
+  ("sl", "slv", "slv"),
+  ("se", "sme", "sme"),
+  ("sm", "smo", "smo"),
+  ("sn", "sna", "sna"),
+  ("sd", "snd", "snd"),
+  ("so", "som", "som"),
+  ("st", "sot", "sot"),
+  ("es", "spa", "spa"),
+  ("sq", "alb", "sqi"),
+  ("sc", "srd", "srd"),
+  ("sr", "srp", "srp"),
+  ("ss", "ssw", "ssw"),
+  ("su", "sun", "sun"),
+  ("sw", "swa", "swa"),
+  ("sv", "swe", "swe"),
+  ("ty", "tah", "tah"),
+  ("ta", "tam", "tam"),
+  ("tt", "tat", "tat"),
+  ("te", "tel", "tel"),
+  ("tg", "tgk", "tgk"),
+  ("tl", "tgl", "tgl"),
+  ("th", "tha", "tha"),
+  ("ti", "tir", "tir"),
+  ("to", "ton", "ton"),
+  ("tn", "tsn", "tsn"),
+  ("ts", "tso", "tso"),
+  ("tk", "tuk", "tuk"),
+  ("tr", "tur", "tur"),
+  ("tw", "twi", "twi"),
+  ("ug", "uig", "uig"),
+  ("uk", "ukr", "ukr"),
+  ("ur", "urd", "urd"),
+  ("uz", "uzb", "uzb"),
+  ("ve", "ven", "ven"),
+  ("vi", "vie", "vie"),
+  ("vo", "vol", "vol"),
+  ("wa", "wln", "wln"),
+  ("wo", "wol", "wol"),
+  ("xh", "xho", "xho"),
+  ("yi", "yid", "yid"),
+  ("yo", "yor", "yor"),
+  ("za", "zha", "zha"),
+  ("zh", "chi", "zho"),
+  ("zu", "zul", "zul"),
+];
+
+/// Language tag following the convention, or as given without convention.
+/// Unknown languages (e.g. qaa, und) keep their code.
+pub fn normalize(language: &str, convention: &Option<LanguageConvention>) -> String {
+  let convention = match convention {
+    Some(convention) => convention,
+    None => return language.to_string(),
+  };
+
+  let mut subtags = language.trim().split(&['-', '_'][..]);
+  let primary_language = subtags.next().unwrap_or_default().to_lowercase();
+  let codes = find_language(&primary_language);
+
+  match convention {
+    LanguageConvention::Iso6391 => codes.map(|codes| codes.0),
+    LanguageConvention::Iso6392B => codes.map(|codes| codes.1),
+    LanguageConvention::Iso6392T => codes.map(|codes| codes.2),
+    LanguageConvention::Bcp47 => {
+      // the shortest code, followed by the script and region subtags
+      let mut tag = codes
+        .map(|codes| codes.0)
+        .unwrap_or(&primary_language)
+        .to_string();
+      for subtag in subtags.filter(|subtag| !subtag.is_empty()) {
+        let subtag = match subtag.len() {
+          2 => subtag.to_uppercase(),
+          4 => subtag[..1].to_uppercase() + &subtag[1..].to_lowercase(),
+          _ => subtag.to_lowercase(),
+        };
+        tag.push('-');
+        tag.push_str(&subtag);
+      }
+      return tag;
+    }
+  }
+  .unwrap_or(&primary_language)
+  .to_string()
+}
+
+/// Same language tags, whatever the convention of their primary language
+pub fn is_same_language(language: &str, other: &str) -> bool {
+  let get_subtags = |language: &str| {
+    let mut subtags: Vec<String> = language
+      .trim()
+      .split(&['-', '_'][..])
+      .map(|subtag| subtag.to_lowercase())
+      .collect();
+    if let Some(codes) = find_language(&subtags[0]) {
+      subtags[0] = codes.2.to_string();
+    }
+    subtags
+  };
+  get_subtags(language) == get_subtags(other)
+}
+
+fn find_language(code: &str) -> Option<&'static (&'static str, &'static str, &'static str)> {
+  LANGUAGES
+    .iter()
+    .find(|codes| codes.0 == code || codes.1 == code || codes.2 == code)
+}
+
+#[test]
+fn normalize_languages() {
+  let bcp_47 = Some(LanguageConvention::Bcp47);
+  assert_eq!(normalize("fre", &bcp_47), "fr");
+  assert_eq!(normalize("fra", &bcp_47), "fr");
+  assert_eq!(normalize("pt_br", &bcp_47), "pt-BR");
+  assert_eq!(normalize("zh-hant-tw", &bcp_47), "zh-Hant-TW");
+  assert_eq!(normalize("qaa", &bcp_47), "qaa");
+
+  assert_eq!(normalize("fr-CA", &Some(LanguageConvention::Iso6391)), "fr");
+  assert_eq!(normalize("fra", &Some(LanguageConvention::Iso6392B)), "fre");
+  assert_eq!(normalize("fr", &Some(LanguageConvention::Iso6392T)), "fra");
+  assert_eq!(normalize("Cze", &Some(LanguageConvention::Iso6392T)), "ces");
+  assert_eq!(normalize("und", &Some(LanguageConvention::Iso6391)), "und");
+  assert_eq!(normalize("fre", &None), "fre");
+
+  assert!(is_same_language("fre", "fr"));
+  assert!(is_same_language("deu", "GER"));
+  assert!(is_same_language("fr_ca", "fra-CA"));
+  assert!(!is_same_language("fr-CA", "fr"));
+  assert!(!is_same_language("fra", "qaa"));
+}
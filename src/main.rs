@@ -12,8 +12,14 @@ use mcai_worker_sdk::{
 use schemars::JsonSchema;
 
 mod dash;
+mod hls;
 mod ism;
+mod language;
+mod mp4;
+mod scte35;
+mod subtitle;
 mod utils;
+mod xml_tree;
 
 macro_rules! crate_version {
   () => {
@@ -24,22 +30,163 @@ macro_rules! crate_version {
 #[derive(Debug, Default)]
 struct DashManifestEvent {}
 
-#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 pub struct DashManifestParameters {
+  /// Action to apply on the Manifest (default: add_subtitle)
+  action: Option<DashAction>,
   /// Source Path of the Manifest
   source_path: String,
-  /// Subtitle source path
-  ttml_path: String,
-  /// Subtitle language
-  ttml_language: String,
+  /// Subtitle source path (TTML or SubRip, or WebVTT when converting it)
+  ttml_path: Option<String>,
+  /// Subtitle language (default: language declared by the file)
+  ttml_language: Option<String>,
   /// Subtitle role
-  ttml_role: String,
-  /// Replace the subtitle (default: false)
+  ttml_role: Option<String>,
+  /// Subtitle accessibility descriptors (e.g. urn:tva:metadata:cs:AudioPurposeCS:2007)
+  ttml_accessibility: Option<Vec<DescriptorParameters>>,
+  /// Subtitle essential property descriptors
+  ttml_essential_property: Option<Vec<DescriptorParameters>>,
+  /// Subtitle supplemental property descriptors
+  ttml_supplemental_property: Option<Vec<DescriptorParameters>>,
+  /// Subtitle label
+  ttml_label: Option<String>,
+  /// Offset added to the subtitle times, in seconds (e.g. -36000 for a timeline starting at 10:00:00)
+  ttml_time_offset: Option<f64>,
+  /// Frame rate the subtitle is authored at, its times being rescaled to the target frame rate
+  ttml_frame_rate: Option<f64>,
+  /// Frame rate of the media the subtitle times are rescaled to
+  ttml_target_frame_rate: Option<f64>,
+  /// Path of the TTML file converted from SubRip or retimed (default: next to the Manifest, failing if it exists)
+  ttml_destination_path: Option<String>,
+  /// Audio source path, packaged as a DASH on-demand MP4 file
+  audio_path: Option<String>,
+  /// Audio language (default: language of the MP4 track)
+  audio_language: Option<String>,
+  /// Audio role (default: alternate)
+  audio_role: Option<String>,
+  /// Audio accessibility descriptors (e.g. urn:tva:metadata:cs:AudioPurposeCS:2007)
+  audio_accessibility: Option<Vec<DescriptorParameters>>,
+  /// Audio label
+  audio_label: Option<String>,
+  /// Packaged MP4 files used to generate the Manifest
+  media_files: Option<Vec<MediaFileParameters>>,
+  /// SCTE-35 splice cues to insert in the Manifest, or cue points where to split the Periods
+  cues: Option<Vec<CueParameters>>,
+  /// Manifests to concatenate, each one becoming a Period of the presentation, or to merge tracks from
+  source_manifests: Option<Vec<SourceManifestParameters>>,
+  /// Content types of the tracks to merge (default: all)
+  content_types: Option<Vec<String>>,
+  /// Languages of the tracks to merge (default: all)
+  languages: Option<Vec<String>>,
+  /// Start of the clip, in seconds (default: 0)
+  start: Option<f64>,
+  /// End of the clip, in seconds (default: end of the presentation)
+  end: Option<f64>,
+  /// Replace the track with the same language and role (default: false)
   replace: Option<bool>,
+  /// Convention of the AdaptationSet languages written in the Manifest (default: as given)
+  language_convention: Option<LanguageConvention>,
+  /// Destination path of the Manifest, of the multivariant playlist when converting to HLS,
+  /// or of the converted subtitle file
   destination_path: Option<String>,
   reference_url: Option<String>,
 }
 
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DashAction {
+  /// Insert a TTML subtitle track, converted from SubRip when needed
+  AddSubtitle,
+  /// Insert an alternate audio track (dubbing, audio description)
+  AddAudio,
+  /// Generate an on-demand Manifest from packaged MP4 files
+  Generate,
+  /// Insert SCTE-35 splice cues as an EventStream
+  AddSpliceCues,
+  /// Split the Periods at the cue times
+  SplitPeriods,
+  /// Concatenate Manifests into a multi-period presentation
+  Concatenate,
+  /// Merge tracks from other Manifests of the same content
+  Merge,
+  /// Clip the presentation to a time range
+  Clip,
+  /// Convert the Manifest to HLS playlists
+  ConvertToHls,
+  /// Convert a subtitle file to the TTML, WebVTT or SubRip format of the destination file extension
+  ConvertSubtitle,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct DescriptorParameters {
+  /// Scheme identifier of the descriptor
+  scheme_id_uri: String,
+  /// Value of the descriptor
+  value: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct MediaFileParameters {
+  /// Path of the packaged MP4 file
+  path: String,
+  /// Language of the track (default: language of the MP4 track)
+  language: Option<String>,
+  /// Role of the track (default: main)
+  role: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct SourceManifestParameters {
+  /// Path of the Manifest
+  path: String,
+  /// Identifier of the asset (default: path of the Manifest)
+  asset_identifier: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct CueParameters {
+  /// Time of the splice point, in seconds from the start of the Period
+  time: f64,
+  /// Duration of the break, in seconds
+  duration: Option<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentType {
+  Audio,
+  Video,
+  /// Subtitle tracks (textstream)
+  Text,
+}
+
+impl ContentType {
+  /// Content type as named by the DASH AdaptationSets and the parameters
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ContentType::Audio => "audio",
+      ContentType::Video => "video",
+      ContentType::Text => "text",
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq)]
+pub enum LanguageConvention {
+  /// Two-letter codes (e.g. fr), or three-letter codes of the languages without one
+  #[serde(rename = "iso_639_1")]
+  Iso6391,
+  /// Bibliographic three-letter codes (e.g. fre)
+  #[serde(rename = "iso_639_2b")]
+  Iso6392B,
+  /// Terminology three-letter codes (e.g. fra)
+  #[serde(rename = "iso_639_2t")]
+  Iso6392T,
+  /// Language tags with their script and region (e.g. fr-CA)
+  #[serde(rename = "bcp_47")]
+  Bcp47,
+}
+
 impl MessageEvent<DashManifestParameters> for DashManifestEvent {
   fn get_name(&self) -> String {
     "DASH Manifest worker".to_string()
@@ -51,6 +198,11 @@ impl MessageEvent<DashManifestParameters> for DashManifestEvent {
 
   fn get_description(&self) -> String {
     r#"Parse DASH manifest file and extract related files.
+    It can add subtitle (TTML) and alternate audio tracks to the manifest,
+    insert SCTE-35 ad markers, split periods at cue points, concatenate or merge manifests,
+    clip the presentation to a time range, convert it to HLS, or generate a new manifest from packaged MP4 files.
+    SubRip subtitles are converted to TTML on insertion,
+    and subtitle files can be converted between TTML and WebVTT before their insertion.
     "#
     .to_string()
   }
@@ -72,10 +224,67 @@ impl MessageEvent<DashManifestParameters> for DashManifestEvent {
 #[derive(Debug, Default)]
 struct IsmManifestEvent {}
 
-#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 pub struct IsmManifestParameters {
+  /// Action to apply on the Manifest (default: inspect)
+  action: Option<IsmAction>,
   /// Source Path of the Manifest
   source_path: String,
+  /// Convention of the track languages (default: as given)
+  language_convention: Option<LanguageConvention>,
+  /// Content types of the returned or removed tracks: audio, video or text (default: all)
+  content_types: Option<Vec<String>>,
+  /// Languages of the returned or removed audio and text tracks (default: all)
+  languages: Option<Vec<String>>,
+  /// Minimum bitrate of the returned tracks, in bits per second
+  min_bit_rate: Option<u64>,
+  /// Maximum bitrate of the returned tracks, in bits per second
+  max_bit_rate: Option<u64>,
+  /// Describe the chunks of the streams from the referenced client manifest (default: false)
+  follow_client_manifest: Option<bool>,
+  /// Head metadata to set, or to remove without content
+  metadata: Option<Vec<MetaParameters>>,
+  /// Source path of the inserted audio or text track
+  track_path: Option<String>,
+  /// Identifier of the inserted track in its source file (default: track ID of the MP4 file)
+  track_id: Option<u32>,
+  /// Language of the inserted track (default: language of the MP4 file)
+  track_language: Option<String>,
+  /// Bitrate of the inserted track, in bits per second (default: average bitrate of the MP4 file)
+  track_bit_rate: Option<u64>,
+  /// FourCC of the inserted track codec, e.g. AACL or stpp (default: codec of the MP4 file)
+  track_codec: Option<String>,
+  /// Role of the inserted track (default: main for audio, subtitle for text)
+  track_role: Option<String>,
+  /// Description of the inserted track
+  track_description: Option<String>,
+  /// Destination path of the Manifest (default: source path)
+  destination_path: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IsmAction {
+  /// List the sources, tracks and head metadata
+  Inspect,
+  /// Set or remove head metadata (e.g. hls_playout, clientManifestRelativePath)
+  EditMetadata,
+  /// Insert a textstream track
+  AddSubtitle,
+  /// Insert an audio track
+  AddAudio,
+  /// Remove the tracks matching the content types and language
+  RemoveTracks,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct MetaParameters {
+  /// Name of the metadata
+  name: String,
+  /// Content of the metadata (default: the metadata is removed)
+  content: Option<String>,
+  /// Identifier of the parameter group holding the metadata (e.g. hls)
+  param_group: Option<String>,
 }
 
 impl MessageEvent<IsmManifestParameters> for IsmManifestEvent {
@@ -89,7 +298,13 @@ impl MessageEvent<IsmManifestParameters> for IsmManifestEvent {
 
   fn get_description(&self) -> String {
     r#"Parse ISM manifest file and extract related files.
-    It can be possible to filter per content-type (Audio, Video, Subtitle)
+    It can be possible to filter per content-type (Audio, Video, Subtitle), language and bitrate range,
+    and to list the track languages following an ISO 639 or BCP 47 convention.
+    Every track is also described with its bitrate, language, role, codec and parameters,
+    along with the playout settings, client manifest path and key server URL of the head,
+    which can be edited.
+    Audio and text tracks can also be inserted, or removed per content-type and language.
+    The duration, chunk counts and chunk durations of the streams can be read from the client manifest.
     "#
     .to_string()
   }
@@ -108,8 +323,92 @@ impl MessageEvent<IsmManifestParameters> for IsmManifestEvent {
   }
 }
 
+#[derive(Debug, Default)]
+struct HlsManifestEvent {}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct HlsManifestParameters {
+  /// Action to apply on the playlists (default: inspect)
+  action: Option<HlsAction>,
+  /// Source Path of the multivariant playlist
+  source_path: String,
+  /// Subtitle source path (TTML, WebVTT or SubRip), or segmented WebVTT media playlist
+  subtitle_path: Option<String>,
+  /// Subtitle language (default: language declared by the file)
+  subtitle_language: Option<String>,
+  /// Subtitle name (default: language)
+  subtitle_name: Option<String>,
+  /// Select the subtitle when the user has no preference (default: false)
+  subtitle_default: Option<bool>,
+  /// Select the subtitle matching the user preferences (default: true)
+  subtitle_autoselect: Option<bool>,
+  /// Subtitle only translating foreign dialogs (default: false)
+  subtitle_forced: Option<bool>,
+  /// Group of the subtitle renditions (default: group of the variant streams, or subtitles)
+  subtitle_group_id: Option<String>,
+  /// Offset added to the subtitle times, in seconds (e.g. -36000 for a timeline starting at 10:00:00)
+  subtitle_time_offset: Option<f64>,
+  /// Frame rate the subtitle is authored at, its times being rescaled to the target frame rate
+  subtitle_frame_rate: Option<f64>,
+  /// Frame rate of the media the subtitle times are rescaled to
+  subtitle_target_frame_rate: Option<f64>,
+  /// Duration of the WebVTT segments, in seconds (default: a single segment)
+  segment_duration: Option<f64>,
+  /// Replace the subtitle with the same language and forced flag (default: false)
+  replace: Option<bool>,
+  /// Destination path of the multivariant playlist, of the subtitle media playlist,
+  /// or of the converted subtitle file
+  destination_path: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HlsAction {
+  /// List the media playlists per content type
+  Inspect,
+  /// Insert a WebVTT subtitle rendition
+  AddSubtitle,
+  /// Generate the WebVTT media playlist of a subtitle file
+  GenerateSubtitlePlaylist,
+  /// Convert a subtitle file to the TTML, WebVTT or SubRip format of the destination file extension
+  ConvertSubtitle,
+}
+
+impl MessageEvent<HlsManifestParameters> for HlsManifestEvent {
+  fn get_name(&self) -> String {
+    "HLS Manifest worker".to_string()
+  }
+
+  fn get_short_description(&self) -> String {
+    "Parse and get some information from HLS playlists".to_string()
+  }
+
+  fn get_description(&self) -> String {
+    r#"Parse HLS multivariant playlist and extract related media playlists.
+    They are listed per content-type (Audio, Video, Subtitle).
+    It can also add WebVTT subtitle renditions to the playlist,
+    generate the media playlist of a TTML, WebVTT or SubRip subtitle file, or convert it between these formats.
+    "#
+    .to_string()
+  }
+
+  fn get_version(&self) -> Version {
+    Version::parse(crate_version!()).expect("unable to locate Package version")
+  }
+
+  fn process(
+    &self,
+    channel: Option<McaiChannel>,
+    parameters: HlsManifestParameters,
+    job_result: JobResult,
+  ) -> Result<JobResult, MessageError> {
+    hls::message::process(channel, parameters, job_result)
+  }
+}
+
 const ISM: &str = "ISM";
 const DASH: &str = "DASH";
+const HLS: &str = "HLS";
 
 fn main() {
   match env::var("MANIFEST_MODE")
@@ -126,6 +425,11 @@ fn main() {
       let message_event = DashManifestEvent::default();
       start_worker(message_event)
     }
+    HLS => {
+      info!("Start worker with HLS mode...");
+      let message_event = HlsManifestEvent::default();
+      start_worker(message_event)
+    }
     value => {
       error!("Unsupported mode: {:?}", value);
       exit(1);
@@ -0,0 +1,711 @@
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Description of a packaged (DASH on-demand profile) MP4 file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaInfo {
+  pub file_size: u64,
+  pub track_id: u32,
+  pub codecs: String,
+  pub handler: String,
+  pub language: Option<String>,
+  pub timescale: u32,
+  pub duration: u64,
+  pub sampling_rate: Option<u32>,
+  pub channels: Option<u16>,
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+  pub brands: Vec<String>,
+  pub initialization_range: (u64, u64),
+  pub index_range: Option<(u64, u64)>,
+  pub segments: Vec<Segment>,
+}
+
+/// Subsegment referenced by the segment index, times are expressed in the track timescale
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Segment {
+  pub offset: u64,
+  pub size: u64,
+  pub start: u64,
+  pub duration: u64,
+}
+
+impl MediaInfo {
+  pub fn read(path: &str) -> Result<Self, String> {
+    let mut file = File::open(path).map_err(|error| format!("{}: {}", path, error))?;
+    let file_size = file
+      .metadata()
+      .map_err(|error| format!("{}: {}", path, error))?
+      .len();
+
+    let mut media_info = MediaInfo {
+      file_size,
+      ..Default::default()
+    };
+
+    let mut moov_found = false;
+    let mut position = 0;
+    while position < file_size {
+      let (kind, size, header_size) = read_box_header(&mut file, position, file_size)?;
+
+      match &kind {
+        b"ftyp" | b"styp" if media_info.brands.is_empty() => {
+          let payload = read_payload(&mut file, position + header_size, size - header_size)?;
+          media_info.brands = payload
+            .chunks_exact(4)
+            .enumerate()
+            // skip the minor version
+            .filter(|(index, _)| *index != 1)
+            .map(|(_, brand)| fourcc(brand))
+            .collect();
+        }
+        b"moov" => {
+          let payload = read_payload(&mut file, position + header_size, size - header_size)?;
+          media_info.parse_movie(&payload)?;
+          media_info.initialization_range = (0, position + size - 1);
+          moov_found = true;
+        }
+        b"sidx" if media_info.index_range.is_none() => {
+          let payload = read_payload(&mut file, position + header_size, size - header_size)?;
+          media_info.parse_segment_index(&payload, position + size)?;
+          media_info.index_range = Some((position, position + size - 1));
+        }
+        _ => {}
+      }
+
+      position += size;
+    }
+
+    if !moov_found {
+      return Err(format!("{}: missing moov box", path));
+    }
+
+    Ok(media_info)
+  }
+
+  /// MIME type of the file, based on the track handler
+  pub fn get_mime_type(&self) -> String {
+    match self.handler.as_str() {
+      "soun" => "audio/mp4",
+      "vide" => "video/mp4",
+      _ => "application/mp4",
+    }
+    .to_string()
+  }
+
+  /// Duration of the media, in seconds
+  pub fn get_duration(&self) -> f64 {
+    if self.timescale == 0 {
+      return 0.0;
+    }
+    self.duration as f64 / self.timescale as f64
+  }
+
+  /// Longest segment duration, in seconds
+  pub fn get_max_segment_duration(&self) -> f64 {
+    if self.timescale == 0 {
+      return 0.0;
+    }
+    self
+      .segments
+      .iter()
+      .map(|segment| segment.duration)
+      .max()
+      .unwrap_or(self.duration) as f64
+      / self.timescale as f64
+  }
+
+  /// Average bandwidth of the media, in bits per second
+  pub fn get_bandwidth(&self) -> u64 {
+    let duration = self.get_duration();
+    if duration == 0.0 {
+      return 0;
+    }
+    (self.file_size as f64 * 8.0 / duration).round() as u64
+  }
+
+  fn parse_movie(&mut self, data: &[u8]) -> Result<(), String> {
+    let mut movie_timescale = 0;
+    let mut fragment_duration = None;
+
+    for (kind, payload) in children(data)? {
+      match &kind {
+        b"mvhd" => {
+          let (version, content) = full_box(payload)?;
+          movie_timescale = read_u32(content, if version == 1 { 16 } else { 8 })?;
+        }
+        b"trak" if self.handler.is_empty() => self.parse_track(payload)?,
+        b"mvex" => {
+          if let Some(mehd) = find_child(payload, b"mehd")? {
+            let (version, content) = full_box(mehd)?;
+            fragment_duration = Some(if version == 1 {
+              read_u64(content, 0)?
+            } else {
+              read_u32(content, 0)? as u64
+            });
+          }
+        }
+        _ => {}
+      }
+    }
+
+    if let Some(fragment_duration) = fragment_duration {
+      if self.duration == 0 && movie_timescale != 0 {
+        self.duration = u64::try_from(
+          fragment_duration as u128 * self.timescale as u128 / movie_timescale as u128,
+        )
+        .map_err(|_| "invalid mehd box".to_string())?;
+      }
+    }
+
+    if self.handler.is_empty() {
+      return Err("missing track in moov box".to_string());
+    }
+    Ok(())
+  }
+
+  fn parse_track(&mut self, data: &[u8]) -> Result<(), String> {
+    let tkhd = find_child(data, b"tkhd")?.ok_or("missing tkhd box")?;
+    let (version, content) = full_box(tkhd)?;
+    self.track_id = read_u32(content, if version == 1 { 16 } else { 8 })?;
+
+    let mdia = find_child(data, b"mdia")?.ok_or("missing mdia box")?;
+
+    for (kind, payload) in children(mdia)? {
+      match &kind {
+        b"mdhd" => {
+          let (version, content) = full_box(payload)?;
+          let (timescale, duration, language_offset) = if version == 1 {
+            (read_u32(content, 16)?, read_u64(content, 20)?, 28)
+          } else {
+            (read_u32(content, 8)?, read_u32(content, 12)? as u64, 16)
+          };
+          self.timescale = timescale;
+          self.duration = duration;
+          self.language = parse_language(read_u16(content, language_offset)?);
+        }
+        b"hdlr" => {
+          let (_, content) = full_box(payload)?;
+          self.handler = fourcc(content.get(4..8).ok_or("invalid hdlr box")?);
+        }
+        b"minf" => {
+          let stbl = find_child(payload, b"stbl")?.ok_or("missing stbl box")?;
+          let stsd = find_child(stbl, b"stsd")?.ok_or("missing stsd box")?;
+          self.parse_sample_description(stsd)?;
+        }
+        _ => {}
+      }
+    }
+    Ok(())
+  }
+
+  fn parse_sample_description(&mut self, data: &[u8]) -> Result<(), String> {
+    let (_, content) = full_box(data)?;
+    let entries = content.get(4..).ok_or("invalid stsd box")?;
+    let (format, entry) = children(entries)?
+      .into_iter()
+      .next()
+      .ok_or("empty stsd box")?;
+
+    self.codecs = fourcc(&format);
+
+    match &format {
+      b"mp4a" | b"ac-3" | b"ec-3" | b"Opus" | b"fLaC" => {
+        // SampleEntry (8 bytes) + reserved (8 bytes) + AudioSampleEntry fields
+        self.channels = Some(read_u16(entry, 16)?);
+        self.sampling_rate = Some(read_u32(entry, 24)? >> 16);
+
+        let boxes = children(entry.get(28..).unwrap_or_default())?;
+        if format == *b"mp4a" {
+          if let Some((_, esds)) = boxes.iter().find(|(kind, _)| kind == b"esds") {
+            self.parse_elementary_stream_descriptor(esds)?;
+          }
+        }
+        if format == *b"Opus" {
+          self.codecs = "opus".to_string();
+        }
+      }
+      b"avc1" | b"avc3" | b"hvc1" | b"hev1" | b"vp09" | b"av01" => {
+        // SampleEntry (8 bytes) + pre-defined and reserved (16 bytes) + VisualSampleEntry fields
+        self.width = Some(read_u16(entry, 24)? as u32);
+        self.height = Some(read_u16(entry, 26)? as u32);
+
+        for (kind, payload) in children(entry.get(78..).unwrap_or_default())? {
+          match &kind {
+            b"avcC" if payload.len() >= 4 => {
+              self.codecs = format!(
+                "{}.{:02X}{:02X}{:02X}",
+                self.codecs, payload[1], payload[2], payload[3]
+              );
+            }
+            b"hvcC" if payload.len() >= 13 => {
+              self.codecs = format!("{}.{}", self.codecs, hevc_codec_parameters(payload));
+            }
+            _ => {}
+          }
+        }
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+
+  fn parse_elementary_stream_descriptor(&mut self, data: &[u8]) -> Result<(), String> {
+    let (_, content) = full_box(data)?;
+
+    let (tag, es_descriptor) = read_descriptor(content)?;
+    if tag != 0x03 {
+      return Ok(());
+    }
+    let flags = *es_descriptor.get(2).ok_or("invalid ES descriptor")?;
+    let mut offset = 3;
+    if flags & 0x80 != 0 {
+      offset += 2;
+    }
+    if flags & 0x40 != 0 {
+      offset += 1 + *es_descriptor.get(offset).ok_or("invalid ES descriptor")? as usize;
+    }
+    if flags & 0x20 != 0 {
+      offset += 2;
+    }
+
+    let (tag, decoder_config) = read_descriptor(es_descriptor.get(offset..).unwrap_or_default())?;
+    if tag != 0x04 {
+      return Ok(());
+    }
+    let object_type = *decoder_config.first().ok_or("invalid decoder config")?;
+    self.codecs = format!("mp4a.{:x}", object_type);
+
+    if let Ok((0x05, specific_info)) = read_descriptor(decoder_config.get(13..).unwrap_or_default())
+    {
+      self.parse_audio_specific_config(specific_info);
+    }
+    Ok(())
+  }
+
+  fn parse_audio_specific_config(&mut self, data: &[u8]) {
+    let mut bits = BitReader::new(data);
+    let mut object_type = bits.read(5);
+    if object_type == Some(31) {
+      object_type = bits.read(6).map(|value| value + 32);
+    }
+    if let Some(object_type) = object_type {
+      self.codecs = format!("{}.{}", self.codecs, object_type);
+    }
+
+    if bits.read(4) == Some(0xf) {
+      bits.read(24);
+    }
+    if let Some(channel_configuration) = bits.read(4) {
+      if channel_configuration > 0 && channel_configuration < 7 {
+        self.channels = Some(channel_configuration as u16);
+      } else if channel_configuration == 7 {
+        self.channels = Some(8);
+      }
+    }
+  }
+
+  fn parse_segment_index(&mut self, data: &[u8], end_position: u64) -> Result<(), String> {
+    let (version, content) = full_box(data)?;
+    let timescale = read_u32(content, 4)?;
+    let (earliest_presentation_time, first_offset, offset) = if version == 0 {
+      (
+        read_u32(content, 8)? as u64,
+        read_u32(content, 12)? as u64,
+        16,
+      )
+    } else {
+      (read_u64(content, 8)?, read_u64(content, 16)?, 24)
+    };
+    let reference_count = read_u16(content, offset + 2)? as usize;
+
+    let rescale = |value: u64| {
+      if self.timescale == 0 || timescale == 0 {
+        Ok(value)
+      } else {
+        u64::try_from(value as u128 * self.timescale as u128 / timescale as u128)
+          .map_err(|_| "invalid sidx box".to_string())
+      }
+    };
+    let add = |value: u64, other: u64| {
+      value
+        .checked_add(other)
+        .ok_or_else(|| "invalid sidx box".to_string())
+    };
+
+    let mut segments = vec![];
+    let mut position = add(end_position, first_offset)?;
+    let mut start = earliest_presentation_time;
+    for index in 0..reference_count {
+      let reference = offset + 4 + index * 12;
+      let size = (read_u32(content, reference)? & 0x7fff_ffff) as u64;
+      let duration = read_u32(content, reference + 4)? as u64;
+
+      segments.push(Segment {
+        offset: position,
+        size,
+        start: rescale(start)?,
+        duration: rescale(duration)?,
+      });
+      position = add(position, size)?;
+      start = add(start, duration)?;
+    }
+
+    // the segment index is the most accurate duration of an on-demand file
+    if self.timescale == 0 {
+      self.timescale = timescale;
+    }
+    self.duration = segments
+      .iter()
+      .try_fold(0, |duration, segment| add(duration, segment.duration))?;
+    self.segments = segments;
+    Ok(())
+  }
+}
+
+fn read_box_header(
+  file: &mut File,
+  position: u64,
+  file_size: u64,
+) -> Result<([u8; 4], u64, u64), String> {
+  let mut header = [0; 8];
+  file
+    .seek(SeekFrom::Start(position))
+    .and_then(|_| file.read_exact(&mut header))
+    .map_err(|error| format!("unable to read box at {}: {}", position, error))?;
+
+  let kind = [header[4], header[5], header[6], header[7]];
+  let (size, header_size) = match read_u32(&header, 0)? {
+    0 => (file_size - position, 8),
+    1 => {
+      let mut large_size = [0; 8];
+      file
+        .read_exact(&mut large_size)
+        .map_err(|error| format!("unable to read box at {}: {}", position, error))?;
+      (u64::from_be_bytes(large_size), 16)
+    }
+    size => (size as u64, 8),
+  };
+
+  // a malformed large size may overflow the position
+  let is_valid_size = size >= header_size
+    && position
+      .checked_add(size)
+      .map_or(false, |end| end <= file_size);
+  if !is_valid_size {
+    return Err(format!(
+      "invalid {} box size at {}",
+      fourcc(&kind),
+      position
+    ));
+  }
+  Ok((kind, size, header_size))
+}
+
+fn read_payload(file: &mut File, position: u64, size: u64) -> Result<Vec<u8>, String> {
+  let mut payload = vec![0; size as usize];
+  file
+    .seek(SeekFrom::Start(position))
+    .and_then(|_| file.read_exact(&mut payload))
+    .map_err(|error| format!("unable to read box at {}: {}", position, error))?;
+  Ok(payload)
+}
+
+type Boxes<'a> = Vec<([u8; 4], &'a [u8])>;
+
+/// List the boxes contained in a box payload
+fn children(data: &[u8]) -> Result<Boxes<'_>, String> {
+  let mut boxes = vec![];
+  let mut offset = 0;
+
+  while offset + 8 <= data.len() {
+    let kind = [
+      data[offset + 4],
+      data[offset + 5],
+      data[offset + 6],
+      data[offset + 7],
+    ];
+    let (size, header_size) = match read_u32(data, offset)? {
+      0 => (data.len() - offset, 8),
+      1 => (
+        usize::try_from(read_u64(data, offset + 8)?).unwrap_or(usize::MAX),
+        16,
+      ),
+      size => (size as usize, 8),
+    };
+
+    let end = offset
+      .checked_add(size)
+      .filter(|end| size >= header_size && *end <= data.len())
+      .ok_or_else(|| format!("invalid {} box size", fourcc(&kind)))?;
+    boxes.push((kind, &data[offset + header_size..end]));
+    offset = end;
+  }
+  Ok(boxes)
+}
+
+fn find_child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Result<Option<&'a [u8]>, String> {
+  Ok(
+    children(data)?
+      .into_iter()
+      .find(|(child_kind, _)| child_kind == kind)
+      .map(|(_, payload)| payload),
+  )
+}
+
+/// Split a full box payload into its version and content
+fn full_box(data: &[u8]) -> Result<(u8, &[u8]), String> {
+  if data.len() < 4 {
+    return Err("invalid full box".to_string());
+  }
+  Ok((data[0], &data[4..]))
+}
+
+fn read_descriptor(data: &[u8]) -> Result<(u8, &[u8]), String> {
+  let tag = *data.first().ok_or("invalid descriptor")?;
+  let mut length = 0;
+  let mut offset = 1;
+  loop {
+    let byte = *data.get(offset).ok_or("invalid descriptor length")?;
+    length = (length << 7) | (byte & 0x7f) as usize;
+    offset += 1;
+    if byte & 0x80 == 0 || offset > 4 {
+      break;
+    }
+  }
+  let content = data
+    .get(offset..offset + length)
+    .ok_or("invalid descriptor length")?;
+  Ok((tag, content))
+}
+
+/// Codec parameters of an HEVC track (ISO/IEC 14496-15 Annex E)
+fn hevc_codec_parameters(configuration: &[u8]) -> String {
+  let profile_space = ["", "A", "B", "C"][(configuration[1] >> 6) as usize];
+  let tier = if configuration[1] & 0x20 != 0 {
+    "H"
+  } else {
+    "L"
+  };
+  let profile = configuration[1] & 0x1f;
+  let compatibility = u32::from_be_bytes([
+    configuration[2],
+    configuration[3],
+    configuration[4],
+    configuration[5],
+  ])
+  .reverse_bits();
+  let level = configuration[12];
+
+  let mut constraints: Vec<u8> = configuration[6..12].to_vec();
+  while constraints.last() == Some(&0) {
+    constraints.pop();
+  }
+
+  let mut parameters = format!(
+    "{}{}.{:X}.{}{}",
+    profile_space, profile, compatibility, tier, level
+  );
+  for constraint in constraints {
+    parameters += &format!(".{:X}", constraint);
+  }
+  parameters
+}
+
+fn parse_language(code: u16) -> Option<String> {
+  let language: String = [(code >> 10) & 0x1f, (code >> 5) & 0x1f, code & 0x1f]
+    .iter()
+    .map(|character| (*character as u8 + 0x60) as char)
+    .collect();
+
+  if language
+    .chars()
+    .all(|character| character.is_ascii_lowercase())
+  {
+    Some(language)
+  } else {
+    None
+  }
+}
+
+fn fourcc(kind: &[u8]) -> String {
+  String::from_utf8_lossy(kind).to_string()
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+  data
+    .get(offset..offset + 2)
+    .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+    .ok_or_else(|| "unexpected end of box".to_string())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+  data
+    .get(offset..offset + 4)
+    .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+    .ok_or_else(|| "unexpected end of box".to_string())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, String> {
+  data
+    .get(offset..offset + 8)
+    .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+    .ok_or_else(|| "unexpected end of box".to_string())
+}
+
+struct BitReader<'a> {
+  data: &'a [u8],
+  position: usize,
+}
+
+impl<'a> BitReader<'a> {
+  fn new(data: &'a [u8]) -> Self {
+    BitReader { data, position: 0 }
+  }
+
+  fn read(&mut self, count: usize) -> Option<u32> {
+    let mut value = 0;
+    for _ in 0..count {
+      let byte = self.data.get(self.position / 8)?;
+      let bit = (byte >> (7 - self.position % 8)) & 1;
+      value = (value << 1) | bit as u32;
+      self.position += 1;
+    }
+    Some(value)
+  }
+}
+
+#[test]
+fn read_audio_media_info() {
+  let media_info = MediaInfo::read("tests/sample_audio_dashinit.mp4").unwrap();
+
+  assert_eq!(media_info.file_size, 2639);
+  assert_eq!(media_info.handler, "soun");
+  assert_eq!(media_info.track_id, 1);
+  assert_eq!(media_info.codecs, "mp4a.40.2");
+  assert_eq!(media_info.language, Some("eng".to_string()));
+  assert_eq!(media_info.sampling_rate, Some(48000));
+  assert_eq!(media_info.channels, Some(2));
+  assert_eq!(media_info.timescale, 48000);
+  assert_eq!(media_info.duration, 288000);
+  assert_eq!(media_info.initialization_range, (0, 602));
+  assert_eq!(media_info.index_range, Some((603, 670)));
+  assert_eq!(media_info.get_bandwidth(), 3519);
+  assert_eq!(media_info.get_max_segment_duration(), 2.0);
+  assert_eq!(
+    media_info.segments,
+    vec![
+      Segment {
+        offset: 671,
+        size: 556,
+        start: 0,
+        duration: 96000
+      },
+      Segment {
+        offset: 1227,
+        size: 656,
+        start: 96000,
+        duration: 96000
+      },
+      Segment {
+        offset: 1883,
+        size: 756,
+        start: 192000,
+        duration: 96000
+      },
+    ]
+  );
+
+  let media_info = MediaInfo::read("tests/sample_audio_track_3.mp4").unwrap();
+  assert_eq!(media_info.track_id, 3);
+}
+
+#[test]
+fn read_video_media_info() {
+  let media_info = MediaInfo::read("tests/sample_video_dashinit.mp4").unwrap();
+
+  assert_eq!(media_info.handler, "vide");
+  assert_eq!(media_info.get_mime_type(), "video/mp4");
+  assert_eq!(media_info.codecs, "avc1.64001F");
+  assert_eq!(media_info.width, Some(1280));
+  assert_eq!(media_info.height, Some(720));
+  assert_eq!(media_info.language, Some("und".to_string()));
+  assert_eq!(media_info.timescale, 12800);
+  assert_eq!(media_info.get_duration(), 6.0);
+  assert_eq!(
+    media_info.brands,
+    vec!["iso6".to_string(), "iso6".to_string(), "dash".to_string()]
+  );
+  assert_eq!(media_info.initialization_range, (0, 642));
+  assert_eq!(media_info.index_range, Some((643, 710)));
+}
+
+#[test]
+fn read_subtitle_media_info() {
+  let media_info = MediaInfo::read("tests/sample_subtitle_dashinit.mp4").unwrap();
+
+  assert_eq!(media_info.handler, "subt");
+  assert_eq!(media_info.get_mime_type(), "application/mp4");
+  assert_eq!(media_info.codecs, "stpp");
+  assert_eq!(media_info.language, Some("fra".to_string()));
+  assert_eq!(media_info.timescale, 1000);
+  assert_eq!(media_info.duration, 6000);
+  assert_eq!(media_info.initialization_range, (0, 567));
+  assert_eq!(media_info.index_range, Some((568, 635)));
+}
+
+#[test]
+fn hevc_codec_string() {
+  let configuration = [
+    0x01, 0x01, 0x60, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5d,
+  ];
+  assert_eq!(hevc_codec_parameters(&configuration), "1.6.L93.90");
+}
+
+#[test]
+fn reject_overflowing_box_sizes() {
+  // 64-bit large size reaching the end of the address space
+  let mut data = vec![0, 0, 0, 1];
+  data.extend_from_slice(b"moov");
+  data.extend_from_slice(&(u64::MAX - 4).to_be_bytes());
+  data.extend_from_slice(&[0; 8]);
+
+  assert!(children(&data).is_err());
+
+  std::fs::write("tests/invalid_large_size.mp4", &data).unwrap();
+  assert!(MediaInfo::read("tests/invalid_large_size.mp4").is_err());
+}
+
+#[test]
+fn reject_overflowing_segment_indexes() {
+  let segment_index = |earliest_presentation_time: u64, first_offset: u64| {
+    let mut data = vec![1, 0, 0, 0];
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&1000u32.to_be_bytes());
+    data.extend_from_slice(&earliest_presentation_time.to_be_bytes());
+    data.extend_from_slice(&first_offset.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&100u32.to_be_bytes());
+    data.extend_from_slice(&1000u32.to_be_bytes());
+    data.extend_from_slice(&0u32.to_be_bytes());
+    data
+  };
+  let mut media_info = MediaInfo {
+    timescale: 48000,
+    ..Default::default()
+  };
+
+  assert!(media_info
+    .parse_segment_index(&segment_index(0, 0), 100)
+    .is_ok());
+  assert_eq!(media_info.duration, 48000);
+  assert_eq!(
+    media_info.parse_segment_index(&segment_index(0, u64::MAX), 100),
+    Err("invalid sidx box".to_string())
+  );
+  assert_eq!(
+    media_info.parse_segment_index(&segment_index(u64::MAX / 2, 0), 100),
+    Err("invalid sidx box".to_string())
+  );
+}
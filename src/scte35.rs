@@ -0,0 +1,124 @@
+/// Frequency of the SCTE-35 time fields
+pub const TIMESCALE: u64 = 90_000;
+
+/// Build a splice_info_section carrying a splice_insert command (ANSI/SCTE 35, section 9.7.3).
+/// Times are expressed in 90 kHz ticks, the duration marks the return to the network.
+pub fn splice_insert(event_id: u32, pts_time: u64, duration: Option<u64>) -> Vec<u8> {
+  let mut command = BitWriter::default();
+  command.write(event_id as u64, 32);
+  // splice_event_cancel_indicator, reserved
+  command.write(0b0111_1111, 8);
+  // out_of_network_indicator, program_splice_flag, duration_flag, splice_immediate_flag, reserved
+  command.write(1, 1);
+  command.write(1, 1);
+  command.write(duration.is_some() as u64, 1);
+  command.write(0, 1);
+  command.write(0b1111, 4);
+  // splice_time: time_specified_flag, reserved, pts_time
+  command.write(1, 1);
+  command.write(0b11_1111, 6);
+  command.write(pts_time, 33);
+  if let Some(duration) = duration {
+    // break_duration: auto_return, reserved, duration
+    command.write(1, 1);
+    command.write(0b11_1111, 6);
+    command.write(duration, 33);
+  }
+  // unique_program_id, avail_num, avails_expected
+  command.write(event_id as u64 & 0xffff, 16);
+  command.write(0, 8);
+  command.write(0, 8);
+  let command = command.bytes;
+
+  let mut body = BitWriter::default();
+  // protocol_version
+  body.write(0, 8);
+  // encrypted_packet, encryption_algorithm, pts_adjustment
+  body.write(0, 1);
+  body.write(0, 6);
+  body.write(0, 33);
+  // cw_index, tier
+  body.write(0, 8);
+  body.write(0xfff, 12);
+  // splice_command_length, splice_command_type
+  body.write(command.len() as u64, 12);
+  body.write(0x05, 8);
+  body.append(&command);
+  // descriptor_loop_length
+  body.write(0, 16);
+
+  let mut section = BitWriter::default();
+  section.write(0xfc, 8);
+  // section_syntax_indicator, private_indicator, sap_type (not specified)
+  section.write(0, 1);
+  section.write(0, 1);
+  section.write(0b11, 2);
+  section.write(body.bytes.len() as u64 + 4, 12);
+  section.append(&body.bytes);
+
+  let crc = crc32_mpeg2(&section.bytes);
+  section.write(crc as u64, 32);
+  section.bytes
+}
+
+fn crc32_mpeg2(data: &[u8]) -> u32 {
+  let mut crc = 0xffff_ffffu32;
+  for byte in data {
+    crc ^= (*byte as u32) << 24;
+    for _ in 0..8 {
+      crc = if crc & 0x8000_0000 != 0 {
+        (crc << 1) ^ 0x04c1_1db7
+      } else {
+        crc << 1
+      };
+    }
+  }
+  crc
+}
+
+#[derive(Default)]
+struct BitWriter {
+  bytes: Vec<u8>,
+  position: usize,
+}
+
+impl BitWriter {
+  fn write(&mut self, value: u64, count: usize) {
+    for index in (0..count).rev() {
+      if self.position == self.bytes.len() * 8 {
+        self.bytes.push(0);
+      }
+      let bit = ((value >> index) & 1) as u8;
+      *self.bytes.last_mut().unwrap() |= bit << (7 - self.position % 8);
+      self.position += 1;
+    }
+  }
+
+  /// Append byte-aligned data
+  fn append(&mut self, bytes: &[u8]) {
+    self.bytes.extend_from_slice(bytes);
+    self.position += bytes.len() * 8;
+  }
+}
+
+#[test]
+fn splice_insert_section() {
+  let section = splice_insert(1, 10 * TIMESCALE, Some(30 * TIMESCALE));
+
+  assert_eq!(section[0], 0xfc);
+  let section_length = ((section[1] as usize & 0x0f) << 8) | section[2] as usize;
+  assert_eq!(section_length + 3, section.len());
+  // splice_command_type
+  assert_eq!(section[13], 0x05);
+  // splice_event_id
+  assert_eq!(&section[14..18], &[0, 0, 0, 1]);
+  // out_of_network, program_splice and duration flags
+  assert_eq!(section[19] & 0xe0, 0xe0);
+  // a valid CRC leaves a null remainder
+  assert_eq!(crc32_mpeg2(&section), 0);
+
+  let immediate_return = splice_insert(2, 0, None);
+  assert_eq!(immediate_return[19] & 0xe0, 0xc0);
+  assert_eq!(immediate_return.len() + 5, section.len());
+  assert_eq!(crc32_mpeg2(&immediate_return), 0);
+}
@@ -0,0 +1,422 @@
+pub mod srt;
+pub mod ttml;
+pub mod webvtt;
+
+use crate::language;
+use crate::utils::strip_suffix;
+use std::fs;
+use std::path::Path;
+
+/// Timed text of a subtitle file, independently of its format
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subtitles {
+  /// Language declared by the file
+  pub language: Option<String>,
+  pub cues: Vec<Cue>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cue {
+  pub id: Option<String>,
+  /// Start time, in seconds
+  pub start: f64,
+  /// End time, in seconds
+  pub end: f64,
+  pub align: Option<TextAlign>,
+  pub area: Option<Area>,
+  /// Styled text of the cue, the lines being separated by line feeds
+  pub spans: Vec<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextAlign {
+  Start,
+  Center,
+  End,
+  Left,
+  Right,
+}
+
+/// Area of the video displaying the cue, in percents of the video size
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Area {
+  pub origin: (f64, f64),
+  pub extent: (f64, f64),
+  pub display_align: DisplayAlign,
+}
+
+/// Vertical alignment of the text in its area
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayAlign {
+  Before,
+  Center,
+  After,
+}
+
+impl Default for DisplayAlign {
+  fn default() -> Self {
+    DisplayAlign::Before
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Span {
+  pub text: String,
+  pub style: Style,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+  /// Text color, the default white being omitted
+  pub color: Option<String>,
+  pub background_color: Option<String>,
+  pub italic: bool,
+  pub bold: bool,
+  pub underline: bool,
+}
+
+/// Shift and rescale of the cue times, to the timeline of the media
+#[derive(Debug, Clone, PartialEq)]
+pub struct Retiming {
+  /// Offset added to the times, in seconds
+  pub offset: f64,
+  /// Ratio of the authoring frame rate to the media frame rate
+  pub scale: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+  SubRip,
+  Ttml,
+  WebVtt,
+}
+
+impl Format {
+  pub fn detect(contents: &str) -> Result<Self, String> {
+    let contents = contents.trim_start_matches('\u{feff}').trim_start();
+    if contents.starts_with("WEBVTT") {
+      Ok(Format::WebVtt)
+    } else if contents.starts_with('<') {
+      Ok(Format::Ttml)
+    } else if contents.lines().take(2).any(|line| line.contains("-->")) {
+      // cues optionally starting with their number
+      Ok(Format::SubRip)
+    } else {
+      Err("unsupported subtitle format".to_string())
+    }
+  }
+
+  pub fn from_extension(path: &str) -> Option<Self> {
+    match Path::new(path)
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .map(|extension| extension.to_lowercase())
+      .as_deref()
+    {
+      Some("ttml") | Some("dfxp") | Some("xml") => Some(Format::Ttml),
+      Some("vtt") => Some(Format::WebVtt),
+      Some("srt") => Some(Format::SubRip),
+      _ => None,
+    }
+  }
+}
+
+impl Subtitles {
+  /// Cues displayed during the time range, with their original timing
+  pub fn clip(&self, start: f64, end: f64) -> Subtitles {
+    Subtitles {
+      language: self.language.clone(),
+      cues: self
+        .cues
+        .iter()
+        .filter(|cue| cue.end > start && cue.start < end)
+        .cloned()
+        .collect(),
+    }
+  }
+
+  /// Cues on the retimed timeline, the ones ending before its start being removed
+  pub fn retime(&mut self, retiming: &Retiming) {
+    for cue in self.cues.iter_mut() {
+      cue.start = ((cue.start + retiming.offset) * retiming.scale).max(0.0);
+      cue.end = (cue.end + retiming.offset) * retiming.scale;
+    }
+    self.cues.retain(|cue| cue.end > 0.0);
+  }
+}
+
+impl Retiming {
+  /// Retiming of the options, the offset applying to the authoring timeline
+  pub fn new(
+    offset: Option<f64>,
+    frame_rate: Option<f64>,
+    target_frame_rate: Option<f64>,
+  ) -> Result<Option<Self>, String> {
+    let scale = match (frame_rate, target_frame_rate) {
+      (Some(frame_rate), Some(target_frame_rate)) => {
+        if frame_rate <= 0.0 || target_frame_rate <= 0.0 {
+          return Err("frame rates must be positive".to_string());
+        }
+        Some(frame_rate / target_frame_rate)
+      }
+      (None, None) => None,
+      _ => {
+        return Err("both the frame rate and the target frame rate are required".to_string());
+      }
+    };
+    if offset.is_none() && scale.is_none() {
+      return Ok(None);
+    }
+
+    Ok(Some(Retiming {
+      offset: offset.unwrap_or_default(),
+      scale: scale.unwrap_or(1.0),
+    }))
+  }
+}
+
+impl TextAlign {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "start" => Some(TextAlign::Start),
+      "center" | "middle" => Some(TextAlign::Center),
+      "end" => Some(TextAlign::End),
+      "left" => Some(TextAlign::Left),
+      "right" => Some(TextAlign::Right),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      TextAlign::Start => "start",
+      TextAlign::Center => "center",
+      TextAlign::End => "end",
+      TextAlign::Left => "left",
+      TextAlign::Right => "right",
+    }
+  }
+}
+
+/// Name of the color among the WebVTT default classes
+pub fn get_color_name(color: &str) -> Option<&'static str> {
+  let names = [
+    ("white", "#ffffff"),
+    ("lime", "#00ff00"),
+    ("cyan", "#00ffff"),
+    ("red", "#ff0000"),
+    ("yellow", "#ffff00"),
+    ("magenta", "#ff00ff"),
+    ("blue", "#0000ff"),
+    ("black", "#000000"),
+  ];
+  let color = color.trim().to_lowercase();
+  // opaque colors only
+  let color = strip_suffix(&color, "ff")
+    .filter(|_| color.len() == 9)
+    .unwrap_or(&color);
+
+  names
+    .iter()
+    .find(|(name, code)| color == *name || color == *code)
+    .map(|(name, _)| *name)
+}
+
+/// Number with at most two decimals, without trailing zeros
+fn format_number(value: f64) -> String {
+  let value = format!("{:.2}", value);
+  value
+    .trim_end_matches('0')
+    .trim_end_matches('.')
+    .to_string()
+}
+
+pub fn parse(contents: &str) -> Result<Subtitles, String> {
+  match Format::detect(contents)? {
+    Format::SubRip => srt::parse(contents),
+    Format::Ttml => ttml::parse(contents),
+    Format::WebVtt => webvtt::parse(contents),
+  }
+}
+
+/// Subtitles of the file, with warnings about what they do not describe
+pub fn parse_with_warnings(contents: &str) -> Result<(Subtitles, Vec<String>), String> {
+  match Format::detect(contents)? {
+    Format::WebVtt => webvtt::parse_with_warnings(contents),
+    _ => Ok((parse(contents)?, vec![])),
+  }
+}
+
+pub fn serialize(subtitles: &Subtitles, format: &Format) -> String {
+  match format {
+    Format::SubRip => srt::serialize(subtitles),
+    Format::Ttml => ttml::serialize(subtitles),
+    Format::WebVtt => webvtt::serialize(subtitles),
+  }
+}
+
+/// Language declared by a subtitle file
+pub fn get_declared_language(path: &str) -> Option<String> {
+  let contents = fs::read_to_string(path).ok()?;
+  parse(&contents).ok()?.language
+}
+
+/// Language given to the subtitles, or else the declared one, with a warning when they conflict
+pub fn resolve_language(
+  language: Option<&str>,
+  declared_language: Option<&str>,
+) -> Option<(String, Option<String>)> {
+  match (language, declared_language) {
+    (Some(language), Some(declared_language)) if !is_same_language(language, declared_language) => {
+      let warning = format!(
+        "the subtitle language {} conflicts with the language {} declared by the file",
+        language, declared_language
+      );
+      Some((language.to_string(), Some(warning)))
+    }
+    (Some(language), _) | (None, Some(language)) => Some((language.to_string(), None)),
+    (None, None) => None,
+  }
+}
+
+/// Languages with the same primary language subtag
+fn is_same_language(language: &str, other: &str) -> bool {
+  fn get_primary_language(language: &str) -> &str {
+    language.split(&['-', '_'][..]).next().unwrap_or_default()
+  }
+  language::is_same_language(get_primary_language(language), get_primary_language(other))
+}
+
+/// Convert a subtitle file to the format of the destination file extension, returning the warnings
+pub fn convert(
+  source_path: &str,
+  destination_path: &str,
+  retiming: &Option<Retiming>,
+) -> Result<Vec<String>, String> {
+  let format = Format::from_extension(destination_path)
+    .ok_or_else(|| format!("unsupported subtitle format of {}", destination_path))?;
+
+  let contents = fs::read_to_string(source_path).map_err(|e| format!("{}: {}", source_path, e))?;
+
+  // TTML documents are retimed in place, keeping what the cues do not describe
+  if format == Format::Ttml && Format::detect(&contents) == Ok(Format::Ttml) {
+    let contents = match retiming {
+      Some(retiming) => ttml::retime(&contents, retiming)
+        .map_err(|message| format!("{}: {}", source_path, message))?,
+      None => contents,
+    };
+    fs::write(destination_path, contents).map_err(|e| format!("{}: {}", destination_path, e))?;
+    return Ok(vec![]);
+  }
+
+  let (mut subtitles, warnings) =
+    parse_with_warnings(&contents).map_err(|message| format!("{}: {}", source_path, message))?;
+  if let Some(retiming) = retiming {
+    subtitles.retime(retiming);
+  }
+
+  fs::write(destination_path, serialize(&subtitles, &format))
+    .map_err(|e| format!("{}: {}", destination_path, e))?;
+  Ok(warnings)
+}
+
+#[test]
+fn convert_subtitle_files() {
+  convert(
+    "tests/sample_subtitle.ttml",
+    "tests/sample_subtitle_converted.vtt",
+    &None,
+  )
+  .unwrap();
+  let warnings = convert(
+    "tests/sample_subtitle_converted.vtt",
+    "tests/sample_subtitle_converted.ttml",
+    &None,
+  )
+  .unwrap();
+  assert!(warnings.is_empty());
+
+  // the cues are kept through the conversions
+  let source = ttml::parse(&fs::read_to_string("tests/sample_subtitle.ttml").unwrap()).unwrap();
+  let result =
+    ttml::parse(&fs::read_to_string("tests/sample_subtitle_converted.ttml").unwrap()).unwrap();
+  assert_eq!(result, source);
+
+  assert!(convert(
+    "tests/sample_subtitle.ttml",
+    "tests/sample_subtitle.txt",
+    &None
+  )
+  .is_err());
+  assert!(convert("tests/sample.ism", "tests/sample_subtitle_error.vtt", &None).is_err());
+}
+
+#[test]
+fn retime_cues() {
+  let contents = fs::read_to_string("tests/sample_subtitle.vtt").unwrap();
+  let mut subtitles = parse(&contents).unwrap();
+  let is_close = |value: f64, expected: f64| (value - expected).abs() < 1e-9;
+
+  // authored at 25 fps against a timeline starting at 00:00:04.000
+  let retiming = Retiming::new(Some(-4.0), Some(25.0), Some(24000.0 / 1001.0))
+    .unwrap()
+    .unwrap();
+  subtitles.retime(&retiming);
+
+  assert_eq!(subtitles.cues.len(), 1);
+  assert!(is_close(
+    subtitles.cues[0].start,
+    1.0 * 25.0 * 1001.0 / 24000.0
+  ));
+  assert!(is_close(
+    subtitles.cues[0].end,
+    6.0 * 25.0 * 1001.0 / 24000.0
+  ));
+
+  assert_eq!(Retiming::new(None, None, None), Ok(None));
+  assert_eq!(
+    Retiming::new(Some(-36000.0), None, None),
+    Ok(Some(Retiming {
+      offset: -36000.0,
+      scale: 1.0
+    }))
+  );
+  assert!(Retiming::new(None, Some(25.0), None).is_err());
+  assert!(Retiming::new(None, Some(25.0), Some(0.0)).is_err());
+}
+
+#[test]
+fn declared_languages() {
+  assert_eq!(
+    get_declared_language("tests/sample_subtitle_en.ttml"),
+    Some("en".to_string())
+  );
+  assert_eq!(get_declared_language("tests/sample_subtitle.ttml"), None);
+  assert_eq!(get_declared_language("tests/missing.vtt"), None);
+
+  assert_eq!(
+    resolve_language(None, Some("fr")),
+    Some(("fr".to_string(), None))
+  );
+  assert_eq!(
+    resolve_language(Some("fr-CA"), Some("fr")),
+    Some(("fr-CA".to_string(), None))
+  );
+  assert_eq!(
+    resolve_language(Some("fre"), Some("fr")),
+    Some(("fre".to_string(), None))
+  );
+  let (language, warning) = resolve_language(Some("de"), Some("fr")).unwrap();
+  assert_eq!(language, "de");
+  assert!(warning.is_some());
+  assert_eq!(resolve_language(None, None), None);
+}
+
+#[test]
+fn color_names() {
+  assert_eq!(get_color_name("Yellow"), Some("yellow"));
+  assert_eq!(get_color_name("#00FFFF"), Some("cyan"));
+  assert_eq!(get_color_name("#ff0000ff"), Some("red"));
+  assert_eq!(get_color_name("#ff000080"), None);
+  assert_eq!(get_color_name("rgb(255, 0, 0)"), None);
+}
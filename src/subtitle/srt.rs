@@ -0,0 +1,238 @@
+use crate::subtitle::{get_color_name, Cue, Span, Style, Subtitles};
+use crate::utils::{split_once, strip_prefix};
+
+pub fn parse(contents: &str) -> Result<Subtitles, String> {
+  let contents = contents
+    .trim_start_matches('\u{feff}')
+    .replace("\r\n", "\n");
+
+  let mut subtitles = Subtitles::default();
+  for block in contents
+    .split("\n\n")
+    .map(|block| block.trim_matches('\n'))
+    .filter(|block| !block.trim().is_empty())
+  {
+    let mut lines: Vec<&str> = block.lines().collect();
+    let timing_index = lines
+      .iter()
+      .position(|line| line.contains("-->"))
+      .filter(|timing_index| *timing_index < 2)
+      .ok_or_else(|| format!("missing cue timing: {}", lines[0]))?;
+    let text = lines.split_off(timing_index + 1).join("\n");
+
+    let (start, end) = split_once(lines[timing_index], "-->").unwrap_or_default();
+    // coordinates may follow the end time
+    let end = end.split_whitespace().next().unwrap_or_default();
+
+    subtitles.cues.push(Cue {
+      id: lines
+        .first()
+        .filter(|_| timing_index == 1)
+        .map(|id| id.trim().to_string()),
+      start: parse_timestamp(start)?,
+      end: parse_timestamp(end)?,
+      spans: parse_text(&text),
+      ..Default::default()
+    });
+  }
+  Ok(subtitles)
+}
+
+pub fn serialize(subtitles: &Subtitles) -> String {
+  let mut blocks = vec![];
+  // cues are numbered from 1
+  for (index, cue) in subtitles.cues.iter().enumerate() {
+    let text: String = cue.spans.iter().map(serialize_span).collect();
+    let lines: Vec<&str> = text.lines().filter(|line| !line.is_empty()).collect();
+    blocks.push(format!(
+      "{}\n{} --> {}\n{}\n",
+      index + 1,
+      format_timestamp(cue.start),
+      format_timestamp(cue.end),
+      lines.join("\n")
+    ));
+  }
+  blocks.join("\n")
+}
+
+/// Styled spans of the cue text, from its b, i, u and font tags
+fn parse_text(text: &str) -> Vec<Span> {
+  let mut spans: Vec<Span> = vec![];
+  let mut tags: Vec<(String, Style)> = vec![];
+  let mut remaining = text;
+
+  while !remaining.is_empty() {
+    let style = tags
+      .last()
+      .map(|(_, style)| style.clone())
+      .unwrap_or_default();
+    // positioning overrides (e.g. {\an8}) are not supported
+    let (text, tag) = match remaining.find(&['<', '{'][..]) {
+      Some(index) => {
+        let closing = if remaining[index..].starts_with('<') {
+          '>'
+        } else {
+          '}'
+        };
+        let end = remaining[index..]
+          .find(closing)
+          .map(|end| index + end)
+          .unwrap_or(remaining.len());
+        let tag = remaining.get(index..=end).unwrap_or_default();
+        let text = &remaining[..index];
+        remaining = remaining.get(end + 1..).unwrap_or_default();
+        (text, Some(tag))
+      }
+      None => {
+        let text = remaining;
+        remaining = "";
+        (text, None)
+      }
+    };
+
+    if !text.is_empty() {
+      match spans.last_mut() {
+        Some(span) if span.style == style => span.text.push_str(text),
+        _ => spans.push(Span {
+          text: text.to_string(),
+          style: style.clone(),
+        }),
+      }
+    }
+
+    let tag = match tag.and_then(|tag| strip_prefix(tag, "<")) {
+      Some(tag) => tag.trim_end_matches('>'),
+      None => continue,
+    };
+    if let Some(name) = strip_prefix(tag, "/") {
+      let name = name.trim().to_lowercase();
+      if let Some(index) = tags.iter().rposition(|(tag_name, _)| *tag_name == name) {
+        tags.truncate(index);
+      }
+      continue;
+    }
+
+    let name = tag
+      .split_whitespace()
+      .next()
+      .unwrap_or_default()
+      .to_lowercase();
+    let mut style = style;
+    match name.as_str() {
+      "b" => style.bold = true,
+      "i" => style.italic = true,
+      "u" => style.underline = true,
+      "font" => {
+        if let Some(color) = get_color(tag) {
+          style.color = Some(color).filter(|color| get_color_name(color) != Some("white"));
+        }
+      }
+      _ => {}
+    }
+    tags.push((name, style));
+  }
+  spans
+}
+
+/// Color attribute of a font tag
+fn get_color(tag: &str) -> Option<String> {
+  let (_, value) = split_once(tag, "color=")?;
+  let value = value.trim_start();
+  let color = match strip_prefix(value, "\"") {
+    Some(value) => value.split('"').next()?,
+    None => value.split_whitespace().next()?,
+  };
+  Some(color.to_string())
+}
+
+fn serialize_span(span: &Span) -> String {
+  let style = &span.style;
+  let mut text = span.text.clone();
+
+  if style.underline {
+    text = format!("<u>{}</u>", text);
+  }
+  if style.italic {
+    text = format!("<i>{}</i>", text);
+  }
+  if style.bold {
+    text = format!("<b>{}</b>", text);
+  }
+  if let Some(color) = &style.color {
+    text = format!("<font color=\"{}\">{}</font>", color, text);
+  }
+  text
+}
+
+/// Timestamp as hh:mm:ss,ttt
+fn parse_timestamp(timestamp: &str) -> Result<f64, String> {
+  let timestamp = timestamp.trim();
+  let error = || format!("invalid timestamp: {}", timestamp);
+
+  let parts: Vec<&str> = timestamp.split(':').collect();
+  if parts.len() != 3 {
+    return Err(error());
+  }
+  let mut seconds = 0.0;
+  for part in parts {
+    let value: f64 = part.replace(',', ".").parse().map_err(|_| error())?;
+    seconds = seconds * 60.0 + value;
+  }
+  Ok(seconds)
+}
+
+fn format_timestamp(seconds: f64) -> String {
+  let milliseconds = (seconds * 1000.0).round() as u64;
+  format!(
+    "{:02}:{:02}:{:02},{:03}",
+    milliseconds / 3_600_000,
+    milliseconds / 60_000 % 60,
+    milliseconds / 1000 % 60,
+    milliseconds % 1000
+  )
+}
+
+#[test]
+fn parse_subrip_cues() {
+  let contents = std::fs::read_to_string("tests/sample_subtitle.srt").unwrap();
+  let subtitles = parse(&contents).unwrap();
+
+  assert_eq!(subtitles.cues.len(), 3);
+  assert_eq!(
+    subtitles.cues[1],
+    Cue {
+      id: Some("2".to_string()),
+      start: 5.0,
+      end: 10.0,
+      spans: vec![Span {
+        text: "that the image formed on\nthe Retina should be inverted?".to_string(),
+        style: Style::default(),
+      }],
+      ..Default::default()
+    }
+  );
+  assert_eq!(
+    subtitles.cues[2].spans,
+    vec![
+      Span {
+        text: "It is ".to_string(),
+        style: Style {
+          color: Some("#ffff00".to_string()),
+          ..Default::default()
+        },
+      },
+      Span {
+        text: "puzzling".to_string(),
+        style: Style {
+          color: Some("#ffff00".to_string()),
+          italic: true,
+          ..Default::default()
+        },
+      },
+    ]
+  );
+  assert_eq!(serialize(&subtitles), contents);
+
+  assert!(parse("1\n00:00:01.000\nHello\n").is_err());
+  assert!(parse("1\n00:01,000 --> 00:02,000\nHello\n").is_err());
+}
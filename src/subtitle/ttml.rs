@@ -0,0 +1,1005 @@
+use crate::subtitle::{
+  format_number, get_color_name, Area, Cue, DisplayAlign, Retiming, Span, Style, Subtitles,
+  TextAlign,
+};
+use crate::utils::{split_once, strip_suffix};
+use crate::xml_tree::{self, Document};
+use std::collections::HashMap;
+use xml::reader::{EventReader, XmlEvent};
+
+const TTML_NAMESPACE: &str = "http://www.w3.org/ns/ttml";
+const STYLING_NAMESPACE: &str = "http://www.w3.org/ns/ttml#styling";
+const SMPTE_NAMESPACE: &str = "http://www.smpte-ra.org/schemas/2052-1/2010/smpte-tt";
+/// Prefix of the IMSC1 styling, parameter and metadata extension namespaces
+const IMSC_NAMESPACE_PREFIX: &str = "http://www.w3.org/ns/ttml/profile/imsc1#";
+/// Stands for the line breaks while the whitespaces of the text are collapsed
+const LINE_BREAK: char = '\u{e000}';
+
+/// Rates of the frame and tick based time expressions
+struct TimeBase {
+  frame_rate: f64,
+  tick_rate: f64,
+}
+
+impl Default for TimeBase {
+  fn default() -> Self {
+    TimeBase {
+      frame_rate: 30.0,
+      tick_rate: 1.0,
+    }
+  }
+}
+
+/// Styling attributes, by local name
+type StyleAttributes = HashMap<String, String>;
+
+/// Style or region definition, with the styles it references
+#[derive(Default)]
+struct StyleDefinition {
+  references: Vec<String>,
+  attributes: StyleAttributes,
+}
+
+/// Ancestor of the current element, with its timing and inherited style
+struct Element {
+  name: String,
+  start: f64,
+  end: Option<f64>,
+  region: Option<String>,
+  attributes: StyleAttributes,
+}
+
+/// Paragraph being read, with its timing and text
+struct Paragraph {
+  cue: Cue,
+  end: Option<f64>,
+}
+
+/// Profile of a TTML document supported by the players
+#[derive(Debug, PartialEq)]
+pub enum Profile {
+  ImscText,
+  ImscImage,
+  EbuTtD,
+}
+
+impl Profile {
+  fn from_designator(designator: &str) -> Option<Self> {
+    match designator.trim() {
+      "http://www.w3.org/ns/ttml/profile/imsc1/text"
+      | "http://www.w3.org/ns/ttml/profile/imsc1.1/text" => Some(Profile::ImscText),
+      "http://www.w3.org/ns/ttml/profile/imsc1/image"
+      | "http://www.w3.org/ns/ttml/profile/imsc1.1/image" => Some(Profile::ImscImage),
+      "urn:ebu:tt:distribution:2014-01" | "urn:ebu:tt:distribution:2018-04" => {
+        Some(Profile::EbuTtD)
+      }
+      _ => None,
+    }
+  }
+
+  /// Codecs of the sidecar file, EBU-TT-D documents conforming to the IMSC1 text profile
+  pub fn get_codecs(&self) -> &'static str {
+    match self {
+      Profile::ImscText | Profile::EbuTtD => "im1t",
+      Profile::ImscImage => "im1i",
+    }
+  }
+}
+
+pub fn parse(contents: &str) -> Result<Subtitles, String> {
+  let mut subtitles = Subtitles::default();
+  let mut time_base = TimeBase::default();
+  let mut root_extent = None;
+  let mut styles: HashMap<String, StyleDefinition> = HashMap::new();
+  let mut regions: HashMap<String, StyleDefinition> = HashMap::new();
+  // the children timing is relative to their parent
+  let mut elements: Vec<Element> = vec![];
+  let mut paragraph: Option<Paragraph> = None;
+
+  for event in EventReader::from_str(contents) {
+    match event.map_err(|e| format!("invalid TTML: {}", e))? {
+      XmlEvent::StartElement {
+        name, attributes, ..
+      } => {
+        let get_attribute = |local_name: &str| {
+          attributes
+            .iter()
+            .find(|attribute| {
+              attribute.name.local_name == local_name
+                && attribute.name.namespace.as_deref() != Some(STYLING_NAMESPACE)
+            })
+            .map(|attribute| attribute.value.as_str())
+        };
+        let definition = StyleDefinition {
+          references: get_attribute("style")
+            .map(|style| style.split_whitespace().map(|id| id.to_string()).collect())
+            .unwrap_or_default(),
+          attributes: attributes
+            .iter()
+            .filter(|attribute| attribute.name.namespace.as_deref() == Some(STYLING_NAMESPACE))
+            .map(|attribute| (attribute.name.local_name.clone(), attribute.value.clone()))
+            .collect(),
+        };
+
+        if elements.is_empty() {
+          if name.local_name != "tt" {
+            return Err(format!("unexpected root element: {}", name.local_name));
+          }
+          time_base = get_time_base(
+            get_attribute("frameRate"),
+            get_attribute("frameRateMultiplier"),
+            get_attribute("tickRate"),
+          )?;
+          subtitles.language = get_attribute("lang")
+            .filter(|language| !language.is_empty())
+            .map(|language| language.to_string());
+          root_extent = definition
+            .attributes
+            .get("extent")
+            .and_then(|extent| parse_pixels(extent));
+        }
+
+        let parent = elements.last();
+        let (parent_start, parent_end) = parent
+          .map(|parent| (parent.start, parent.end))
+          .unwrap_or_default();
+        let start = match get_attribute("begin") {
+          Some(begin) => parent_start + parse_time(begin, &time_base)?,
+          None => parent_start,
+        };
+        let end = match (get_attribute("end"), get_attribute("dur")) {
+          (Some(end), _) => Some(parent_start + parse_time(end, &time_base)?),
+          (None, Some(duration)) => Some(start + parse_time(duration, &time_base)?),
+          (None, None) => parent_end,
+        };
+
+        // the background color is only inherited inside the paragraphs
+        let mut inherited = parent
+          .map(|parent| parent.attributes.clone())
+          .unwrap_or_default();
+        if paragraph.is_none() {
+          inherited.remove("backgroundColor");
+        }
+        let region = get_attribute("region").map(|region| region.to_string());
+        if let Some(definition) = region.as_ref().and_then(|region| regions.get(region)) {
+          let mut attributes = resolve_style(definition, &styles, 0);
+          attributes.remove("backgroundColor");
+          inherited.extend(attributes);
+        }
+        inherited.extend(resolve_style(&definition, &styles, 0));
+        let region = region.or_else(|| parent.and_then(|parent| parent.region.clone()));
+
+        match (
+          parent.map(|parent| parent.name.as_str()),
+          name.local_name.as_str(),
+        ) {
+          (Some("styling"), "style") => {
+            if let Some(id) = get_attribute("id") {
+              styles.insert(id.to_string(), definition);
+            }
+          }
+          (_, "region") => {
+            if let Some(id) = get_attribute("id") {
+              regions.insert(id.to_string(), definition);
+            }
+          }
+          // styles defined inline in a region
+          (Some("region"), "style") => {
+            let region = parent
+              .and_then(|parent| parent.region.as_ref())
+              .and_then(|region| regions.get_mut(region));
+            if let Some(region) = region {
+              region.attributes.extend(definition.attributes);
+            }
+          }
+          (_, "p") => {
+            paragraph = Some(Paragraph {
+              cue: Cue {
+                id: get_attribute("id").map(|id| id.to_string()),
+                start,
+                align: inherited
+                  .get("textAlign")
+                  .and_then(|text_align| TextAlign::parse(text_align)),
+                area: region
+                  .as_ref()
+                  .and_then(|region| regions.get(region))
+                  .and_then(|region| get_area(&resolve_style(region, &styles, 0), root_extent)),
+                ..Default::default()
+              },
+              end,
+            })
+          }
+          (_, "br") => {
+            if let Some(paragraph) = &mut paragraph {
+              push_text(
+                &mut paragraph.cue.spans,
+                &LINE_BREAK.to_string(),
+                get_style(&inherited),
+              );
+            }
+          }
+          _ => {}
+        }
+
+        // a region element is the region of its inline styles
+        let region = if name.local_name == "region" {
+          get_attribute("id").map(|id| id.to_string())
+        } else {
+          region
+        };
+        elements.push(Element {
+          name: name.local_name,
+          start,
+          end,
+          region,
+          attributes: inherited,
+        });
+      }
+      XmlEvent::Characters(text) | XmlEvent::Whitespace(text) | XmlEvent::CData(text) => {
+        if let (Some(paragraph), Some(element)) = (&mut paragraph, elements.last()) {
+          push_text(
+            &mut paragraph.cue.spans,
+            &text,
+            get_style(&element.attributes),
+          );
+        }
+      }
+      XmlEvent::EndElement { name } => {
+        elements.pop();
+        if name.local_name != "p" {
+          continue;
+        }
+        let mut paragraph = match paragraph.take() {
+          Some(paragraph) => paragraph,
+          None => continue,
+        };
+        // the end is given by the paragraph or one of its ancestors
+        paragraph.cue.end = paragraph.end.ok_or_else(|| {
+          format!(
+            "missing end time of the paragraph at {}s",
+            paragraph.cue.start
+          )
+        })?;
+        paragraph.cue.spans = collapse_whitespaces(paragraph.cue.spans);
+
+        subtitles.cues.push(paragraph.cue);
+      }
+      _ => {}
+    }
+  }
+
+  subtitles.cues.sort_by(|a, b| {
+    a.start
+      .partial_cmp(&b.start)
+      .unwrap_or(std::cmp::Ordering::Equal)
+  });
+  Ok(subtitles)
+}
+
+pub fn serialize(subtitles: &Subtitles) -> String {
+  // one region per distinct area
+  let mut areas: Vec<&Area> = vec![];
+  for area in subtitles.cues.iter().filter_map(|cue| cue.area.as_ref()) {
+    if !areas.contains(&area) {
+      areas.push(area);
+    }
+  }
+
+  let mut contents = format!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling" xml:lang="{}">
+"#,
+    escape(subtitles.language.as_deref().unwrap_or_default())
+  );
+
+  if !areas.is_empty() {
+    contents.push_str("  <head>\n    <layout>\n");
+    for (index, area) in areas.iter().enumerate() {
+      let display_align = match area.display_align {
+        DisplayAlign::Before => "before",
+        DisplayAlign::Center => "center",
+        DisplayAlign::After => "after",
+      };
+      contents.push_str(&format!(
+        "      <region xml:id=\"r{}\" tts:origin=\"{}% {}%\" tts:extent=\"{}% {}%\" tts:displayAlign=\"{}\"/>\n",
+        index + 1,
+        format_number(area.origin.0),
+        format_number(area.origin.1),
+        format_number(area.extent.0),
+        format_number(area.extent.1),
+        display_align
+      ));
+    }
+    contents.push_str("    </layout>\n  </head>\n");
+  }
+
+  contents.push_str("  <body>\n    <div>\n");
+  for cue in &subtitles.cues {
+    contents.push_str("      <p");
+    if let Some(id) = &cue.id {
+      // identifiers are XML names, which cannot start with a digit
+      let prefix = if id.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        ""
+      } else {
+        "cue"
+      };
+      contents.push_str(&format!(" xml:id=\"{}{}\"", prefix, escape(id)));
+    }
+    contents.push_str(&format!(
+      " begin=\"{}\" end=\"{}\"",
+      format_time(cue.start),
+      format_time(cue.end)
+    ));
+    if let Some(index) = cue
+      .area
+      .as_ref()
+      .and_then(|area| areas.iter().position(|item| *item == area))
+    {
+      contents.push_str(&format!(" region=\"r{}\"", index + 1));
+    }
+    if let Some(align) = &cue.align {
+      contents.push_str(&format!(" tts:textAlign=\"{}\"", align.as_str()));
+    }
+    contents.push('>');
+
+    for span in &cue.spans {
+      let text = span
+        .text
+        .split('\n')
+        .map(escape)
+        .collect::<Vec<String>>()
+        .join("<br/>");
+      let attributes = get_style_attributes(&span.style);
+      if attributes.is_empty() {
+        contents.push_str(&text);
+      } else {
+        contents.push_str(&format!("<span{}>{}</span>", attributes, text));
+      }
+    }
+    contents.push_str("</p>\n");
+  }
+  contents.push_str("    </div>\n  </body>\n</tt>\n");
+  contents
+}
+
+/// Document with its times shifted and rescaled, the rest being kept as written
+pub fn retime(contents: &str, retiming: &Retiming) -> Result<String, String> {
+  let mut document =
+    Document::parse(contents).map_err(|message| format!("invalid TTML: {}", message))?;
+  let root = document
+    .root_mut()
+    .filter(|root| root.local_name() == "tt")
+    .ok_or_else(|| "the root element must be tt".to_string())?;
+  let time_base = get_time_base(
+    root.get_attribute_by_local_name("frameRate").as_deref(),
+    root
+      .get_attribute_by_local_name("frameRateMultiplier")
+      .as_deref(),
+    root.get_attribute_by_local_name("tickRate").as_deref(),
+  )?;
+
+  retime_children(root, retiming.offset * retiming.scale, retiming, &time_base)?;
+  Ok(document.to_string())
+}
+
+/// Retime the child elements, whose times relative to the parent begin are shifted by the delta
+fn retime_children(
+  element: &mut xml_tree::Element,
+  delta: f64,
+  retiming: &Retiming,
+  time_base: &TimeBase,
+) -> Result<(), String> {
+  let mut is_kept = vec![];
+  for child in element.elements_mut() {
+    is_kept.push(retime_element(child, delta, retiming, time_base)?);
+  }
+  let mut is_kept = is_kept.into_iter();
+  element.retain_elements(|_| is_kept.next().unwrap_or(true));
+  Ok(())
+}
+
+/// Retime the element and its children, false when it ends before the timeline starts
+fn retime_element(
+  element: &mut xml_tree::Element,
+  delta: f64,
+  retiming: &Retiming,
+  time_base: &TimeBase,
+) -> Result<bool, String> {
+  if element.local_name() == "metadata" {
+    return Ok(true);
+  }
+  let get_time = |name: &str| {
+    element
+      .get_attribute(name)
+      .map(|value| parse_time(&value, time_base))
+      .transpose()
+  };
+  let (begin, end, duration) = (get_time("begin")?, get_time("end")?, get_time("dur")?);
+
+  let mut children_delta = delta;
+  if begin.is_some() || end.is_some() || duration.is_some() {
+    // an element starting before the timeline is cut, its children being shifted back
+    let start = begin.unwrap_or_default() * retiming.scale + delta;
+    children_delta = start.min(0.0);
+    if begin.is_some() || start > 0.0 {
+      element.set_attribute("begin", &format_time(start.max(0.0)));
+    }
+    if let Some(end) = end {
+      let end = end * retiming.scale + delta;
+      if end <= 0.0 {
+        return Ok(false);
+      }
+      element.set_attribute("end", &format_time(end));
+    }
+    if let Some(duration) = duration {
+      let duration = duration * retiming.scale + start.min(0.0);
+      if duration <= 0.0 {
+        return Ok(false);
+      }
+      element.set_attribute("dur", &format_time(duration));
+    }
+  }
+
+  retime_children(element, children_delta, retiming, time_base)?;
+  Ok(true)
+}
+
+/// Profile declared by the document, or marked by its IMSC1 extensions, None when not declared
+pub fn detect_profile(contents: &str) -> Result<Option<Profile>, String> {
+  let mut designators = vec![];
+  let mut has_images = false;
+  let mut has_imsc_extensions = false;
+  let mut is_root = true;
+  let mut is_standard = false;
+
+  for event in EventReader::from_str(contents) {
+    match event.map_err(|e| format!("invalid TTML: {}", e))? {
+      XmlEvent::StartElement {
+        name, attributes, ..
+      } => {
+        if is_root {
+          if name.local_name != "tt" || name.namespace.as_deref() != Some(TTML_NAMESPACE) {
+            return Err(format!(
+              "the root element must be tt in the {} namespace",
+              TTML_NAMESPACE
+            ));
+          }
+          is_root = false;
+        }
+
+        let is_imsc_namespace = |namespace: &Option<String>| {
+          namespace.as_deref().map_or(false, |namespace| {
+            namespace.starts_with(IMSC_NAMESPACE_PREFIX)
+          })
+        };
+        has_imsc_extensions |= is_imsc_namespace(&name.namespace)
+          || attributes
+            .iter()
+            .any(|attribute| is_imsc_namespace(&attribute.name.namespace));
+
+        for attribute in &attributes {
+          match attribute.name.local_name.as_str() {
+            "profile" | "contentProfiles" if name.local_name == "tt" => designators.extend(
+              attribute
+                .value
+                .split_whitespace()
+                .map(|designator| designator.to_string()),
+            ),
+            "use" if name.local_name == "profile" => designators.push(attribute.value.clone()),
+            "backgroundImage" if attribute.name.namespace.as_deref() == Some(SMPTE_NAMESPACE) => {
+              has_images = true
+            }
+            _ => {}
+          }
+        }
+        has_images |= name.local_name == "image";
+        is_standard = name.local_name == "conformsToStandard";
+      }
+      XmlEvent::Characters(text) if is_standard => designators.push(text),
+      XmlEvent::EndElement { .. } => is_standard = false,
+      _ => {}
+    }
+  }
+
+  if designators.is_empty() {
+    return Ok(match (has_imsc_extensions, has_images) {
+      (true, true) => Some(Profile::ImscImage),
+      (true, false) => Some(Profile::ImscText),
+      (false, _) => None,
+    });
+  }
+  designators
+    .iter()
+    .find_map(|designator| Profile::from_designator(designator))
+    .map(Some)
+    .ok_or_else(|| {
+      format!(
+        "unsupported TTML profile: {}, expecting IMSC1 or EBU-TT-D",
+        designators.join(", ")
+      )
+    })
+}
+
+/// Attributes of a style, overriding the ones of the styles it references
+fn resolve_style(
+  definition: &StyleDefinition,
+  styles: &HashMap<String, StyleDefinition>,
+  depth: usize,
+) -> StyleAttributes {
+  let mut attributes = StyleAttributes::new();
+  // loops are not allowed between the references, but are not followed either
+  if depth < 16 {
+    for reference in &definition.references {
+      if let Some(style) = styles.get(reference) {
+        attributes.extend(resolve_style(style, styles, depth + 1));
+      }
+    }
+  }
+  attributes.extend(definition.attributes.clone());
+  attributes
+}
+
+fn get_style(attributes: &StyleAttributes) -> Style {
+  let get_attribute = |name: &str| attributes.get(name).map(|value| value.as_str());
+
+  Style {
+    color: get_attribute("color")
+      .filter(|color| get_color_name(color) != Some("white"))
+      .map(|color| color.to_string()),
+    background_color: get_attribute("backgroundColor")
+      .filter(|color| *color != "transparent")
+      .map(|color| color.to_string()),
+    italic: matches!(get_attribute("fontStyle"), Some("italic") | Some("oblique")),
+    bold: get_attribute("fontWeight") == Some("bold"),
+    underline: get_attribute("textDecoration")
+      .map_or(false, |decoration| decoration.contains("underline")),
+  }
+}
+
+fn get_style_attributes(style: &Style) -> String {
+  let mut attributes = String::new();
+  if let Some(color) = &style.color {
+    attributes.push_str(&format!(" tts:color=\"{}\"", escape(color)));
+  }
+  if let Some(background_color) = &style.background_color {
+    attributes.push_str(&format!(
+      " tts:backgroundColor=\"{}\"",
+      escape(background_color)
+    ));
+  }
+  if style.italic {
+    attributes.push_str(" tts:fontStyle=\"italic\"");
+  }
+  if style.bold {
+    attributes.push_str(" tts:fontWeight=\"bold\"");
+  }
+  if style.underline {
+    attributes.push_str(" tts:textDecoration=\"underline\"");
+  }
+  attributes
+}
+
+/// Area of a region, pixel lengths requiring the extent of the root container
+fn get_area(attributes: &StyleAttributes, root_extent: Option<(f64, f64)>) -> Option<Area> {
+  let origin = match attributes.get("origin") {
+    Some(origin) => parse_lengths(origin, root_extent)?,
+    None => (0.0, 0.0),
+  };
+  let extent = match attributes.get("extent") {
+    Some(extent) => parse_lengths(extent, root_extent)?,
+    None => (100.0, 100.0),
+  };
+  let display_align = match attributes.get("displayAlign").map(|value| value.as_str()) {
+    Some("center") => DisplayAlign::Center,
+    Some("after") => DisplayAlign::After,
+    _ => DisplayAlign::Before,
+  };
+
+  Some(Area {
+    origin,
+    extent,
+    display_align,
+  })
+}
+
+/// Horizontal and vertical lengths, in percents of the root container
+fn parse_lengths(value: &str, root_extent: Option<(f64, f64)>) -> Option<(f64, f64)> {
+  let parse_length = |length: &str, root_length: Option<f64>| {
+    if let Some(percents) = strip_suffix(length, "%") {
+      percents.parse::<f64>().ok()
+    } else {
+      let pixels = strip_suffix(length, "px")?.parse::<f64>().ok()?;
+      Some(pixels * 100.0 / root_length?)
+    }
+  };
+
+  match value.split_whitespace().collect::<Vec<&str>>()[..] {
+    [horizontal, vertical] => Some((
+      parse_length(horizontal, root_extent.map(|extent| extent.0))?,
+      parse_length(vertical, root_extent.map(|extent| extent.1))?,
+    )),
+    _ => None,
+  }
+}
+
+/// Horizontal and vertical lengths in pixels
+fn parse_pixels(value: &str) -> Option<(f64, f64)> {
+  match value.split_whitespace().collect::<Vec<&str>>()[..] {
+    [horizontal, vertical] => Some((
+      strip_suffix(horizontal, "px")?.parse().ok()?,
+      strip_suffix(vertical, "px")?.parse().ok()?,
+    )),
+    _ => None,
+  }
+}
+
+fn get_time_base(
+  frame_rate: Option<&str>,
+  frame_rate_multiplier: Option<&str>,
+  tick_rate: Option<&str>,
+) -> Result<TimeBase, String> {
+  let mut time_base = TimeBase::default();
+  if let Some(frame_rate) = frame_rate {
+    time_base.frame_rate = parse_number(frame_rate)?;
+  }
+  if let Some(multiplier) = frame_rate_multiplier {
+    let (numerator, denominator) = split_once(multiplier, " ").unwrap_or((multiplier, "1"));
+    time_base.frame_rate *= parse_number(numerator)? / parse_number(denominator)?;
+  }
+  // the tick rate defaults to the frame rate when it is specified
+  time_base.tick_rate = match tick_rate {
+    Some(tick_rate) => parse_number(tick_rate)?,
+    None if frame_rate.is_some() => time_base.frame_rate,
+    None => 1.0,
+  };
+  Ok(time_base)
+}
+
+/// Clock time (hh:mm:ss.fraction or hh:mm:ss:frames) or offset time (e.g. 2.5s, 40f) in seconds
+fn parse_time(expression: &str, time_base: &TimeBase) -> Result<f64, String> {
+  let expression = expression.trim();
+
+  if expression.contains(':') {
+    let parts: Vec<&str> = expression.split(':').collect();
+    if !(3..=4).contains(&parts.len()) {
+      return Err(format!("invalid time expression: {}", expression));
+    }
+    let mut seconds =
+      parse_number(parts[0])? * 3600.0 + parse_number(parts[1])? * 60.0 + parse_number(parts[2])?;
+    if let Some(frames) = parts.get(3) {
+      seconds += parse_number(frames)? / time_base.frame_rate;
+    }
+    return Ok(seconds);
+  }
+
+  let metrics = [
+    ("ms", 0.001),
+    ("h", 3600.0),
+    ("m", 60.0),
+    ("s", 1.0),
+    ("f", 1.0 / time_base.frame_rate),
+    ("t", 1.0 / time_base.tick_rate),
+  ];
+  for (metric, scale) in metrics.iter() {
+    if let Some(value) = strip_suffix(expression, metric) {
+      return Ok(parse_number(value)? * scale);
+    }
+  }
+  Err(format!("invalid time expression: {}", expression))
+}
+
+/// Clock time as hh:mm:ss.fraction
+fn format_time(seconds: f64) -> String {
+  let milliseconds = (seconds * 1000.0).round() as u64;
+  format!(
+    "{:02}:{:02}:{:02}.{:03}",
+    milliseconds / 3_600_000,
+    milliseconds / 60_000 % 60,
+    milliseconds / 1000 % 60,
+    milliseconds % 1000
+  )
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+  value
+    .trim()
+    .parse()
+    .map_err(|_| format!("invalid number: {}", value))
+}
+
+/// Append the text, to the last span when it has the same style
+fn push_text(spans: &mut Vec<Span>, text: &str, style: Style) {
+  match spans.last_mut() {
+    Some(span) if span.style == style => span.text.push_str(text),
+    _ => spans.push(Span {
+      text: text.to_string(),
+      style,
+    }),
+  }
+}
+
+/// Text lines with their whitespaces collapsed, as with the default xml:space
+fn collapse_whitespaces(spans: Vec<Span>) -> Vec<Span> {
+  let mut result: Vec<Span> = vec![];
+  let mut is_line_start = true;
+  let mut is_after_space = false;
+
+  for span in spans {
+    let mut text = String::new();
+    for character in span.text.chars() {
+      if character == LINE_BREAK {
+        // the spaces before a line break are dropped too
+        if text.ends_with(' ') {
+          text.pop();
+        } else if text.is_empty() && is_after_space {
+          if let Some(previous) = result.last_mut() {
+            previous.text.pop();
+          }
+        }
+        text.push('\n');
+        is_line_start = true;
+        is_after_space = false;
+      } else if character.is_whitespace() {
+        if !is_line_start && !is_after_space {
+          text.push(' ');
+          is_after_space = true;
+        }
+      } else {
+        text.push(character);
+        is_line_start = false;
+        is_after_space = false;
+      }
+    }
+    push_text(&mut result, &text, span.style);
+  }
+
+  if is_after_space {
+    if let Some(last) = result.last_mut() {
+      last.text.pop();
+    }
+  }
+  result.retain(|span| !span.text.is_empty());
+  result
+}
+
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+#[test]
+fn parse_ttml_cues() {
+  let contents = std::fs::read_to_string("tests/sample_subtitle.ttml").unwrap();
+  let subtitles = parse(&contents).unwrap();
+
+  assert_eq!(subtitles.cues.len(), 5);
+  assert_eq!(
+    subtitles.cues[0],
+    Cue {
+      id: Some("subtitle1".to_string()),
+      start: 0.76,
+      end: 3.45,
+      align: Some(TextAlign::Center),
+      area: None,
+      spans: vec![Span {
+        text: "It seems a paradox, does it not,".to_string(),
+        style: Style::default(),
+      }],
+    }
+  );
+  assert_eq!(
+    subtitles.cues[2].spans,
+    vec![Span {
+      text: "It is puzzling, why is it\nwe do not see things upside-down?".to_string(),
+      style: Style {
+        color: Some("yellow".to_string()),
+        ..Default::default()
+      },
+    }]
+  );
+}
+
+#[test]
+fn parse_ttml_time_expressions() {
+  let contents = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:frameRate="25" ttp:tickRate="10000000">
+  <body><div begin="10s">
+    <p begin="00:00:01:05" dur="40f">Hello <span>world</span></p>
+    <p begin="25000000t" end="00:00:03.500">Bye</p>
+  </div></body>
+</tt>"#;
+  let subtitles = parse(contents).unwrap();
+  let is_close = |value: f64, expected: f64| (value - expected).abs() < 1e-9;
+
+  assert!(is_close(subtitles.cues[0].start, 11.2));
+  assert!(is_close(subtitles.cues[0].end, 12.8));
+  assert_eq!(subtitles.cues[0].spans[0].text, "Hello world");
+  assert!(is_close(subtitles.cues[1].start, 12.5));
+  assert!(is_close(subtitles.cues[1].end, 13.5));
+
+  assert!(parse("<smil><body /></smil>").is_err());
+  assert!(parse("<tt><body><p begin=\"1s\">Hello</p></body></tt>").is_err());
+  assert!(parse("<tt><body><p begin=\"one\" end=\"2s\">Hello</p></body></tt>").is_err());
+}
+
+#[test]
+fn parse_ttml_styles_and_regions() {
+  let contents = r##"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling" tts:extent="1920px 1080px">
+  <head>
+    <styling>
+      <style xml:id="italic" tts:fontStyle="italic"/>
+      <style xml:id="emphasis" style="italic" tts:fontWeight="bold" tts:color="#FFFF00"/>
+    </styling>
+    <layout>
+      <region xml:id="bottom" tts:origin="192px 864px" tts:extent="1536px 108px" tts:displayAlign="after" tts:backgroundColor="black">
+        <style tts:textAlign="center"/>
+      </region>
+    </layout>
+  </head>
+  <body region="bottom">
+    <div>
+      <p begin="1s" end="2s">
+        Plain <span style="emphasis">loud</span>
+        <br/> <span tts:backgroundColor="black" tts:textDecoration="underline">under</span>
+      </p>
+    </div>
+  </body>
+</tt>"##;
+  let subtitles = parse(contents).unwrap();
+  let cue = &subtitles.cues[0];
+
+  assert_eq!(cue.align, Some(TextAlign::Center));
+  assert_eq!(
+    cue.area,
+    Some(Area {
+      origin: (10.0, 80.0),
+      extent: (80.0, 10.0),
+      display_align: DisplayAlign::After,
+    })
+  );
+  assert_eq!(
+    cue.spans,
+    vec![
+      Span {
+        text: "Plain ".to_string(),
+        style: Style::default(),
+      },
+      Span {
+        text: "loud".to_string(),
+        style: Style {
+          color: Some("#FFFF00".to_string()),
+          italic: true,
+          bold: true,
+          ..Default::default()
+        },
+      },
+      Span {
+        text: "\n".to_string(),
+        style: Style::default(),
+      },
+      Span {
+        text: "under".to_string(),
+        style: Style {
+          background_color: Some("black".to_string()),
+          underline: true,
+          ..Default::default()
+        },
+      },
+    ]
+  );
+
+  // the styles and regions are written back
+  assert_eq!(parse(&serialize(&subtitles)).unwrap(), subtitles);
+}
+
+#[test]
+fn retime_ttml_document() {
+  let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" xmlns:tts="http://www.w3.org/ns/ttml#styling" ttp:profile="http://www.w3.org/ns/ttml/profile/imsc1/text" ttp:frameRate="25">
+  <head>
+    <metadata><ttm:title xmlns:ttm="http://www.w3.org/ns/ttml#metadata" begin="1s">Title</ttm:title></metadata>
+    <styling><style xml:id="s1" tts:fontSize="80%" tts:fontFamily="monospaceSansSerif"/></styling>
+  </head>
+  <body style="s1">
+    <!-- cues -->
+    <div>
+      <p begin="00:00:01:00" end="00:00:02:00">Gone</p>
+      <p begin="1s" end="5s">Cut</p>
+      <p begin="4s" dur="2s"><span begin="1s">Shifted</span></p>
+    </div>
+    <div begin="10s"><p begin="1s" end="2s">Nested</p></div>
+  </body>
+</tt>
+"#;
+  let retiming = Retiming {
+    offset: -2.0,
+    scale: 1.0,
+  };
+  let retimed = retime(contents, &retiming).unwrap();
+
+  assert_eq!(
+    retimed,
+    contents
+      .replace(
+        "\n      <p begin=\"00:00:01:00\" end=\"00:00:02:00\">Gone</p>",
+        ""
+      )
+      .replace(
+        r#"<p begin="1s" end="5s">"#,
+        r#"<p begin="00:00:00.000" end="00:00:03.000">"#
+      )
+      .replace(
+        r#"<p begin="4s" dur="2s">"#,
+        r#"<p begin="00:00:02.000" dur="00:00:02.000">"#
+      )
+      .replace(r#"<span begin="1s">"#, r#"<span begin="00:00:01.000">"#)
+      .replace(r#"<div begin="10s">"#, r#"<div begin="00:00:08.000">"#)
+      .replace(
+        r#"<p begin="1s" end="2s">Nested"#,
+        r#"<p begin="00:00:01.000" end="00:00:02.000">Nested"#
+      )
+  );
+
+  // the children of a cut element are shifted back
+  let contents = r#"<tt xmlns="http://www.w3.org/ns/ttml"><body><div begin="1s" dur="4s"><p begin="2s" end="3s">Hello</p></div></body></tt>"#;
+  let retimed = retime(contents, &retiming).unwrap();
+  assert_eq!(
+    retimed,
+    r#"<tt xmlns="http://www.w3.org/ns/ttml"><body><div begin="00:00:00.000" dur="00:00:03.000"><p begin="00:00:01.000" end="00:00:02.000">Hello</p></div></body></tt>"#
+  );
+
+  let retiming = Retiming {
+    offset: 0.0,
+    scale: 2.0,
+  };
+  let retimed = retime(contents, &retiming).unwrap();
+  assert!(retimed.contains(
+    r#"<div begin="00:00:02.000" dur="00:00:08.000"><p begin="00:00:04.000" end="00:00:06.000">"#
+  ));
+  assert!(retime("<tt><body>", &retiming).is_err());
+}
+
+#[test]
+fn detect_ttml_profiles() {
+  // no profile is guessed from a document declaring none
+  let contents = std::fs::read_to_string("tests/sample_subtitle.ttml").unwrap();
+  assert_eq!(detect_profile(&contents), Ok(None));
+
+  let contents = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:profile="http://www.w3.org/ns/ttml/profile/imsc1/image"><body /></tt>"#;
+  assert_eq!(
+    detect_profile(contents).unwrap().unwrap().get_codecs(),
+    "im1i"
+  );
+
+  let contents = r##"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:smpte="http://www.smpte-ra.org/schemas/2052-1/2010/smpte-tt">
+  <body><div smpte:backgroundImage="#image1" /></body>
+</tt>"##;
+  assert_eq!(detect_profile(contents), Ok(None));
+
+  let contents = r##"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:smpte="http://www.smpte-ra.org/schemas/2052-1/2010/smpte-tt" xmlns:itts="http://www.w3.org/ns/ttml/profile/imsc1#styling">
+  <body><div smpte:backgroundImage="#image1" itts:forcedDisplay="true" /></body>
+</tt>"##;
+  assert_eq!(detect_profile(contents), Ok(Some(Profile::ImscImage)));
+
+  let contents = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:itts="http://www.w3.org/ns/ttml/profile/imsc1#styling">
+  <body><div><p itts:fillLineGap="true">Hello</p></div></body>
+</tt>"#;
+  assert_eq!(detect_profile(contents), Ok(Some(Profile::ImscText)));
+
+  let contents = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ebuttm="urn:ebu:tt:metadata">
+  <head><metadata><ebuttm:documentMetadata>
+    <ebuttm:conformsToStandard>urn:ebu:tt:distribution:2014-01</ebuttm:conformsToStandard>
+  </ebuttm:documentMetadata></metadata></head>
+  <body />
+</tt>"#;
+  assert_eq!(detect_profile(contents), Ok(Some(Profile::EbuTtD)));
+  assert_eq!(Profile::EbuTtD.get_codecs(), "im1t");
+
+  let contents = r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:profile="http://www.w3.org/ns/ttml/profile/dfxp-full"><body /></tt>"#;
+  assert!(detect_profile(contents).is_err());
+  assert!(detect_profile("<tt><body /></tt>").is_err());
+  assert!(detect_profile(r#"<tt xmlns="http://www.w3.org/ns/ttml"><body>"#).is_err());
+  assert!(detect_profile("WEBVTT\n").is_err());
+}
@@ -0,0 +1,467 @@
+use crate::subtitle::{
+  format_number, get_color_name, Area, Cue, DisplayAlign, Span, Style, Subtitles, TextAlign,
+};
+use crate::utils::{split_once, strip_prefix, strip_suffix};
+
+pub fn parse(contents: &str) -> Result<Subtitles, String> {
+  Ok(parse_with_warnings(contents)?.0)
+}
+
+/// Cues of the file, with warnings about the styles, regions, settings and voices they do not keep
+pub fn parse_with_warnings(contents: &str) -> Result<(Subtitles, Vec<String>), String> {
+  let contents = contents
+    .trim_start_matches('\u{feff}')
+    .replace("\r\n", "\n");
+  let mut blocks = contents.split("\n\n").map(|block| block.trim_matches('\n'));
+
+  // the header holds the signature and the metadata
+  let header = blocks.next().unwrap_or_default();
+  if !header.starts_with("WEBVTT") {
+    return Err("missing WEBVTT signature".to_string());
+  }
+
+  let mut subtitles = Subtitles {
+    language: header
+      .lines()
+      .skip(1)
+      .filter_map(|line| split_once(line, ":"))
+      .find(|(key, _)| key.trim().eq_ignore_ascii_case("language"))
+      .map(|(_, language)| language.trim().to_string())
+      .filter(|language| !language.is_empty()),
+    ..Default::default()
+  };
+  let mut warnings = vec![];
+  let mut warn = |warning: String| {
+    if !warnings.contains(&warning) {
+      warnings.push(warning);
+    }
+  };
+  for block in blocks.filter(|block| !block.is_empty()) {
+    let mut lines: Vec<&str> = block.lines().collect();
+    let timing_index = match lines.iter().position(|line| line.contains("-->")) {
+      Some(timing_index) if timing_index < 2 => timing_index,
+      // comments, styles and regions
+      _ => {
+        for name in &["STYLE", "REGION"] {
+          if lines[0].trim() == *name {
+            warn(format!(
+              "the {} blocks of the WebVTT file are not kept",
+              name
+            ));
+          }
+        }
+        continue;
+      }
+    };
+    let text = lines.split_off(timing_index + 1).join("\n");
+
+    let (start, end) = split_once(lines[timing_index], "-->").unwrap_or_default();
+    // cue settings follow the end time
+    let mut settings = end.split_whitespace();
+    let end = settings.next().unwrap_or_default();
+    let get_setting = |name: &str| {
+      settings
+        .clone()
+        .filter_map(|setting| split_once(setting, ":"))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+    };
+    let area = get_area(
+      get_setting("line"),
+      get_setting("position"),
+      get_setting("size"),
+    );
+    for setting in settings.clone() {
+      let (name, value) = split_once(setting, ":").unwrap_or((setting, ""));
+      let is_kept = match name {
+        "align" => TextAlign::parse(value).is_some(),
+        // the area is only described by a percent line
+        "line" | "position" | "size" => area.is_some(),
+        _ => false,
+      };
+      if !is_kept {
+        warn(format!(
+          "the {} settings of the WebVTT cues are not kept",
+          name
+        ));
+      }
+    }
+    if text.contains("<v ") || text.contains("<v.") {
+      warn("the voice names of the WebVTT cues are not kept".to_string());
+    }
+
+    subtitles.cues.push(Cue {
+      id: lines
+        .first()
+        .filter(|_| timing_index == 1)
+        .map(|id| id.to_string()),
+      start: parse_timestamp(start)?,
+      end: parse_timestamp(end)?,
+      align: get_setting("align").and_then(TextAlign::parse),
+      area,
+      spans: parse_text(&text),
+    });
+  }
+  Ok((subtitles, warnings))
+}
+
+pub fn serialize(subtitles: &Subtitles) -> String {
+  let mut contents = "WEBVTT\n".to_string();
+  if let Some(language) = &subtitles.language {
+    contents.push_str(&format!("Language: {}\n", language));
+  }
+  for cue in &subtitles.cues {
+    contents.push('\n');
+    if let Some(id) = &cue.id {
+      contents.push_str(&format!("{}\n", id));
+    }
+    contents.push_str(&format!(
+      "{} --> {}",
+      format_timestamp(cue.start),
+      format_timestamp(cue.end)
+    ));
+    if let Some(align) = &cue.align {
+      contents.push_str(&format!(" align:{}", align.as_str()));
+    }
+    if let Some(area) = &cue.area {
+      contents.push_str(&get_area_settings(area));
+    }
+
+    let text: String = cue.spans.iter().map(serialize_span).collect();
+    // blank lines would end the cue
+    let lines: Vec<&str> = text.lines().filter(|line| !line.is_empty()).collect();
+    contents.push_str(&format!("\n{}\n", lines.join("\n")));
+  }
+  contents
+}
+
+/// Area of the cue box, only given by percent line settings
+fn get_area(line: Option<&str>, position: Option<&str>, size: Option<&str>) -> Option<Area> {
+  let parse_percent = |value: &str| strip_suffix(value, "%")?.parse::<f64>().ok();
+
+  let (line, line_align) = split_once(line?, ",").unwrap_or((line?, "start"));
+  let line = parse_percent(line)?;
+  let (display_align, y, height) = match line_align {
+    "center" => {
+      let height = 2.0 * line.min(100.0 - line);
+      (DisplayAlign::Center, line - height / 2.0, height)
+    }
+    "end" => (DisplayAlign::After, 0.0, line),
+    _ => (DisplayAlign::Before, line, 100.0 - line),
+  };
+
+  let width = size.and_then(parse_percent).unwrap_or(100.0);
+  let x = match position.map(|position| split_once(position, ",").unwrap_or((position, "center"))) {
+    Some((position, position_align)) => {
+      let position = parse_percent(position)?;
+      match position_align {
+        "line-left" => position,
+        "line-right" => position - width,
+        _ => position - width / 2.0,
+      }
+    }
+    None => (100.0 - width) / 2.0,
+  };
+
+  Some(Area {
+    origin: (x, y),
+    extent: (width, height),
+    display_align,
+  })
+}
+
+fn get_area_settings(area: &Area) -> String {
+  let (x, y) = area.origin;
+  let (width, height) = area.extent;
+  let line = match area.display_align {
+    DisplayAlign::Before => format!("{}%", format_number(y)),
+    DisplayAlign::Center => format!("{}%,center", format_number(y + height / 2.0)),
+    DisplayAlign::After => format!("{}%,end", format_number(y + height)),
+  };
+  format!(
+    " line:{} position:{}%,line-left size:{}%",
+    line,
+    format_number(x),
+    format_number(width)
+  )
+}
+
+/// Styled spans of the cue text, from its b, i, u and c tags
+fn parse_text(text: &str) -> Vec<Span> {
+  let mut spans: Vec<Span> = vec![];
+  // open tags with their style
+  let mut tags: Vec<(String, Style)> = vec![];
+  let mut remaining = text;
+
+  while !remaining.is_empty() {
+    let style = tags
+      .last()
+      .map(|(_, style)| style.clone())
+      .unwrap_or_default();
+    let (text, tag) = match remaining.find('<') {
+      Some(index) => {
+        let end = remaining[index..]
+          .find('>')
+          .map(|end| index + end)
+          .unwrap_or(remaining.len());
+        let tag = remaining.get(index + 1..end).unwrap_or_default();
+        let text = &remaining[..index];
+        remaining = remaining.get(end + 1..).unwrap_or_default();
+        (text, Some(tag))
+      }
+      None => {
+        let text = remaining;
+        remaining = "";
+        (text, None)
+      }
+    };
+
+    if !text.is_empty() {
+      let text = decode_entities(text);
+      match spans.last_mut() {
+        Some(span) if span.style == style => span.text.push_str(&text),
+        _ => spans.push(Span {
+          text,
+          style: style.clone(),
+        }),
+      }
+    }
+
+    let tag = match tag {
+      Some(tag) => tag,
+      None => continue,
+    };
+    if let Some(name) = strip_prefix(tag, "/") {
+      if let Some(index) = tags
+        .iter()
+        .rposition(|(tag_name, _)| tag_name == name.trim())
+      {
+        tags.truncate(index);
+      }
+      continue;
+    }
+    // timestamps of the karaoke cues
+    if tag.starts_with(|c: char| c.is_ascii_digit()) {
+      continue;
+    }
+
+    let tag = tag.split_whitespace().next().unwrap_or_default();
+    let mut classes = tag.split('.');
+    let name = classes.next().unwrap_or_default().to_string();
+    let mut style = style;
+    match name.as_str() {
+      "b" => style.bold = true,
+      "i" => style.italic = true,
+      "u" => style.underline = true,
+      _ => {}
+    }
+    for class in classes {
+      if let Some(color) = strip_prefix(class, "bg_").and_then(get_color_name) {
+        style.background_color = Some(color.to_string());
+      } else if let Some(color) = get_color_name(class) {
+        style.color = Some(color.to_string()).filter(|color| color != "white");
+      }
+    }
+    tags.push((name, style));
+  }
+  spans
+}
+
+fn serialize_span(span: &Span) -> String {
+  let style = &span.style;
+  let mut text = span
+    .text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;");
+
+  if style.underline {
+    text = format!("<u>{}</u>", text);
+  }
+  if style.italic {
+    text = format!("<i>{}</i>", text);
+  }
+  if style.bold {
+    text = format!("<b>{}</b>", text);
+  }
+  // only the colors of the default classes are kept
+  let mut classes = String::new();
+  if let Some(color) = style.color.as_deref().and_then(get_color_name) {
+    classes.push_str(&format!(".{}", color));
+  }
+  if let Some(color) = style.background_color.as_deref().and_then(get_color_name) {
+    classes.push_str(&format!(".bg_{}", color));
+  }
+  if !classes.is_empty() {
+    text = format!("<c{}>{}</c>", classes, text);
+  }
+  text
+}
+
+fn decode_entities(text: &str) -> String {
+  text
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&nbsp;", "\u{a0}")
+    .replace("&lrm;", "\u{200e}")
+    .replace("&rlm;", "\u{200f}")
+    .replace("&amp;", "&")
+}
+
+/// Timestamp as [hh:]mm:ss.ttt
+fn parse_timestamp(timestamp: &str) -> Result<f64, String> {
+  let timestamp = timestamp.trim();
+  let error = || format!("invalid timestamp: {}", timestamp);
+
+  let mut seconds = 0.0;
+  for part in timestamp.split(':') {
+    let value: f64 = part.parse().map_err(|_| error())?;
+    seconds = seconds * 60.0 + value;
+  }
+  if !(2..=3).contains(&timestamp.split(':').count()) {
+    return Err(error());
+  }
+  Ok(seconds)
+}
+
+fn format_timestamp(seconds: f64) -> String {
+  let milliseconds = (seconds * 1000.0).round() as u64;
+  format!(
+    "{:02}:{:02}:{:02}.{:03}",
+    milliseconds / 3_600_000,
+    milliseconds / 60_000 % 60,
+    milliseconds / 1000 % 60,
+    milliseconds % 1000
+  )
+}
+
+#[test]
+fn parse_webvtt_cues() {
+  let contents = std::fs::read_to_string("tests/sample_subtitle.vtt").unwrap();
+  let subtitles = parse(&contents).unwrap();
+
+  assert_eq!(
+    subtitles.cues[1],
+    Cue {
+      id: Some("2".to_string()),
+      start: 5.0,
+      end: 10.0,
+      align: None,
+      area: None,
+      spans: vec![Span {
+        text: "that the image formed on\nthe Retina should be inverted?".to_string(),
+        style: Style::default(),
+      }],
+    }
+  );
+  assert_eq!(serialize(&subtitles), contents);
+
+  let subtitles =
+    parse("WEBVTT\n\nNOTE comment\n\n01:02.500 --> 01:04.000 align:start\nHello\n").unwrap();
+  assert_eq!(subtitles.cues.len(), 1);
+  assert_eq!(subtitles.cues[0].id, None);
+  assert_eq!(subtitles.cues[0].start, 62.5);
+  assert_eq!(subtitles.cues[0].end, 64.0);
+  assert_eq!(subtitles.cues[0].align, Some(TextAlign::Start));
+
+  let subtitles =
+    parse("WEBVTT\nKind: captions\nLanguage: es\n\n00:01.000 --> 00:02.000\nHola\n").unwrap();
+  assert_eq!(subtitles.language, Some("es".to_string()));
+  assert!(serialize(&subtitles).starts_with("WEBVTT\nLanguage: es\n\n"));
+
+  assert!(parse("1\n00:00:01.000 --> 00:00:02.000\nHello\n").is_err());
+  assert!(parse("WEBVTT\n\n00:01.000 --> later\nHello\n").is_err());
+}
+
+#[test]
+fn parse_webvtt_settings_and_tags() {
+  let contents = "WEBVTT
+
+00:00:01.000 --> 00:00:02.000 align:center line:90%,end position:10%,line-left size:80%
+<v Narrator><c.yellow.bg_black>Tom &amp; <b>Jerry</b></c></v>
+<i>again</i>
+";
+  let subtitles = parse(contents).unwrap();
+  let cue = &subtitles.cues[0];
+
+  assert_eq!(
+    cue.area,
+    Some(Area {
+      origin: (10.0, 0.0),
+      extent: (80.0, 90.0),
+      display_align: DisplayAlign::After,
+    })
+  );
+  let colored = Style {
+    color: Some("yellow".to_string()),
+    background_color: Some("black".to_string()),
+    ..Default::default()
+  };
+  assert_eq!(
+    cue.spans,
+    vec![
+      Span {
+        text: "Tom & ".to_string(),
+        style: colored.clone(),
+      },
+      Span {
+        text: "Jerry".to_string(),
+        style: Style {
+          bold: true,
+          ..colored
+        },
+      },
+      Span {
+        text: "\n".to_string(),
+        style: Style::default(),
+      },
+      Span {
+        text: "again".to_string(),
+        style: Style {
+          italic: true,
+          ..Default::default()
+        },
+      },
+    ]
+  );
+
+  assert_eq!(
+    serialize(&subtitles),
+    "WEBVTT
+
+00:00:01.000 --> 00:00:02.000 align:center line:90%,end position:10%,line-left size:80%
+<c.yellow.bg_black>Tom &amp; </c><c.yellow.bg_black><b>Jerry</b></c>
+<i>again</i>
+"
+  );
+
+  let (_, warnings) = parse_with_warnings(contents).unwrap();
+  assert_eq!(
+    warnings,
+    vec!["the voice names of the WebVTT cues are not kept"]
+  );
+
+  let contents = "WEBVTT
+
+STYLE
+::cue { color: yellow }
+
+REGION
+id:top
+
+00:00:01.000 --> 00:00:02.000 vertical:rl line:0 region:top
+Hello
+";
+  let (subtitles, warnings) = parse_with_warnings(contents).unwrap();
+  assert_eq!(subtitles.cues[0].area, None);
+  assert_eq!(
+    warnings,
+    vec![
+      "the STYLE blocks of the WebVTT file are not kept",
+      "the REGION blocks of the WebVTT file are not kept",
+      "the vertical settings of the WebVTT cues are not kept",
+      "the line settings of the WebVTT cues are not kept",
+      "the region settings of the WebVTT cues are not kept",
+    ]
+  );
+}
@@ -1,5 +1,289 @@
+use mcai_worker_sdk::{
+  job::{JobResult, JobStatus},
+  MessageError,
+};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use yaserde::{de::from_str, ser::to_string, YaDeserialize, YaSerialize};
+
+pub fn processing_error(job_result: &JobResult, message: &str) -> MessageError {
+  MessageError::ProcessingError(
+    job_result
+      .clone()
+      .with_status(JobStatus::Error)
+      .with_message(message),
+  )
+}
+
+/// Read and parse a text file, e.g. an HLS playlist
+pub fn load_file<T: FromStr<Err = String>>(
+  job_result: &JobResult,
+  path: &str,
+) -> Result<T, MessageError> {
+  read_file(job_result, path)?
+    .parse()
+    .map_err(|message: String| processing_error(job_result, &format!("{}: {}", path, message)))
+}
+
+/// Read and deserialize an XML file, e.g. a DASH manifest
+pub fn load_xml_file<T: YaDeserialize>(
+  job_result: &JobResult,
+  path: &str,
+) -> Result<T, MessageError> {
+  from_str(&read_file(job_result, path)?)
+    .map_err(|message| processing_error(job_result, &format!("{}: {}", path, message)))
+}
+
+pub fn save_file<T: ToString>(
+  job_result: &JobResult,
+  value: &T,
+  path: &str,
+) -> Result<(), MessageError> {
+  write_file(job_result, &value.to_string(), path)
+}
+
+pub fn save_xml_file<T: YaSerialize>(
+  job_result: &JobResult,
+  value: &T,
+  path: &str,
+) -> Result<(), MessageError> {
+  let contents = to_string(value).map_err(|message| processing_error(job_result, &message))?;
+  write_file(job_result, &contents, path)
+}
+
+fn read_file(job_result: &JobResult, path: &str) -> Result<String, MessageError> {
+  fs::read_to_string(path).map_err(|e| processing_error(job_result, &format!("{}: {}", path, e)))
+}
+
+fn write_file(job_result: &JobResult, contents: &str, path: &str) -> Result<(), MessageError> {
+  fs::write(path, contents).map_err(|e| processing_error(job_result, &format!("{}: {}", path, e)))
+}
+
 pub fn remove_duplicates(mut values: Vec<String>) -> Vec<String> {
   values.sort();
   values.dedup();
   values
 }
+
+/// Format seconds as an ISO 8601 duration (e.g. PT0H2M6.520S)
+pub fn format_duration(seconds: f64) -> String {
+  let milliseconds = (seconds * 1000.0).round() as u64;
+  format!(
+    "PT{}H{}M{}.{:03}S",
+    milliseconds / 3_600_000,
+    milliseconds / 60_000 % 60,
+    milliseconds / 1000 % 60,
+    milliseconds % 1000
+  )
+}
+
+/// Parse an ISO 8601 duration (e.g. PT0H2M6.520S) as seconds
+pub fn parse_duration(duration: &str) -> Option<f64> {
+  let mut seconds = 0.0;
+  let mut value = String::new();
+  let mut in_time = false;
+  if !duration.starts_with('P') {
+    return None;
+  }
+  for character in duration[1..].chars() {
+    let unit = match (character, in_time) {
+      ('0'..='9', _) | ('.', _) => {
+        value.push(character);
+        continue;
+      }
+      ('T', false) if value.is_empty() => {
+        in_time = true;
+        continue;
+      }
+      ('W', false) => 604_800.0,
+      ('D', false) => 86_400.0,
+      ('H', true) => 3_600.0,
+      ('M', true) => 60.0,
+      ('S', true) => 1.0,
+      _ => return None,
+    };
+    seconds += value.parse::<f64>().ok()? * unit;
+    value.clear();
+  }
+  if value.is_empty() {
+    Some(seconds)
+  } else {
+    None
+  }
+}
+
+#[test]
+fn iso_8601_durations() {
+  assert_eq!(format_duration(126.52), "PT0H2M6.520S");
+  assert_eq!(format_duration(3723.0), "PT1H2M3.000S");
+
+  assert_eq!(parse_duration("PT0H2M6.520S"), Some(126.52));
+  assert_eq!(parse_duration("PT1.5S"), Some(1.5));
+  assert_eq!(parse_duration("P1DT1H"), Some(90000.0));
+  assert_eq!(parse_duration("PT"), Some(0.0));
+  assert_eq!(parse_duration("2M6S"), None);
+  assert_eq!(parse_duration("P1Y"), None);
+  assert_eq!(parse_duration("PT6"), None);
+}
+
+/// Rest of the value after the prefix, if the value starts with it
+pub fn strip_prefix<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+  if value.starts_with(prefix) {
+    Some(&value[prefix.len()..])
+  } else {
+    None
+  }
+}
+
+/// Rest of the value before the suffix, if the value ends with it
+pub fn strip_suffix<'a>(value: &'a str, suffix: &str) -> Option<&'a str> {
+  if value.ends_with(suffix) {
+    Some(&value[..value.len() - suffix.len()])
+  } else {
+    None
+  }
+}
+
+/// Parts of the value before and after the first occurrence of the delimiter
+pub fn split_once<'a>(value: &'a str, delimiter: &str) -> Option<(&'a str, &'a str)> {
+  value
+    .find(delimiter)
+    .map(|index| (&value[..index], &value[index + delimiter.len()..]))
+}
+
+pub fn encode_base64(data: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+  let mut encoded = String::new();
+  for chunk in data.chunks(3) {
+    let bytes = [
+      chunk[0],
+      *chunk.get(1).unwrap_or(&0),
+      *chunk.get(2).unwrap_or(&0),
+    ];
+    let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+
+    for index in 0..4 {
+      if index <= chunk.len() {
+        encoded.push(ALPHABET[(value >> (18 - 6 * index) & 0x3f) as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+  encoded
+}
+
+#[test]
+fn base64_encoding() {
+  assert_eq!(encode_base64(b""), "");
+  assert_eq!(encode_base64(b"f"), "Zg==");
+  assert_eq!(encode_base64(b"fo"), "Zm8=");
+  assert_eq!(encode_base64(b"foo"), "Zm9v");
+  assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+}
+
+/// URL of a resource referenced by the source manifest, relative to the destination manifest folder
+pub fn rebase_url(
+  url: &str,
+  source_manifest_path: &str,
+  destination_manifest_path: &str,
+) -> String {
+  if url.contains("://") || url.starts_with('/') {
+    return url.to_string();
+  }
+
+  let source_folder = Path::new(source_manifest_path)
+    .parent()
+    .unwrap_or_else(|| Path::new(""));
+  let destination_folder = Path::new(destination_manifest_path)
+    .parent()
+    .unwrap_or_else(|| Path::new(""));
+  let source_path = source_folder.join(url);
+  let target = normalize_path(&source_path);
+  if source_folder.is_absolute() != destination_folder.is_absolute() {
+    return target
+      .iter()
+      .collect::<PathBuf>()
+      .to_string_lossy()
+      .to_string();
+  }
+  let destination = normalize_path(destination_folder);
+
+  let common_length = target
+    .iter()
+    .zip(destination.iter())
+    .take_while(|(target, destination)| target == destination)
+    .count();
+  let mut relative_path = PathBuf::new();
+  for _ in common_length..destination.len() {
+    relative_path.push("..");
+  }
+  for component in &target[common_length..] {
+    relative_path.push(component);
+  }
+  relative_path.to_string_lossy().to_string()
+}
+
+fn normalize_path(path: &Path) -> Vec<Component<'_>> {
+  let mut components = vec![];
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir if matches!(components.last(), Some(Component::Normal(_))) => {
+        components.pop();
+      }
+      component => components.push(component),
+    }
+  }
+  components
+}
+
+#[test]
+fn rebase_relative_urls() {
+  assert_eq!(
+    rebase_url(
+      "video/init.mp4",
+      "tests/intro/manifest.mpd",
+      "tests/manifest.mpd"
+    ),
+    "intro/video/init.mp4"
+  );
+  assert_eq!(
+    rebase_url(
+      "./audio.mp4",
+      "tests/intro/manifest.mpd",
+      "tests/outro/manifest.mpd"
+    ),
+    "../intro/audio.mp4"
+  );
+  assert_eq!(
+    rebase_url(
+      "../media/audio.mp4",
+      "tests/intro/manifest.mpd",
+      "tests/manifest.mpd"
+    ),
+    "media/audio.mp4"
+  );
+  assert_eq!(
+    rebase_url("audio.mp4", "manifest.mpd", "tests/manifest.mpd"),
+    "../audio.mp4"
+  );
+  assert_eq!(
+    rebase_url(
+      "audio.mp4",
+      "/data/intro/manifest.mpd",
+      "tests/manifest.mpd"
+    ),
+    "/data/intro/audio.mp4"
+  );
+  assert_eq!(
+    rebase_url(
+      "http://server/audio.mp4",
+      "tests/intro/manifest.mpd",
+      "manifest.mpd"
+    ),
+    "http://server/audio.mp4"
+  );
+}
@@ -0,0 +1,486 @@
+//! XML documents kept as written (comments, whitespaces and attribute order), to be edited in place
+
+use crate::utils::strip_prefix;
+use std::fmt;
+use xml::escape::escape_str_attribute;
+use xml::reader::EventReader;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Document {
+  nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+  Element(Element),
+  /// Character data, escaped as written
+  Text(String),
+  /// Comment, processing instruction, CDATA section or document type declaration, as written
+  Other(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+  name: String,
+  attributes: Vec<Attribute>,
+  /// Whitespaces closing the start tag
+  tag_end: String,
+  children: Vec<Node>,
+  is_empty: bool,
+  /// Whitespaces closing the end tag
+  end_tag_end: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Attribute {
+  /// Whitespaces before the attribute
+  spacing: String,
+  name: String,
+  /// Equal sign, with its surrounding whitespaces
+  separator: String,
+  quote: char,
+  /// Value, escaped as written
+  value: String,
+}
+
+impl Document {
+  pub fn parse(contents: &str) -> Result<Self, String> {
+    // the document is checked to be well-formed before being split into nodes
+    for event in EventReader::from_str(contents) {
+      event.map_err(|e| format!("invalid XML: {}", e))?;
+    }
+
+    let mut parser = Parser {
+      contents,
+      position: 0,
+    };
+    let mut stack: Vec<Element> = vec![];
+    let mut nodes = vec![];
+    while let Some(token) = parser.next_token()? {
+      let node = match token {
+        Token::Node(node) => node,
+        Token::Start(element) if element.is_empty => Node::Element(element),
+        Token::Start(element) => {
+          stack.push(element);
+          continue;
+        }
+        Token::End(end_tag_end) => {
+          let mut element = stack
+            .pop()
+            .ok_or_else(|| "invalid XML: unexpected end tag".to_string())?;
+          element.end_tag_end = end_tag_end;
+          Node::Element(element)
+        }
+      };
+      match stack.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => nodes.push(node),
+      }
+    }
+    Ok(Document { nodes })
+  }
+
+  pub fn root_mut(&mut self) -> Option<&mut Element> {
+    self.nodes.iter_mut().find_map(|node| match node {
+      Node::Element(element) => Some(element),
+      _ => None,
+    })
+  }
+}
+
+impl fmt::Display for Document {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for node in &self.nodes {
+      write!(f, "{}", node)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Node {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Node::Element(element) => write!(f, "{}", element),
+      Node::Text(text) | Node::Other(text) => write!(f, "{}", text),
+    }
+  }
+}
+
+impl Element {
+  pub fn new(name: &str) -> Self {
+    Element {
+      name: name.to_string(),
+      attributes: vec![],
+      tag_end: " ".to_string(),
+      children: vec![],
+      is_empty: true,
+      end_tag_end: "".to_string(),
+    }
+  }
+
+  /// Name without its namespace prefix
+  pub fn local_name(&self) -> &str {
+    local_name(&self.name)
+  }
+
+  /// Unescaped value of the attribute, by qualified name
+  pub fn get_attribute(&self, name: &str) -> Option<String> {
+    self
+      .attributes
+      .iter()
+      .find(|attribute| attribute.name == name)
+      .map(|attribute| unescape(&attribute.value))
+  }
+
+  /// Unescaped value of the attribute, by name without its namespace prefix
+  pub fn get_attribute_by_local_name(&self, name: &str) -> Option<String> {
+    self
+      .attributes
+      .iter()
+      .find(|attribute| local_name(&attribute.name) == name)
+      .map(|attribute| unescape(&attribute.value))
+  }
+
+  /// Replace the value of the attribute, or append the attribute
+  pub fn set_attribute(&mut self, name: &str, value: &str) {
+    let value = escape_str_attribute(value).to_string();
+    match self
+      .attributes
+      .iter_mut()
+      .find(|attribute| attribute.name == name)
+    {
+      Some(attribute) => {
+        attribute.value = match attribute.quote {
+          '\'' => value.replace("&quot;", "\"").replace('\'', "&apos;"),
+          _ => value,
+        }
+      }
+      None => self.attributes.push(Attribute {
+        spacing: " ".to_string(),
+        name: name.to_string(),
+        separator: "=".to_string(),
+        quote: '"',
+        value,
+      }),
+    }
+  }
+
+  pub fn elements(&self) -> impl Iterator<Item = &Element> {
+    self.children.iter().filter_map(|node| match node {
+      Node::Element(element) => Some(element),
+      _ => None,
+    })
+  }
+
+  pub fn elements_mut(&mut self) -> impl Iterator<Item = &mut Element> {
+    self.children.iter_mut().filter_map(|node| match node {
+      Node::Element(element) => Some(element),
+      _ => None,
+    })
+  }
+
+  /// First child element with the local name
+  pub fn find_mut(&mut self, local_name: &str) -> Option<&mut Element> {
+    self
+      .elements_mut()
+      .find(|element| element.local_name() == local_name)
+  }
+
+  /// Whitespaces preceding the child elements
+  pub fn get_indentation(&self) -> Option<&str> {
+    self.children.windows(2).find_map(|nodes| match nodes {
+      [Node::Text(text), Node::Element(_)] if text.trim().is_empty() => Some(text.as_str()),
+      _ => None,
+    })
+  }
+
+  /// Append the child element on its own line, the end tag being indented with the end indentation
+  pub fn push_element(&mut self, element: Element, indentation: &str, end_indentation: &str) {
+    if let Some(Node::Text(text)) = self.children.last() {
+      if text.trim().is_empty() {
+        self.children.pop();
+      }
+    }
+    self.set_not_empty();
+    self.children.push(Node::Text(indentation.to_string()));
+    self.children.push(Node::Element(element));
+    self.children.push(Node::Text(end_indentation.to_string()));
+  }
+
+  /// Insert the child element before the one at the index among the child elements, indented as them
+  pub fn insert_element(&mut self, index: usize, element: Element) {
+    let indentation = self.get_indentation().unwrap_or("").to_string();
+    let positions: Vec<usize> = self
+      .children
+      .iter()
+      .enumerate()
+      .filter(|(_, node)| matches!(node, Node::Element(_)))
+      .map(|(position, _)| position)
+      .collect();
+
+    match (positions.get(index), positions.last()) {
+      (Some(&position), _) => {
+        let nodes = vec![Node::Element(element), Node::Text(indentation)];
+        self.children.splice(position..position, nodes);
+      }
+      (None, Some(&position)) => {
+        let nodes = vec![Node::Text(indentation), Node::Element(element)];
+        self.children.splice(position + 1..position + 1, nodes);
+      }
+      (None, None) => {
+        self.set_not_empty();
+        self.children.push(Node::Element(element));
+      }
+    }
+  }
+
+  /// Write a start and an end tag, instead of an empty element tag
+  fn set_not_empty(&mut self) {
+    if self.is_empty {
+      self.is_empty = false;
+      self.tag_end.clear();
+    }
+  }
+
+  /// Keep the child elements matching the predicate, with the whitespaces preceding them
+  pub fn retain_elements<F: FnMut(&Element) -> bool>(&mut self, mut predicate: F) {
+    let mut children: Vec<Node> = vec![];
+    for node in self.children.drain(..) {
+      match node {
+        Node::Element(element) if !predicate(&element) => {
+          if let Some(Node::Text(text)) = children.last() {
+            if text.trim().is_empty() {
+              children.pop();
+            }
+          }
+        }
+        node => children.push(node),
+      }
+    }
+    self.children = children;
+  }
+}
+
+impl fmt::Display for Element {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "<{}", self.name)?;
+    for attribute in &self.attributes {
+      write!(
+        f,
+        "{}{}{}{}{}{}",
+        attribute.spacing,
+        attribute.name,
+        attribute.separator,
+        attribute.quote,
+        attribute.value,
+        attribute.quote
+      )?;
+    }
+    if self.is_empty && self.children.is_empty() {
+      return write!(f, "{}/>", self.tag_end);
+    }
+
+    write!(f, "{}>", self.tag_end)?;
+    for node in &self.children {
+      write!(f, "{}", node)?;
+    }
+    write!(f, "</{}{}>", self.name, self.end_tag_end)
+  }
+}
+
+enum Token {
+  Node(Node),
+  Start(Element),
+  /// Whitespaces closing the end tag
+  End(String),
+}
+
+struct Parser<'a> {
+  contents: &'a str,
+  position: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn next_token(&mut self) -> Result<Option<Token>, String> {
+    let remaining = &self.contents[self.position..];
+    if remaining.is_empty() {
+      return Ok(None);
+    }
+
+    if !remaining.starts_with('<') {
+      let length = remaining.find('<').unwrap_or(remaining.len());
+      self.position += length;
+      return Ok(Some(Token::Node(Node::Text(
+        remaining[..length].to_string(),
+      ))));
+    }
+
+    let delimiters = [("<!--", "-->"), ("<![CDATA[", "]]>"), ("<?", "?>")];
+    for (start, end) in delimiters.iter() {
+      if let Some(contents) = strip_prefix(remaining, start) {
+        let length = contents
+          .find(end)
+          .map(|index| start.len() + index + end.len())
+          .ok_or_else(|| format!("invalid XML: unterminated {}", start))?;
+        return Ok(Some(Token::Node(Node::Other(self.take(length)))));
+      }
+    }
+
+    if remaining.starts_with("<!") {
+      // the document type declaration may have an internal subset
+      let mut depth = 0;
+      for (index, character) in remaining.char_indices() {
+        match character {
+          '[' => depth += 1,
+          ']' => depth -= 1,
+          '>' if depth == 0 => return Ok(Some(Token::Node(Node::Other(self.take(index + 1))))),
+          _ => {}
+        }
+      }
+      return Err("invalid XML: unterminated declaration".to_string());
+    }
+
+    if remaining.starts_with("</") {
+      let length = remaining
+        .find('>')
+        .ok_or_else(|| "invalid XML: unterminated end tag".to_string())?;
+      let tag = self.take(length + 1);
+      let name_end = tag[2..]
+        .find(|character: char| character.is_whitespace() || character == '>')
+        .map(|index| index + 2)
+        .unwrap_or(length);
+      return Ok(Some(Token::End(tag[name_end..length].to_string())));
+    }
+
+    self.position += 1;
+    let name = self
+      .take_while(|character| !character.is_whitespace() && character != '>' && character != '/');
+    let mut element = Element::new(&name);
+    loop {
+      let spacing = self.take_while(char::is_whitespace);
+      let remaining = &self.contents[self.position..];
+      if remaining.starts_with("/>") {
+        self.position += 2;
+        element.tag_end = spacing;
+        return Ok(Some(Token::Start(element)));
+      }
+      if remaining.starts_with('>') {
+        self.position += 1;
+        element.tag_end = spacing;
+        element.is_empty = false;
+        return Ok(Some(Token::Start(element)));
+      }
+
+      let name = self.take_while(|character| !character.is_whitespace() && character != '=');
+      let separator = self.take_while(|character| character.is_whitespace() || character == '=');
+      let quote = self.contents[self.position..]
+        .chars()
+        .next()
+        .filter(|quote| *quote == '"' || *quote == '\'')
+        .ok_or_else(|| format!("invalid XML: unquoted {} attribute", name))?;
+      self.position += 1;
+      let value = self.take_while(|character| character != quote);
+      self.position += 1;
+      element.attributes.push(Attribute {
+        spacing,
+        name,
+        separator,
+        quote,
+        value,
+      });
+    }
+  }
+
+  fn take(&mut self, length: usize) -> String {
+    let value = self.contents[self.position..self.position + length].to_string();
+    self.position += length;
+    value
+  }
+
+  fn take_while<P: Fn(char) -> bool>(&mut self, predicate: P) -> String {
+    let remaining = &self.contents[self.position..];
+    let length = remaining
+      .find(|character| !predicate(character))
+      .unwrap_or(remaining.len());
+    self.take(length)
+  }
+}
+
+fn local_name(name: &str) -> &str {
+  name.rsplit(':').next().unwrap_or(name)
+}
+
+fn unescape(value: &str) -> String {
+  let mut result = String::new();
+  let mut remaining = value;
+  while let Some(start) = remaining.find('&') {
+    result.push_str(&remaining[..start]);
+    remaining = &remaining[start..];
+    let end = match remaining.find(';') {
+      Some(end) => end,
+      None => break,
+    };
+    let reference = &remaining[1..end];
+    let character = match reference {
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "amp" => Some('&'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      _ => strip_prefix(reference, "#x")
+        .map(|code| u32::from_str_radix(code, 16))
+        .or_else(|| strip_prefix(reference, "#").map(|code| code.parse()))
+        .and_then(Result::ok)
+        .and_then(std::char::from_u32),
+    };
+    match character {
+      Some(character) => result.push(character),
+      None => result.push_str(&remaining[..=end]),
+    }
+    remaining = &remaining[end + 1..];
+  }
+  result.push_str(remaining);
+  result
+}
+
+#[test]
+fn edit_xml_document() {
+  let contents = std::fs::read_to_string("tests/sample.ism").unwrap();
+  let mut document = Document::parse(&contents).unwrap();
+  assert_eq!(document.to_string(), contents);
+
+  let root = document.root_mut().unwrap();
+  assert_eq!(root.local_name(), "smil");
+  let body = root.find_mut("body").unwrap();
+  let switch = body.find_mut("switch").unwrap();
+  switch.retain_elements(|element| element.local_name() != "video");
+  let audio = switch.find_mut("audio").unwrap();
+  assert_eq!(
+    audio.get_attribute("src"),
+    Some("test_file.isma".to_string())
+  );
+  audio.set_attribute("systemLanguage", "a&b");
+  audio.set_attribute("systemBitrate", "96000");
+
+  let contents = document.to_string();
+  assert!(contents
+    .contains(r#"<audio src="test_file.isma" systemBitrate="96000" systemLanguage="a&amp;b">"#));
+  assert!(contents.contains("    </textstream>\n        </switch>"));
+  assert!(!contents.contains("<video"));
+  assert!(contents.starts_with(
+    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!-- Created with Unified Streaming Platform(version=1.8.3) -->\n"
+  ));
+
+  let mut document = Document::parse(r#"<a b='x &amp; &#233;' ><c/><d></d ></a>"#).unwrap();
+  let root = document.root_mut().unwrap();
+  assert_eq!(root.get_attribute("b"), Some("x & é".to_string()));
+  root.insert_element(1, Element::new("e"));
+  let mut f = Element::new("f");
+  f.push_element(Element::new("g"), " ", "");
+  root.insert_element(3, f);
+  assert_eq!(
+    root.to_string(),
+    r#"<a b='x &amp; &#233;' ><c/><e /><d></d ><f> <g /></f></a>"#
+  );
+  assert!(Document::parse("<a><b></a>").is_err());
+}